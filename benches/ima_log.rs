@@ -0,0 +1,155 @@
+// Compares the allocating and the borrowing parsers, and extending one event at a
+// time with the batch replay, on a synthetic IMA measurement list the size of a busy
+// server's.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use tpmless_tpm2::{
+    parse_ima_log, DigestAlgorithm, ImaEvent, PcrExtender, PcrExtenderBuilder, ReplayEvent, Tpm2b,
+    Tpm2bRef,
+};
+
+const EVENTS: usize = 100_000;
+
+fn ima_ng_event(index: usize) -> ImaEvent {
+    let mut template_data = Vec::new();
+    let mut digest = b"sha256:\0".to_vec();
+    digest.extend((0..32).map(|i| (index + i) as u8));
+    let path = format!("/usr/lib/modules/6.1.0/kernel/drivers/file-{}.ko\0", index);
+    for field in [&digest[..], path.as_bytes()] {
+        template_data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        template_data.extend_from_slice(field);
+    }
+
+    ImaEvent {
+        pcr: 10,
+        template_digest: (0..20).map(|i| (index * 7 + i) as u8).collect(),
+        template_name: "ima-ng".into(),
+        template_data,
+    }
+}
+
+fn ima_log() -> Vec<u8> {
+    let mut log = Vec::new();
+    for index in 0..EVENTS {
+        ima_ng_event(index).to_writer(&mut log).unwrap();
+    }
+    log
+}
+
+fn tpm2b_list() -> Vec<u8> {
+    let mut list = Vec::new();
+    for index in 0..EVENTS {
+        Tpm2b::new(vec![index as u8; 32 + index % 32])
+            .unwrap()
+            .to_writer(&mut list)
+            .unwrap();
+    }
+    list
+}
+
+fn bench_ima_log(c: &mut Criterion) {
+    let log = ima_log();
+    let mut group = c.benchmark_group("ima_log");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let mut reader = &log[..];
+            let mut count = 0;
+            while !reader.is_empty() {
+                black_box(ImaEvent::from_reader(&mut reader).unwrap());
+                count += 1;
+            }
+            assert_eq!(count, EVENTS);
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for event in parse_ima_log(&log) {
+                black_box(event.unwrap());
+                count += 1;
+            }
+            assert_eq!(count, EVENTS);
+        })
+    });
+    group.finish();
+}
+
+fn bench_tpm2b(c: &mut Criterion) {
+    let list = tpm2b_list();
+    let mut group = c.benchmark_group("tpm2b");
+    group.throughput(Throughput::Bytes(list.len() as u64));
+
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let mut reader = &list[..];
+            while !reader.is_empty() {
+                black_box(Tpm2b::from_reader(&mut reader).unwrap());
+            }
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut buf = &list[..];
+            while !buf.is_empty() {
+                black_box(Tpm2bRef::from_bytes(&mut buf).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+const BANKS: [DigestAlgorithm; 3] = [
+    DigestAlgorithm::Sha1,
+    DigestAlgorithm::Sha256,
+    DigestAlgorithm::Sha384,
+];
+
+fn extender() -> PcrExtender {
+    let mut builder = PcrExtenderBuilder::new();
+    for algo in BANKS {
+        builder.add_digest_method(algo);
+    }
+    builder.build()
+}
+
+fn bench_replay(c: &mut Criterion) {
+    let log = ima_log();
+    let events = parse_ima_log(&log)
+        .map(|event| {
+            event.map(|event| ReplayEvent::Data {
+                pcr: event.pcr,
+                data: event.template_data,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let extend = || {
+        let mut extender = extender();
+        for event in parse_ima_log(&log) {
+            let event = event.unwrap();
+            extender.extend(event.pcr, event.template_data).unwrap();
+        }
+        extender
+    };
+    let replay = || {
+        let mut extender = extender();
+        extender.replay(&events).unwrap();
+        extender
+    };
+    assert_eq!(extend().values(), replay().values());
+
+    let mut group = c.benchmark_group("replay");
+    group.sample_size(10);
+    group.bench_function("extend", |b| b.iter(|| black_box(extend())));
+    group.bench_function("batch", |b| b.iter(|| black_box(replay())));
+    group.finish();
+}
+
+criterion_group!(benches, bench_ima_log, bench_tpm2b, bench_replay);
+criterion_main!(benches);
@@ -0,0 +1,277 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    objects::{
+        EccCurve, EccScheme, ObjectAttributes, ObjectType, PublicParameters, RsaScheme,
+        SignatureScheme, TpmtPublic,
+    },
+    DigestAlgorithm,
+};
+
+/// A reason an AK is not accepted by an AkPolicy
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AkViolation {
+    /// Required attributes that are not set
+    MissingAttributes(ObjectAttributes),
+    /// Forbidden attributes that are set
+    ForbiddenAttributes(ObjectAttributes),
+    NameAlgNotAllowed(DigestAlgorithm),
+    /// The signing scheme, or None if the key has no signing scheme
+    SchemeNotAllowed(Option<SignatureScheme>),
+    KeyTypeNotAllowed(ObjectType),
+    RsaKeyTooSmall {
+        key_bits: u16,
+        minimum: u16,
+    },
+    CurveNotAllowed(EccCurve),
+    AuthPolicyMismatch,
+}
+
+/// A configurable policy for accepting attestation keys
+///
+/// The defaults follow the TCG guidance for AKs: a restricted signing key that was
+/// generated in the TPM and can not leave it, using SHA-256 or stronger, with RSA
+/// keys of at least 2048 bits or NIST P-256 and larger curves.
+#[derive(Debug, Clone)]
+pub struct AkPolicy {
+    required_attributes: ObjectAttributes,
+    forbidden_attributes: ObjectAttributes,
+    name_algs: Vec<DigestAlgorithm>,
+    schemes: Vec<SignatureScheme>,
+    min_rsa_key_bits: u16,
+    curves: Vec<EccCurve>,
+    auth_policy: Option<Vec<u8>>,
+}
+
+impl Default for AkPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AkPolicy {
+    pub fn new() -> Self {
+        let strong_hashes = [
+            DigestAlgorithm::Sha256,
+            DigestAlgorithm::Sha384,
+            DigestAlgorithm::Sha512,
+        ];
+
+        AkPolicy {
+            required_attributes: ObjectAttributes::FIXED_TPM
+                | ObjectAttributes::FIXED_PARENT
+                | ObjectAttributes::SENSITIVE_DATA_ORIGIN
+                | ObjectAttributes::RESTRICTED
+                | ObjectAttributes::SIGN_ENCRYPT,
+            forbidden_attributes: ObjectAttributes::DECRYPT,
+            name_algs: strong_hashes.to_vec(),
+            schemes: strong_hashes
+                .iter()
+                .flat_map(|hash| {
+                    vec![
+                        SignatureScheme::RsaSsa(*hash),
+                        SignatureScheme::RsaPss(*hash),
+                        SignatureScheme::EcDsa(*hash),
+                    ]
+                })
+                .collect(),
+            min_rsa_key_bits: 2048,
+            curves: vec![EccCurve::NistP256, EccCurve::NistP384, EccCurve::NistP521],
+            auth_policy: None,
+        }
+    }
+
+    pub fn set_required_attributes(&mut self, val: ObjectAttributes) -> &mut Self {
+        self.required_attributes = val;
+        self
+    }
+
+    pub fn set_forbidden_attributes(&mut self, val: ObjectAttributes) -> &mut Self {
+        self.forbidden_attributes = val;
+        self
+    }
+
+    pub fn set_allowed_name_algs(&mut self, val: &[DigestAlgorithm]) -> &mut Self {
+        self.name_algs = val.to_vec();
+        self
+    }
+
+    pub fn set_allowed_schemes(&mut self, val: &[SignatureScheme]) -> &mut Self {
+        self.schemes = val.to_vec();
+        self
+    }
+
+    pub fn set_min_rsa_key_bits(&mut self, val: u16) -> &mut Self {
+        self.min_rsa_key_bits = val;
+        self
+    }
+
+    /// Sets the allowed curves, where an empty list rejects all ECC keys
+    pub fn set_allowed_curves(&mut self, val: &[EccCurve]) -> &mut Self {
+        self.curves = val.to_vec();
+        self
+    }
+
+    /// Requires a specific authPolicy, which is not checked by default
+    pub fn set_auth_policy(&mut self, val: Option<&[u8]>) -> &mut Self {
+        self.auth_policy = val.map(|val| val.to_vec());
+        self
+    }
+
+    /// Evaluates the AK, returning all violations of the policy
+    ///
+    /// The AK is acceptable if the list is empty.
+    pub fn check(&self, ak: &TpmtPublic) -> Vec<AkViolation> {
+        let mut violations = Vec::new();
+        let attrs = ak.object_attributes;
+
+        let missing = ObjectAttributes::from_bits(self.required_attributes.bits() & !attrs.bits());
+        if missing != ObjectAttributes::empty() {
+            violations.push(AkViolation::MissingAttributes(missing));
+        }
+        let forbidden =
+            ObjectAttributes::from_bits(self.forbidden_attributes.bits() & attrs.bits());
+        if forbidden != ObjectAttributes::empty() {
+            violations.push(AkViolation::ForbiddenAttributes(forbidden));
+        }
+
+        if !self.name_algs.contains(&ak.name_alg) {
+            violations.push(AkViolation::NameAlgNotAllowed(ak.name_alg));
+        }
+
+        let scheme = match &ak.parameters {
+            PublicParameters::Rsa {
+                scheme, key_bits, ..
+            } => {
+                if *key_bits < self.min_rsa_key_bits {
+                    violations.push(AkViolation::RsaKeyTooSmall {
+                        key_bits: *key_bits,
+                        minimum: self.min_rsa_key_bits,
+                    });
+                }
+                match scheme {
+                    RsaScheme::RsaSsa(hash) => Some(SignatureScheme::RsaSsa(*hash)),
+                    RsaScheme::RsaPss(hash) => Some(SignatureScheme::RsaPss(*hash)),
+                    _ => None,
+                }
+            }
+            PublicParameters::Ecc { scheme, curve, .. } => {
+                if !self.curves.contains(curve) {
+                    violations.push(AkViolation::CurveNotAllowed(*curve));
+                }
+                match scheme {
+                    EccScheme::EcDsa(hash) => Some(SignatureScheme::EcDsa(*hash)),
+                    _ => None,
+                }
+            }
+            _ => {
+                violations.push(AkViolation::KeyTypeNotAllowed(ak.object_type()));
+                None
+            }
+        };
+        if ak.object_type() == ObjectType::Rsa || ak.object_type() == ObjectType::Ecc {
+            match scheme {
+                Some(scheme) if self.schemes.contains(&scheme) => {}
+                _ => violations.push(AkViolation::SchemeNotAllowed(scheme)),
+            }
+        }
+
+        if let Some(auth_policy) = &self.auth_policy {
+            if *auth_policy != ak.auth_policy {
+                violations.push(AkViolation::AuthPolicyMismatch);
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::PKey,
+        rsa::Rsa,
+    };
+
+    use super::*;
+
+    // The attributes tpm2_createak uses: fixedTPM | fixedParent | sensitiveDataOrigin |
+    // userWithAuth | restricted | sign
+    const AK_ATTRIBUTES: ObjectAttributes = ObjectAttributes::from_bits(0x0005_0072);
+
+    fn rsa_ak(key_bits: u32, scheme: RsaScheme) -> TpmtPublic {
+        let key = PKey::from_rsa(Rsa::generate(key_bits).unwrap()).unwrap();
+        let mut public =
+            TpmtPublic::from_public_key(&key, DigestAlgorithm::Sha256, AK_ATTRIBUTES).unwrap();
+        if let PublicParameters::Rsa { scheme: s, .. } = &mut public.parameters {
+            *s = scheme;
+        }
+        public
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = AkPolicy::new();
+
+        let ak = rsa_ak(2048, RsaScheme::RsaSsa(DigestAlgorithm::Sha256));
+        assert!(policy.check(&ak).is_empty());
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut ak =
+            TpmtPublic::from_public_key(&key, DigestAlgorithm::Sha256, AK_ATTRIBUTES).unwrap();
+        if let PublicParameters::Ecc { scheme, .. } = &mut ak.parameters {
+            *scheme = EccScheme::EcDsa(DigestAlgorithm::Sha256);
+        }
+        assert!(policy.check(&ak).is_empty());
+
+        let mut ak = rsa_ak(1024, RsaScheme::RsaSsa(DigestAlgorithm::Sha1));
+        ak.name_alg = DigestAlgorithm::Sha1;
+        ak.object_attributes.set(ObjectAttributes::FIXED_TPM, false);
+        ak.object_attributes |= ObjectAttributes::DECRYPT;
+        assert_eq!(
+            policy.check(&ak),
+            vec![
+                AkViolation::MissingAttributes(ObjectAttributes::FIXED_TPM),
+                AkViolation::ForbiddenAttributes(ObjectAttributes::DECRYPT),
+                AkViolation::NameAlgNotAllowed(DigestAlgorithm::Sha1),
+                AkViolation::RsaKeyTooSmall {
+                    key_bits: 1024,
+                    minimum: 2048
+                },
+                AkViolation::SchemeNotAllowed(Some(SignatureScheme::RsaSsa(DigestAlgorithm::Sha1))),
+            ]
+        );
+
+        let ak = rsa_ak(2048, RsaScheme::Null);
+        assert_eq!(policy.check(&ak), vec![AkViolation::SchemeNotAllowed(None)]);
+    }
+
+    #[test]
+    fn test_configured_policy() {
+        let mut policy = AkPolicy::new();
+        policy
+            .set_allowed_schemes(&[SignatureScheme::RsaPss(DigestAlgorithm::Sha256)])
+            .set_allowed_curves(&[])
+            .set_auth_policy(Some(&[0xAA; 32]));
+
+        let mut ak = rsa_ak(2048, RsaScheme::RsaPss(DigestAlgorithm::Sha256));
+        assert_eq!(policy.check(&ak), vec![AkViolation::AuthPolicyMismatch]);
+        ak.auth_policy = vec![0xAA; 32];
+        assert!(policy.check(&ak).is_empty());
+
+        let ak = rsa_ak(2048, RsaScheme::RsaSsa(DigestAlgorithm::Sha256));
+        assert_eq!(
+            policy.check(&ak),
+            vec![
+                AkViolation::SchemeNotAllowed(Some(SignatureScheme::RsaSsa(
+                    DigestAlgorithm::Sha256
+                ))),
+                AkViolation::AuthPolicyMismatch,
+            ]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use core::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use crate::{DigestAlgorithm, Error};
+
+/// What kind of algorithm an AlgorithmId identifies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AlgorithmCategory {
+    Hash,
+    Asymmetric,
+    Symmetric,
+    /// Signing, encryption, key exchange and key derivation schemes
+    Scheme,
+    /// Block cipher modes
+    Mode,
+    /// Object types that are not an algorithm themselves
+    ObjectType,
+}
+
+/// A TPM_ALG_ID
+///
+/// Unknown holds identifiers that are not in the registry. Unknown with a
+/// registered identifier compares equal to the registered algorithm.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum AlgorithmId {
+    Error,
+    Rsa,
+    Tdes,
+    Sha1,
+    Hmac,
+    Aes,
+    Mgf1,
+    KeyedHash,
+    Xor,
+    Sha256,
+    Sha384,
+    Sha512,
+    Null,
+    Sm3_256,
+    Sm4,
+    RsaSsa,
+    RsaEs,
+    RsaPss,
+    Oaep,
+    EcDsa,
+    EcDh,
+    EcDaa,
+    Sm2,
+    EcSchnorr,
+    EcMqv,
+    Kdf1Sp800_56a,
+    Kdf2,
+    Kdf1Sp800_108,
+    Ecc,
+    SymCipher,
+    Camellia,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
+    Cmac,
+    Ctr,
+    Ofb,
+    Cbc,
+    Cfb,
+    Ecb,
+    /// An algorithm identifier that is not in the registry
+    Unknown(u16),
+}
+
+use AlgorithmCategory as C;
+
+/// A registered algorithm with its tpm2-tools name and category
+type RegistryEntry = (AlgorithmId, &'static str, Option<AlgorithmCategory>);
+
+#[rustfmt::skip]
+const REGISTRY: &[RegistryEntry] = &[
+    (AlgorithmId::Error,         "error", None),
+    (AlgorithmId::Rsa,           "rsa", Some(C::Asymmetric)),
+    (AlgorithmId::Tdes,          "tdes", Some(C::Symmetric)),
+    (AlgorithmId::Sha1,          "sha1", Some(C::Hash)),
+    (AlgorithmId::Hmac,          "hmac", Some(C::Scheme)),
+    (AlgorithmId::Aes,           "aes", Some(C::Symmetric)),
+    (AlgorithmId::Mgf1,          "mgf1", Some(C::Scheme)),
+    (AlgorithmId::KeyedHash,     "keyedhash", Some(C::ObjectType)),
+    (AlgorithmId::Xor,           "xor", Some(C::Symmetric)),
+    (AlgorithmId::Sha256,        "sha256", Some(C::Hash)),
+    (AlgorithmId::Sha384,        "sha384", Some(C::Hash)),
+    (AlgorithmId::Sha512,        "sha512", Some(C::Hash)),
+    (AlgorithmId::Null,          "null", None),
+    (AlgorithmId::Sm3_256,       "sm3_256", Some(C::Hash)),
+    (AlgorithmId::Sm4,           "sm4", Some(C::Symmetric)),
+    (AlgorithmId::RsaSsa,        "rsassa", Some(C::Scheme)),
+    (AlgorithmId::RsaEs,         "rsaes", Some(C::Scheme)),
+    (AlgorithmId::RsaPss,        "rsapss", Some(C::Scheme)),
+    (AlgorithmId::Oaep,          "oaep", Some(C::Scheme)),
+    (AlgorithmId::EcDsa,         "ecdsa", Some(C::Scheme)),
+    (AlgorithmId::EcDh,          "ecdh", Some(C::Scheme)),
+    (AlgorithmId::EcDaa,         "ecdaa", Some(C::Scheme)),
+    (AlgorithmId::Sm2,           "sm2", Some(C::Scheme)),
+    (AlgorithmId::EcSchnorr,     "ecschnorr", Some(C::Scheme)),
+    (AlgorithmId::EcMqv,         "ecmqv", Some(C::Scheme)),
+    (AlgorithmId::Kdf1Sp800_56a, "kdf1_sp800_56a", Some(C::Scheme)),
+    (AlgorithmId::Kdf2,          "kdf2", Some(C::Scheme)),
+    (AlgorithmId::Kdf1Sp800_108, "kdf1_sp800_108", Some(C::Scheme)),
+    (AlgorithmId::Ecc,           "ecc", Some(C::Asymmetric)),
+    (AlgorithmId::SymCipher,     "symcipher", Some(C::ObjectType)),
+    (AlgorithmId::Camellia,      "camellia", Some(C::Symmetric)),
+    (AlgorithmId::Sha3_256,      "sha3_256", Some(C::Hash)),
+    (AlgorithmId::Sha3_384,      "sha3_384", Some(C::Hash)),
+    (AlgorithmId::Sha3_512,      "sha3_512", Some(C::Hash)),
+    (AlgorithmId::Cmac,          "cmac", Some(C::Scheme)),
+    (AlgorithmId::Ctr,           "ctr", Some(C::Mode)),
+    (AlgorithmId::Ofb,           "ofb", Some(C::Mode)),
+    (AlgorithmId::Cbc,           "cbc", Some(C::Mode)),
+    (AlgorithmId::Cfb,           "cfb", Some(C::Mode)),
+    (AlgorithmId::Ecb,           "ecb", Some(C::Mode)),
+];
+
+impl AlgorithmId {
+    fn entry(&self) -> Option<&'static RegistryEntry> {
+        REGISTRY.iter().find(|entry| entry.0 == *self)
+    }
+
+    pub const fn to_tpm_alg_id(&self) -> u16 {
+        match self {
+            AlgorithmId::Error => 0x0000,
+            AlgorithmId::Rsa => 0x0001,
+            AlgorithmId::Tdes => 0x0003,
+            AlgorithmId::Sha1 => 0x0004,
+            AlgorithmId::Hmac => 0x0005,
+            AlgorithmId::Aes => 0x0006,
+            AlgorithmId::Mgf1 => 0x0007,
+            AlgorithmId::KeyedHash => 0x0008,
+            AlgorithmId::Xor => 0x000A,
+            AlgorithmId::Sha256 => 0x000B,
+            AlgorithmId::Sha384 => 0x000C,
+            AlgorithmId::Sha512 => 0x000D,
+            AlgorithmId::Null => 0x0010,
+            AlgorithmId::Sm3_256 => 0x0012,
+            AlgorithmId::Sm4 => 0x0013,
+            AlgorithmId::RsaSsa => 0x0014,
+            AlgorithmId::RsaEs => 0x0015,
+            AlgorithmId::RsaPss => 0x0016,
+            AlgorithmId::Oaep => 0x0017,
+            AlgorithmId::EcDsa => 0x0018,
+            AlgorithmId::EcDh => 0x0019,
+            AlgorithmId::EcDaa => 0x001A,
+            AlgorithmId::Sm2 => 0x001B,
+            AlgorithmId::EcSchnorr => 0x001C,
+            AlgorithmId::EcMqv => 0x001D,
+            AlgorithmId::Kdf1Sp800_56a => 0x0020,
+            AlgorithmId::Kdf2 => 0x0021,
+            AlgorithmId::Kdf1Sp800_108 => 0x0022,
+            AlgorithmId::Ecc => 0x0023,
+            AlgorithmId::SymCipher => 0x0025,
+            AlgorithmId::Camellia => 0x0026,
+            AlgorithmId::Sha3_256 => 0x0027,
+            AlgorithmId::Sha3_384 => 0x0028,
+            AlgorithmId::Sha3_512 => 0x0029,
+            AlgorithmId::Cmac => 0x003F,
+            AlgorithmId::Ctr => 0x0040,
+            AlgorithmId::Ofb => 0x0041,
+            AlgorithmId::Cbc => 0x0042,
+            AlgorithmId::Cfb => 0x0043,
+            AlgorithmId::Ecb => 0x0044,
+            AlgorithmId::Unknown(alg_id) => *alg_id,
+        }
+    }
+
+    /// The name tpm2-tools uses for the algorithm, or None if it is not registered
+    pub fn name(&self) -> Option<&'static str> {
+        self.entry().map(|entry| entry.1)
+    }
+
+    /// The category, or None for TPM_ALG_NULL, TPM_ALG_ERROR and unknown algorithms
+    pub fn category(&self) -> Option<AlgorithmCategory> {
+        self.entry().and_then(|entry| entry.2)
+    }
+
+    pub fn is_hash(&self) -> bool {
+        self.category() == Some(AlgorithmCategory::Hash)
+    }
+}
+
+impl PartialEq for AlgorithmId {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_tpm_alg_id() == other.to_tpm_alg_id()
+    }
+}
+
+impl Eq for AlgorithmId {}
+
+impl Hash for AlgorithmId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_tpm_alg_id().hash(state)
+    }
+}
+
+impl PartialOrd for AlgorithmId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AlgorithmId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_tpm_alg_id().cmp(&other.to_tpm_alg_id())
+    }
+}
+
+impl From<u16> for AlgorithmId {
+    fn from(alg_id: u16) -> Self {
+        REGISTRY
+            .iter()
+            .map(|entry| entry.0)
+            .find(|algo| algo.to_tpm_alg_id() == alg_id)
+            .unwrap_or(AlgorithmId::Unknown(alg_id))
+    }
+}
+
+impl From<AlgorithmId> for u16 {
+    fn from(algo: AlgorithmId) -> Self {
+        algo.to_tpm_alg_id()
+    }
+}
+
+impl fmt::Display for AlgorithmId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "0x{:04x}", self.to_tpm_alg_id()),
+        }
+    }
+}
+
+impl FromStr for AlgorithmId {
+    type Err = Error;
+
+    /// Parses a tpm2-tools algorithm name or a hexadecimal TPM_ALG_ID
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.to_lowercase();
+        if let Some(alg_id) = s.strip_prefix("0x") {
+            let alg_id = u16::from_str_radix(alg_id, 16).map_err(|_| Error::UnsupportedAlgo)?;
+            return Ok(AlgorithmId::from(alg_id));
+        }
+        REGISTRY
+            .iter()
+            .find(|entry| entry.1 == s)
+            .map(|entry| entry.0)
+            .ok_or(Error::UnsupportedAlgo)
+    }
+}
+
+impl From<DigestAlgorithm> for AlgorithmId {
+    fn from(algo: DigestAlgorithm) -> Self {
+        match algo {
+            DigestAlgorithm::Sha1 => AlgorithmId::Sha1,
+            DigestAlgorithm::Sha256 => AlgorithmId::Sha256,
+            DigestAlgorithm::Sha384 => AlgorithmId::Sha384,
+            DigestAlgorithm::Sha512 => AlgorithmId::Sha512,
+            DigestAlgorithm::Sm3_256 => AlgorithmId::Sm3_256,
+            DigestAlgorithm::Sha3_256 => AlgorithmId::Sha3_256,
+            DigestAlgorithm::Sha3_384 => AlgorithmId::Sha3_384,
+            DigestAlgorithm::Sha3_512 => AlgorithmId::Sha3_512,
+        }
+    }
+}
+
+impl TryFrom<AlgorithmId> for DigestAlgorithm {
+    type Error = Error;
+
+    fn try_from(algo: AlgorithmId) -> Result<Self, Error> {
+        // Unknown identifiers of registered algorithms match by the registered one
+        match AlgorithmId::from(algo.to_tpm_alg_id()) {
+            AlgorithmId::Sha1 => Ok(DigestAlgorithm::Sha1),
+            AlgorithmId::Sha256 => Ok(DigestAlgorithm::Sha256),
+            AlgorithmId::Sha384 => Ok(DigestAlgorithm::Sha384),
+            AlgorithmId::Sha512 => Ok(DigestAlgorithm::Sha512),
+            AlgorithmId::Sm3_256 => Ok(DigestAlgorithm::Sm3_256),
+            AlgorithmId::Sha3_256 => Ok(DigestAlgorithm::Sha3_256),
+            AlgorithmId::Sha3_384 => Ok(DigestAlgorithm::Sha3_384),
+            AlgorithmId::Sha3_512 => Ok(DigestAlgorithm::Sha3_512),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        for (algo, name, _) in REGISTRY {
+            let alg_id = u16::from(*algo);
+            assert!(!matches!(
+                AlgorithmId::from(alg_id),
+                AlgorithmId::Unknown(_)
+            ));
+            assert_eq!(AlgorithmId::from(alg_id), *algo);
+            assert_eq!(AlgorithmId::Unknown(alg_id), *algo);
+            assert_eq!(algo.to_string(), *name);
+            assert_eq!(name.parse::<AlgorithmId>().unwrap(), *algo);
+        }
+        assert_eq!(u16::from(AlgorithmId::Rsa), 0x0001);
+        assert_eq!(u16::from(AlgorithmId::Ecb), 0x0044);
+
+        assert_eq!(AlgorithmId::from(0x1234), AlgorithmId::Unknown(0x1234));
+        assert_ne!(AlgorithmId::Unknown(0x0001), AlgorithmId::Unknown(0x0004));
+        assert_eq!(AlgorithmId::Unknown(0x0001).to_string(), "rsa");
+        assert_eq!(
+            AlgorithmId::Unknown(0x0006).category(),
+            Some(AlgorithmCategory::Symmetric)
+        );
+        assert_eq!(
+            DigestAlgorithm::try_from(AlgorithmId::Unknown(0x000B)).unwrap(),
+            DigestAlgorithm::Sha256
+        );
+        assert_eq!(AlgorithmId::Unknown(0x1234).to_string(), "0x1234");
+        assert_eq!(
+            "0x1234".parse::<AlgorithmId>().unwrap(),
+            AlgorithmId::Unknown(0x1234)
+        );
+        assert_eq!(
+            "0x000B".parse::<AlgorithmId>().unwrap(),
+            AlgorithmId::Sha256
+        );
+        assert_eq!(
+            "RSAPSS".parse::<AlgorithmId>().unwrap(),
+            AlgorithmId::RsaPss
+        );
+        match "md5".parse::<AlgorithmId>() {
+            Err(Error::UnsupportedAlgo) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(
+            AlgorithmId::Aes.category(),
+            Some(AlgorithmCategory::Symmetric)
+        );
+        assert_eq!(AlgorithmId::Cfb.category(), Some(AlgorithmCategory::Mode));
+        assert_eq!(
+            AlgorithmId::EcSchnorr.category(),
+            Some(AlgorithmCategory::Scheme)
+        );
+        assert_eq!(AlgorithmId::Null.category(), None);
+        assert!(AlgorithmId::Sha3_384.is_hash());
+        assert!(!AlgorithmId::Hmac.is_hash());
+    }
+
+    #[test]
+    fn test_digest_algorithms() {
+        for algo in REGISTRY.iter().map(|entry| entry.0) {
+            match DigestAlgorithm::try_from(algo) {
+                Ok(digest) => {
+                    assert!(algo.is_hash());
+                    assert_eq!(AlgorithmId::from(digest), algo);
+                    assert_eq!(digest.to_tpm_alg_id(), algo.to_tpm_alg_id());
+                }
+                Err(Error::UnsupportedAlgo) => assert!(!algo.is_hash()),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+}
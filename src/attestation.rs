@@ -0,0 +1,917 @@
+use alloc::vec::Vec;
+
+use byteorder::BigEndian;
+
+use crate::{
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    nv::NvPublic,
+    objects::{
+        read_hash_alg, ObjectType, Tpm2b, TpmtPublic, TPM_ALG_ECDSA, TPM_ALG_RSAPSS, TPM_ALG_RSASSA,
+    },
+    pcrs::PcrSelection,
+    provider::provider,
+    DigestAlgorithm, Error,
+};
+
+const TPM_GENERATED_VALUE: u32 = 0xFF54_4347;
+
+const TPM_ST_ATTEST_NV: u16 = 0x8014;
+const TPM_ST_ATTEST_COMMAND_AUDIT: u16 = 0x8015;
+const TPM_ST_ATTEST_SESSION_AUDIT: u16 = 0x8016;
+const TPM_ST_ATTEST_CERTIFY: u16 = 0x8017;
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+const TPM_ST_ATTEST_TIME: u16 = 0x8019;
+const TPM_ST_ATTEST_CREATION: u16 = 0x801A;
+const TPM_ST_ATTEST_NV_DIGEST: u16 = 0x801C;
+
+/// A TPMT_SIGNATURE
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Signature {
+    RsaSsa {
+        hash_alg: DigestAlgorithm,
+        signature: Vec<u8>,
+    },
+    RsaPss {
+        hash_alg: DigestAlgorithm,
+        signature: Vec<u8>,
+    },
+    EcDsa {
+        hash_alg: DigestAlgorithm,
+        r: Vec<u8>,
+        s: Vec<u8>,
+    },
+}
+
+impl Signature {
+    pub fn hash_alg(&self) -> DigestAlgorithm {
+        match self {
+            Signature::RsaSsa { hash_alg, .. }
+            | Signature::RsaPss { hash_alg, .. }
+            | Signature::EcDsa { hash_alg, .. } => *hash_alg,
+        }
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Signature, Error> {
+        let sig_alg = reader.read_u16::<BigEndian>()?;
+        let hash_alg = read_hash_alg(&mut reader)?;
+        match sig_alg {
+            TPM_ALG_RSASSA => Ok(Signature::RsaSsa {
+                hash_alg,
+                signature: reader.read_tpm2b()?,
+            }),
+            TPM_ALG_RSAPSS => Ok(Signature::RsaPss {
+                hash_alg,
+                signature: reader.read_tpm2b()?,
+            }),
+            TPM_ALG_ECDSA => Ok(Signature::EcDsa {
+                hash_alg,
+                r: reader.read_tpm2b()?,
+                s: reader.read_tpm2b()?,
+            }),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        match self {
+            Signature::RsaSsa {
+                hash_alg,
+                signature,
+            } => {
+                writer.write_u16::<BigEndian>(TPM_ALG_RSASSA)?;
+                writer.write_u16::<BigEndian>(hash_alg.to_tpm_alg_id())?;
+                Tpm2b::new(&signature[..])?.to_writer(writer)
+            }
+            Signature::RsaPss {
+                hash_alg,
+                signature,
+            } => {
+                writer.write_u16::<BigEndian>(TPM_ALG_RSAPSS)?;
+                writer.write_u16::<BigEndian>(hash_alg.to_tpm_alg_id())?;
+                Tpm2b::new(&signature[..])?.to_writer(writer)
+            }
+            Signature::EcDsa { hash_alg, r, s } => {
+                writer.write_u16::<BigEndian>(TPM_ALG_ECDSA)?;
+                writer.write_u16::<BigEndian>(hash_alg.to_tpm_alg_id())?;
+                Tpm2b::new(&r[..])?.to_writer(&mut writer)?;
+                Tpm2b::new(&s[..])?.to_writer(writer)
+            }
+        }
+    }
+
+    /// Verifies the signature over message with the signing key
+    ///
+    /// Returns Error::InvalidSignature if the signature does not match, or was made
+    /// with a scheme that does not fit the signing key.
+    pub fn verify(&self, signing_key: &TpmtPublic, message: &[u8]) -> Result<(), Error> {
+        let key_type = match self {
+            Signature::RsaSsa { .. } | Signature::RsaPss { .. } => ObjectType::Rsa,
+            Signature::EcDsa { .. } => ObjectType::Ecc,
+        };
+        if signing_key.object_type() != key_type {
+            return Err(Error::InvalidSignature);
+        }
+
+        provider().verify_signature(signing_key, self, message)
+    }
+}
+
+/// A TPMS_CLOCK_INFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockInfo {
+    pub clock: u64,
+    pub reset_count: u32,
+    pub restart_count: u32,
+    pub safe: bool,
+}
+
+impl ClockInfo {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<ClockInfo, Error> {
+        Ok(ClockInfo {
+            clock: reader.read_u64::<BigEndian>()?,
+            reset_count: reader.read_u32::<BigEndian>()?,
+            restart_count: reader.read_u32::<BigEndian>()?,
+            safe: reader.read_u8()? != 0,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u64::<BigEndian>(self.clock)?;
+        writer.write_u32::<BigEndian>(self.reset_count)?;
+        writer.write_u32::<BigEndian>(self.restart_count)?;
+        writer.write_u8(self.safe as u8)?;
+        Ok(())
+    }
+}
+
+/// The TPMU_ATTEST, describing what was attested
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Attested {
+    /// A TPMS_CERTIFY_INFO
+    Certify {
+        name: Vec<u8>,
+        qualified_name: Vec<u8>,
+    },
+    /// A TPMS_CREATION_INFO
+    Creation {
+        object_name: Vec<u8>,
+        creation_hash: Vec<u8>,
+    },
+    /// A TPMS_QUOTE_INFO
+    Quote {
+        pcr_select: PcrSelection,
+        pcr_digest: Vec<u8>,
+    },
+    /// A TPMS_NV_CERTIFY_INFO
+    Nv {
+        index_name: Vec<u8>,
+        offset: u16,
+        nv_contents: Vec<u8>,
+    },
+    /// A TPMS_NV_DIGEST_CERTIFY_INFO
+    NvDigest {
+        index_name: Vec<u8>,
+        nv_digest: Vec<u8>,
+    },
+    /// A TPMS_TIME_ATTEST_INFO
+    Time {
+        time: u64,
+        clock_info: ClockInfo,
+        firmware_version: u64,
+    },
+    /// A TPMS_COMMAND_AUDIT_INFO
+    CommandAudit {
+        audit_counter: u64,
+        digest_alg: DigestAlgorithm,
+        audit_digest: Vec<u8>,
+        command_digest: Vec<u8>,
+    },
+    /// A TPMS_SESSION_AUDIT_INFO
+    SessionAudit {
+        exclusive_session: bool,
+        session_digest: Vec<u8>,
+    },
+}
+
+impl Attested {
+    fn attest_type(&self) -> u16 {
+        match self {
+            Attested::Certify { .. } => TPM_ST_ATTEST_CERTIFY,
+            Attested::Creation { .. } => TPM_ST_ATTEST_CREATION,
+            Attested::Quote { .. } => TPM_ST_ATTEST_QUOTE,
+            Attested::Nv { .. } => TPM_ST_ATTEST_NV,
+            Attested::NvDigest { .. } => TPM_ST_ATTEST_NV_DIGEST,
+            Attested::Time { .. } => TPM_ST_ATTEST_TIME,
+            Attested::CommandAudit { .. } => TPM_ST_ATTEST_COMMAND_AUDIT,
+            Attested::SessionAudit { .. } => TPM_ST_ATTEST_SESSION_AUDIT,
+        }
+    }
+
+    /// Whether this is an NV_Certify of the whole data of the index with the Name
+    /// index: the contents themselves from offset 0, or their nvDigest computed
+    /// with hash_alg
+    pub(crate) fn certifies_nv_data(
+        &self,
+        index: &[u8],
+        contents: &[u8],
+        hash_alg: DigestAlgorithm,
+    ) -> Result<bool, Error> {
+        match self {
+            Attested::Nv {
+                index_name,
+                offset,
+                nv_contents,
+            } => Ok(index_name == index && *offset == 0 && nv_contents == contents),
+            Attested::NvDigest {
+                index_name,
+                nv_digest,
+            } => Ok(index_name == index && *nv_digest == provider().hash(hash_alg, &[contents])?),
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether this is an NV_Certify of exactly the range of the data of the index
+    /// with the Name index that starts at offset and holds contents
+    pub(crate) fn certifies_nv_range(&self, index: &[u8], offset: u16, contents: &[u8]) -> bool {
+        match self {
+            Attested::Nv {
+                index_name,
+                offset: certified_offset,
+                nv_contents,
+            } => index_name == index && *certified_offset == offset && nv_contents == contents,
+            _ => false,
+        }
+    }
+
+    fn from_reader<R: Read>(attest_type: u16, mut reader: R) -> Result<Attested, Error> {
+        match attest_type {
+            TPM_ST_ATTEST_CERTIFY => Ok(Attested::Certify {
+                name: reader.read_tpm2b()?,
+                qualified_name: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_CREATION => Ok(Attested::Creation {
+                object_name: reader.read_tpm2b()?,
+                creation_hash: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_QUOTE => Ok(Attested::Quote {
+                pcr_select: PcrSelection::from_reader(&mut reader)?,
+                pcr_digest: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_NV => Ok(Attested::Nv {
+                index_name: reader.read_tpm2b()?,
+                offset: reader.read_u16::<BigEndian>()?,
+                nv_contents: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_NV_DIGEST => Ok(Attested::NvDigest {
+                index_name: reader.read_tpm2b()?,
+                nv_digest: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_TIME => Ok(Attested::Time {
+                time: reader.read_u64::<BigEndian>()?,
+                clock_info: ClockInfo::from_reader(&mut reader)?,
+                firmware_version: reader.read_u64::<BigEndian>()?,
+            }),
+            TPM_ST_ATTEST_COMMAND_AUDIT => Ok(Attested::CommandAudit {
+                audit_counter: reader.read_u64::<BigEndian>()?,
+                digest_alg: read_hash_alg(&mut reader)?,
+                audit_digest: reader.read_tpm2b()?,
+                command_digest: reader.read_tpm2b()?,
+            }),
+            TPM_ST_ATTEST_SESSION_AUDIT => Ok(Attested::SessionAudit {
+                exclusive_session: reader.read_u8()? != 0,
+                session_digest: reader.read_tpm2b()?,
+            }),
+            _ => Err(Error::InvalidAttestation),
+        }
+    }
+
+    fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        match self {
+            Attested::Certify {
+                name: first,
+                qualified_name: second,
+            }
+            | Attested::Creation {
+                object_name: first,
+                creation_hash: second,
+            }
+            | Attested::NvDigest {
+                index_name: first,
+                nv_digest: second,
+            } => {
+                Tpm2b::new(&first[..])?.to_writer(&mut writer)?;
+                Tpm2b::new(&second[..])?.to_writer(&mut writer)?;
+            }
+            Attested::Quote {
+                pcr_select,
+                pcr_digest,
+            } => {
+                pcr_select.to_writer(&mut writer)?;
+                Tpm2b::new(&pcr_digest[..])?.to_writer(&mut writer)?;
+            }
+            Attested::Nv {
+                index_name,
+                offset,
+                nv_contents,
+            } => {
+                Tpm2b::new(&index_name[..])?.to_writer(&mut writer)?;
+                writer.write_u16::<BigEndian>(*offset)?;
+                Tpm2b::new(&nv_contents[..])?.to_writer(&mut writer)?;
+            }
+            Attested::Time {
+                time,
+                clock_info,
+                firmware_version,
+            } => {
+                writer.write_u64::<BigEndian>(*time)?;
+                clock_info.to_writer(&mut writer)?;
+                writer.write_u64::<BigEndian>(*firmware_version)?;
+            }
+            Attested::CommandAudit {
+                audit_counter,
+                digest_alg,
+                audit_digest,
+                command_digest,
+            } => {
+                writer.write_u64::<BigEndian>(*audit_counter)?;
+                writer.write_u16::<BigEndian>(digest_alg.to_tpm_alg_id())?;
+                Tpm2b::new(&audit_digest[..])?.to_writer(&mut writer)?;
+                Tpm2b::new(&command_digest[..])?.to_writer(&mut writer)?;
+            }
+            Attested::SessionAudit {
+                exclusive_session,
+                session_digest,
+            } => {
+                writer.write_u8(*exclusive_session as u8)?;
+                Tpm2b::new(&session_digest[..])?.to_writer(&mut writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A TPMS_ATTEST
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attestation {
+    pub qualified_signer: Vec<u8>,
+    pub extra_data: Vec<u8>,
+    pub clock_info: ClockInfo,
+    pub firmware_version: u64,
+    pub attested: Attested,
+}
+
+impl Attestation {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Attestation, Error> {
+        if reader.read_u32::<BigEndian>()? != TPM_GENERATED_VALUE {
+            return Err(Error::InvalidAttestation);
+        }
+        let attest_type = reader.read_u16::<BigEndian>()?;
+
+        Ok(Attestation {
+            qualified_signer: reader.read_tpm2b()?,
+            extra_data: reader.read_tpm2b()?,
+            clock_info: ClockInfo::from_reader(&mut reader)?,
+            firmware_version: reader.read_u64::<BigEndian>()?,
+            attested: Attested::from_reader(attest_type, &mut reader)?,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u32::<BigEndian>(TPM_GENERATED_VALUE)?;
+        writer.write_u16::<BigEndian>(self.attested.attest_type())?;
+        Tpm2b::new(&self.qualified_signer[..])?.to_writer(&mut writer)?;
+        Tpm2b::new(&self.extra_data[..])?.to_writer(&mut writer)?;
+        self.clock_info.to_writer(&mut writer)?;
+        writer.write_u64::<BigEndian>(self.firmware_version)?;
+        self.attested.to_writer(writer)
+    }
+}
+
+/// Verifies the signature over a marshalled TPMS_ATTEST of any type and parses it
+///
+/// The extra data must match the qualifying data that was passed to the TPM. The
+/// caller is responsible for checking the attested contents.
+pub fn verify_attestation<ET: AsRef<[u8]>>(
+    attest: &[u8],
+    signature: &Signature,
+    signing_key: &TpmtPublic,
+    extra_data: ET,
+) -> Result<Attestation, Error> {
+    signature.verify(signing_key, attest)?;
+
+    let mut reader = attest;
+    let attestation = Attestation::from_reader(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(Error::InvalidSize);
+    }
+    if attestation.extra_data != extra_data.as_ref() {
+        return Err(Error::AttestationMismatch);
+    }
+    Ok(attestation)
+}
+
+/// Verifies a TPM2_Certify attestation signed by signing_key, and checks that it
+/// certifies the object with the certified public area.
+pub fn verify_certify<ET: AsRef<[u8]>>(
+    attest: &[u8],
+    signature: &Signature,
+    signing_key: &TpmtPublic,
+    certified: &TpmtPublic,
+    extra_data: ET,
+) -> Result<Attestation, Error> {
+    let attestation = verify_attestation(attest, signature, signing_key, extra_data)?;
+    match &attestation.attested {
+        Attested::Certify { name, .. } if *name == certified.name()? => Ok(attestation),
+        _ => Err(Error::AttestationMismatch),
+    }
+}
+
+/// Verifies a TPM2_CertifyCreation attestation signed by signing_key, and checks
+/// that it certifies the object with the certified public area and creation hash.
+pub fn verify_certify_creation<ET: AsRef<[u8]>, CT: AsRef<[u8]>>(
+    attest: &[u8],
+    signature: &Signature,
+    signing_key: &TpmtPublic,
+    certified: &TpmtPublic,
+    creation_hash: CT,
+    extra_data: ET,
+) -> Result<Attestation, Error> {
+    let attestation = verify_attestation(attest, signature, signing_key, extra_data)?;
+    match &attestation.attested {
+        Attested::Creation {
+            object_name,
+            creation_hash: attested_hash,
+        } if *object_name == certified.name()? && attested_hash == creation_hash.as_ref() => {
+            Ok(attestation)
+        }
+        _ => Err(Error::AttestationMismatch),
+    }
+}
+
+/// Verifies a TPM2_NV_Certify attestation signed by signing_key, and checks that it
+/// certifies nv_contents as the data of the index with the nv_public area.
+///
+/// The nv_public area must be the one after the index was written, as the Name
+/// includes TPMA_NV_WRITTEN. nv_contents is the whole data of the index, so it must
+/// be dataSize bytes long, and the attestation must certify all of it: either as
+/// the contents from offset 0, or as their digest with the hash algorithm of the
+/// signature. Use verify_nv_certify_range for an attestation of part of the data.
+pub fn verify_nv_certify<ET: AsRef<[u8]>, CT: AsRef<[u8]>>(
+    attest: &[u8],
+    signature: &Signature,
+    signing_key: &TpmtPublic,
+    nv_public: &NvPublic,
+    nv_contents: CT,
+    extra_data: ET,
+) -> Result<Attestation, Error> {
+    if nv_contents.as_ref().len() != nv_public.data_size as usize {
+        return Err(Error::InvalidSize);
+    }
+    let attestation = verify_attestation(attest, signature, signing_key, extra_data)?;
+    if attestation.attested.certifies_nv_data(
+        &nv_public.name()?,
+        nv_contents.as_ref(),
+        signature.hash_alg(),
+    )? {
+        Ok(attestation)
+    } else {
+        Err(Error::AttestationMismatch)
+    }
+}
+
+/// Verifies a TPM2_NV_Certify attestation signed by signing_key, and checks that it
+/// certifies exactly the range of the data of the index with the nv_public area
+/// that starts at offset and holds contents.
+///
+/// The range must not be empty, as certifying no data says nothing about the index.
+pub fn verify_nv_certify_range<ET: AsRef<[u8]>, CT: AsRef<[u8]>>(
+    attest: &[u8],
+    signature: &Signature,
+    signing_key: &TpmtPublic,
+    nv_public: &NvPublic,
+    offset: u16,
+    contents: CT,
+    extra_data: ET,
+) -> Result<Attestation, Error> {
+    let contents = contents.as_ref();
+    if contents.is_empty() || offset as usize + contents.len() > nv_public.data_size as usize {
+        return Err(Error::InvalidSize);
+    }
+    let attestation = verify_attestation(attest, signature, signing_key, extra_data)?;
+    if attestation
+        .attested
+        .certifies_nv_range(&nv_public.name()?, offset, contents)
+    {
+        Ok(attestation)
+    } else {
+        Err(Error::AttestationMismatch)
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+pub(crate) mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        ecdsa::EcdsaSig,
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::{PKey, PKeyRef, Private},
+        rsa::{Padding, Rsa},
+        sign::{RsaPssSaltlen, Signer},
+    };
+
+    use super::*;
+    use crate::objects::ObjectAttributes;
+
+    /// Signs like a TPM would with the given key
+    pub(crate) fn sign(key: &PKeyRef<Private>, scheme: u16, message: &[u8]) -> Signature {
+        let hash_alg = DigestAlgorithm::Sha256;
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        match scheme {
+            TPM_ALG_RSASSA => Signature::RsaSsa {
+                hash_alg,
+                signature: signer.sign_oneshot_to_vec(message).unwrap(),
+            },
+            TPM_ALG_RSAPSS => {
+                signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+                signer
+                    .set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)
+                    .unwrap();
+                Signature::RsaPss {
+                    hash_alg,
+                    signature: signer.sign_oneshot_to_vec(message).unwrap(),
+                }
+            }
+            _ => {
+                let signature =
+                    EcdsaSig::from_der(&signer.sign_oneshot_to_vec(message).unwrap()).unwrap();
+                Signature::EcDsa {
+                    hash_alg,
+                    r: signature.r().to_vec(),
+                    s: signature.s().to_vec(),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn attestation(attested: Attested) -> Attestation {
+        Attestation {
+            qualified_signer: b"\x00\x0bsigner".to_vec(),
+            extra_data: b"nonce".to_vec(),
+            clock_info: ClockInfo {
+                clock: 1234,
+                reset_count: 1,
+                restart_count: 0,
+                safe: true,
+            },
+            firmware_version: 0x0001_0002_0003_0004,
+            attested,
+        }
+    }
+
+    fn rsa_key() -> (PKey<Private>, TpmtPublic) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let public = TpmtPublic::from_public_key(
+            &key,
+            DigestAlgorithm::Sha256,
+            ObjectAttributes::from_bits(0x0005_0072),
+        )
+        .unwrap();
+        (key, public)
+    }
+
+    #[test]
+    fn test_signature_encoding() {
+        let signature = Signature::EcDsa {
+            hash_alg: DigestAlgorithm::Sha256,
+            r: vec![1, 2],
+            s: vec![3, 4],
+        };
+        let mut encoded = Vec::new();
+        signature.to_writer(&mut encoded).unwrap();
+        assert_eq!(encoded, hex::decode("0018000b0002010200020304").unwrap());
+        assert_eq!(Signature::from_reader(&encoded[..]).unwrap(), signature);
+    }
+
+    #[test]
+    fn test_verify_certify() {
+        let (ak, ak_public) = rsa_key();
+        let (_, certified) = rsa_key();
+
+        let attestation = attestation(Attested::Certify {
+            name: certified.name().unwrap(),
+            qualified_name: b"\x00\x0bqualified".to_vec(),
+        });
+        let mut attest = Vec::new();
+        attestation.to_writer(&mut attest).unwrap();
+
+        for scheme in &[TPM_ALG_RSASSA, TPM_ALG_RSAPSS] {
+            let signature = sign(&ak, *scheme, &attest);
+            assert_eq!(
+                verify_certify(&attest, &signature, &ak_public, &certified, b"nonce").unwrap(),
+                attestation
+            );
+
+            match verify_certify(&attest, &signature, &ak_public, &certified, b"other") {
+                Err(Error::AttestationMismatch) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+            match verify_certify(&attest, &signature, &ak_public, &ak_public, b"nonce") {
+                Err(Error::AttestationMismatch) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+            match verify_certify(&attest, &signature, &certified, &certified, b"nonce") {
+                Err(Error::InvalidSignature) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_certify_creation() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ak = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let ak_public = TpmtPublic::from_public_key(
+            &ak,
+            DigestAlgorithm::Sha256,
+            ObjectAttributes::from_bits(0x0005_0072),
+        )
+        .unwrap();
+        let (_, certified) = rsa_key();
+
+        let attestation = attestation(Attested::Creation {
+            object_name: certified.name().unwrap(),
+            creation_hash: vec![0xAA; 32],
+        });
+        let mut attest = Vec::new();
+        attestation.to_writer(&mut attest).unwrap();
+        let signature = sign(&ak, TPM_ALG_ECDSA, &attest);
+
+        assert_eq!(
+            verify_certify_creation(
+                &attest, &signature, &ak_public, &certified, [0xAA; 32], b"nonce"
+            )
+            .unwrap(),
+            attestation
+        );
+        match verify_certify_creation(
+            &attest, &signature, &ak_public, &certified, [0xBB; 32], b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        // A creation attestation is not a certify attestation
+        match verify_certify(&attest, &signature, &ak_public, &certified, b"nonce") {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut tampered = attest.clone();
+        tampered[10] ^= 0x01;
+        match verify_certify(&tampered, &signature, &ak_public, &certified, b"nonce") {
+            Err(Error::InvalidSignature) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_nv_certify_encoding() {
+        let encoded = hex::decode(
+            "ff544347801400000005\
+             6e6f6e636500000000000004d2000000010000000001\
+             0001000200030004\
+             0006000b0102030400000003010203",
+        )
+        .unwrap();
+        let attestation = Attestation::from_reader(&encoded[..]).unwrap();
+        assert!(attestation.qualified_signer.is_empty());
+        assert_eq!(attestation.extra_data, b"nonce");
+        assert_eq!(attestation.clock_info.clock, 1234);
+        assert!(attestation.clock_info.safe);
+        assert_eq!(
+            attestation.attested,
+            Attested::Nv {
+                index_name: hex::decode("000b01020304").unwrap(),
+                offset: 0,
+                nv_contents: vec![1, 2, 3],
+            }
+        );
+
+        let mut reencoded = Vec::new();
+        attestation.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+
+        let mut unknown = encoded;
+        unknown[5] = 0x1B;
+        match Attestation::from_reader(&unknown[..]) {
+            Err(Error::InvalidAttestation) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_verify_attestation_types() {
+        let (ak, ak_public) = rsa_key();
+        let mut pcr_select = PcrSelection::new();
+        pcr_select.add_bank(DigestAlgorithm::Sha256, &[0, 7]);
+
+        let all_attested = vec![
+            Attested::Quote {
+                pcr_select,
+                pcr_digest: vec![0x11; 32],
+            },
+            Attested::NvDigest {
+                index_name: vec![0x22; 34],
+                nv_digest: vec![0x33; 32],
+            },
+            Attested::Time {
+                time: 5678,
+                clock_info: ClockInfo {
+                    clock: 1234,
+                    reset_count: 1,
+                    restart_count: 0,
+                    safe: true,
+                },
+                firmware_version: 0x0001_0002_0003_0004,
+            },
+            Attested::CommandAudit {
+                audit_counter: 42,
+                digest_alg: DigestAlgorithm::Sha256,
+                audit_digest: vec![0x44; 32],
+                command_digest: vec![0x55; 32],
+            },
+            Attested::SessionAudit {
+                exclusive_session: true,
+                session_digest: vec![0x66; 32],
+            },
+        ];
+
+        for attested in all_attested {
+            let attestation = attestation(attested);
+            let mut attest = Vec::new();
+            attestation.to_writer(&mut attest).unwrap();
+            let signature = sign(&ak, TPM_ALG_RSASSA, &attest);
+
+            assert_eq!(
+                verify_attestation(&attest, &signature, &ak_public, b"nonce").unwrap(),
+                attestation
+            );
+
+            attest.push(0);
+            match verify_attestation(
+                &attest,
+                &sign(&ak, TPM_ALG_RSASSA, &attest),
+                &ak_public,
+                b"nonce",
+            ) {
+                Err(Error::InvalidSize) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_nv_certify() {
+        let (ak, ak_public) = rsa_key();
+        let mut nv_public =
+            NvPublic::from_reader(&hex::decode("01500016000b2206001200000008").unwrap()[..])
+                .unwrap();
+        let contents = [0, 0, 0, 0, 0, 0, 0, 3];
+        let sign_attested = |attested: Attested| {
+            let attested = attestation(attested);
+            let mut attest = Vec::new();
+            attested.to_writer(&mut attest).unwrap();
+            let signature = sign(&ak, TPM_ALG_RSASSA, &attest);
+            (attested, attest, signature)
+        };
+
+        let (certified, attest, signature) = sign_attested(Attested::Nv {
+            index_name: nv_public.name().unwrap(),
+            offset: 0,
+            nv_contents: contents.to_vec(),
+        });
+        assert_eq!(
+            verify_nv_certify(&attest, &signature, &ak_public, &nv_public, contents, b"nonce")
+                .unwrap(),
+            certified
+        );
+        match verify_nv_certify(
+            &attest,
+            &signature,
+            &ak_public,
+            &nv_public,
+            [0, 0, 0, 0, 0, 0, 0, 4],
+            b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match verify_nv_certify(
+            &attest,
+            &signature,
+            &ak_public,
+            &nv_public,
+            &contents[..7],
+            b"nonce",
+        ) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Part of the data only verifies when that range is expected explicitly
+        let (certified, attest, signature) = sign_attested(Attested::Nv {
+            index_name: nv_public.name().unwrap(),
+            offset: 6,
+            nv_contents: vec![0, 3],
+        });
+        match verify_nv_certify(
+            &attest, &signature, &ak_public, &nv_public, contents, b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            verify_nv_certify_range(
+                &attest,
+                &signature,
+                &ak_public,
+                &nv_public,
+                6,
+                [0, 3],
+                b"nonce"
+            )
+            .unwrap(),
+            certified
+        );
+        for (offset, range) in [(6, &[0, 4][..]), (5, &[0, 3][..]), (6, &[3][..])] {
+            match verify_nv_certify_range(
+                &attest, &signature, &ak_public, &nv_public, offset, range, b"nonce",
+            ) {
+                Err(Error::AttestationMismatch) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        // Certifying no data matches any contents, so it is never accepted
+        let (_, attest, signature) = sign_attested(Attested::Nv {
+            index_name: nv_public.name().unwrap(),
+            offset: 0,
+            nv_contents: Vec::new(),
+        });
+        match verify_nv_certify(
+            &attest, &signature, &ak_public, &nv_public, contents, b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match verify_nv_certify_range(&attest, &signature, &ak_public, &nv_public, 0, [], b"nonce")
+        {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // A certified digest is checked against the hash of the contents
+        let certified_digest = attestation(Attested::NvDigest {
+            index_name: nv_public.name().unwrap(),
+            nv_digest: provider()
+                .hash(DigestAlgorithm::Sha256, &[&contents])
+                .unwrap(),
+        });
+        let mut attest = Vec::new();
+        certified_digest.to_writer(&mut attest).unwrap();
+        let signature = sign(&ak, TPM_ALG_RSASSA, &attest);
+
+        assert_eq!(
+            verify_nv_certify(&attest, &signature, &ak_public, &nv_public, contents, b"nonce")
+                .unwrap(),
+            certified_digest
+        );
+        match verify_nv_certify(
+            &attest,
+            &signature,
+            &ak_public,
+            &nv_public,
+            [0, 0, 0, 0, 0, 0, 0, 4],
+            b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        nv_public.data_size = 16;
+        match verify_nv_certify(
+            &attest, &signature, &ak_public, &nv_public, contents, b"nonce",
+        ) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match verify_nv_certify(
+            &attest, &signature, &ak_public, &nv_public, [0; 16], b"nonce",
+        ) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
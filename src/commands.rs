@@ -0,0 +1,531 @@
+use alloc::{vec, vec::Vec};
+use core::hash::{Hash, Hasher};
+
+use byteorder::BigEndian;
+
+use crate::{
+    io::{Write, WriteBytesExt},
+    nv::NvPublic,
+    objects::{SignatureScheme, SymmetricDefinition, Tpm2b},
+    provider::provider,
+    DigestAlgorithm, Error,
+};
+
+/// A TPM_CC value
+///
+/// Codes without a variant of their own are kept as Other, which compares equal to
+/// the named variant with the same code.
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub enum CommandCode {
+    NvUndefineSpaceSpecial,
+    EvictControl,
+    NvUndefineSpace,
+    Clear,
+    HierarchyChangeAuth,
+    NvDefineSpace,
+    PcrAllocate,
+    CreatePrimary,
+    NvGlobalWriteLock,
+    GetCommandAuditDigest,
+    NvIncrement,
+    NvSetBits,
+    NvExtend,
+    NvWrite,
+    NvWriteLock,
+    NvChangeAuth,
+    PcrEvent,
+    PcrReset,
+    ActivateCredential,
+    Certify,
+    PolicyNv,
+    CertifyCreation,
+    Duplicate,
+    GetTime,
+    GetSessionAuditDigest,
+    NvRead,
+    NvReadLock,
+    ObjectChangeAuth,
+    PolicySecret,
+    Rewrap,
+    Create,
+    EcdhZGen,
+    Hmac,
+    Import,
+    Load,
+    Quote,
+    RsaDecrypt,
+    Sign,
+    Unseal,
+    PolicySigned,
+    EncryptDecrypt,
+    LoadExternal,
+    MakeCredential,
+    NvReadPublic,
+    PolicyAuthorize,
+    PolicyCommandCode,
+    ReadPublic,
+    RsaEncrypt,
+    StartAuthSession,
+    VerifySignature,
+    GetCapability,
+    PcrRead,
+    PcrExtend,
+    NvCertify,
+    CreateLoaded,
+    Other(u32),
+}
+
+impl CommandCode {
+    pub fn from_tpm_cc(cc: u32) -> Self {
+        match cc {
+            0x0000_011F => CommandCode::NvUndefineSpaceSpecial,
+            0x0000_0120 => CommandCode::EvictControl,
+            0x0000_0122 => CommandCode::NvUndefineSpace,
+            0x0000_0126 => CommandCode::Clear,
+            0x0000_0129 => CommandCode::HierarchyChangeAuth,
+            0x0000_012A => CommandCode::NvDefineSpace,
+            0x0000_012B => CommandCode::PcrAllocate,
+            0x0000_0131 => CommandCode::CreatePrimary,
+            0x0000_0132 => CommandCode::NvGlobalWriteLock,
+            0x0000_0133 => CommandCode::GetCommandAuditDigest,
+            0x0000_0134 => CommandCode::NvIncrement,
+            0x0000_0135 => CommandCode::NvSetBits,
+            0x0000_0136 => CommandCode::NvExtend,
+            0x0000_0137 => CommandCode::NvWrite,
+            0x0000_0138 => CommandCode::NvWriteLock,
+            0x0000_013B => CommandCode::NvChangeAuth,
+            0x0000_013C => CommandCode::PcrEvent,
+            0x0000_013D => CommandCode::PcrReset,
+            0x0000_0147 => CommandCode::ActivateCredential,
+            0x0000_0148 => CommandCode::Certify,
+            0x0000_0149 => CommandCode::PolicyNv,
+            0x0000_014A => CommandCode::CertifyCreation,
+            0x0000_014B => CommandCode::Duplicate,
+            0x0000_014C => CommandCode::GetTime,
+            0x0000_014D => CommandCode::GetSessionAuditDigest,
+            0x0000_014E => CommandCode::NvRead,
+            0x0000_014F => CommandCode::NvReadLock,
+            0x0000_0150 => CommandCode::ObjectChangeAuth,
+            0x0000_0151 => CommandCode::PolicySecret,
+            0x0000_0152 => CommandCode::Rewrap,
+            0x0000_0153 => CommandCode::Create,
+            0x0000_0154 => CommandCode::EcdhZGen,
+            0x0000_0155 => CommandCode::Hmac,
+            0x0000_0156 => CommandCode::Import,
+            0x0000_0157 => CommandCode::Load,
+            0x0000_0158 => CommandCode::Quote,
+            0x0000_0159 => CommandCode::RsaDecrypt,
+            0x0000_015D => CommandCode::Sign,
+            0x0000_015E => CommandCode::Unseal,
+            0x0000_0160 => CommandCode::PolicySigned,
+            0x0000_0164 => CommandCode::EncryptDecrypt,
+            0x0000_0167 => CommandCode::LoadExternal,
+            0x0000_0168 => CommandCode::MakeCredential,
+            0x0000_0169 => CommandCode::NvReadPublic,
+            0x0000_016A => CommandCode::PolicyAuthorize,
+            0x0000_016C => CommandCode::PolicyCommandCode,
+            0x0000_0173 => CommandCode::ReadPublic,
+            0x0000_0174 => CommandCode::RsaEncrypt,
+            0x0000_0176 => CommandCode::StartAuthSession,
+            0x0000_0177 => CommandCode::VerifySignature,
+            0x0000_017A => CommandCode::GetCapability,
+            0x0000_017E => CommandCode::PcrRead,
+            0x0000_0182 => CommandCode::PcrExtend,
+            0x0000_0184 => CommandCode::NvCertify,
+            0x0000_0191 => CommandCode::CreateLoaded,
+            cc => CommandCode::Other(cc),
+        }
+    }
+
+    pub fn to_tpm_cc(&self) -> u32 {
+        match self {
+            CommandCode::NvUndefineSpaceSpecial => 0x0000_011F,
+            CommandCode::EvictControl => 0x0000_0120,
+            CommandCode::NvUndefineSpace => 0x0000_0122,
+            CommandCode::Clear => 0x0000_0126,
+            CommandCode::HierarchyChangeAuth => 0x0000_0129,
+            CommandCode::NvDefineSpace => 0x0000_012A,
+            CommandCode::PcrAllocate => 0x0000_012B,
+            CommandCode::CreatePrimary => 0x0000_0131,
+            CommandCode::NvGlobalWriteLock => 0x0000_0132,
+            CommandCode::GetCommandAuditDigest => 0x0000_0133,
+            CommandCode::NvIncrement => 0x0000_0134,
+            CommandCode::NvSetBits => 0x0000_0135,
+            CommandCode::NvExtend => 0x0000_0136,
+            CommandCode::NvWrite => 0x0000_0137,
+            CommandCode::NvWriteLock => 0x0000_0138,
+            CommandCode::NvChangeAuth => 0x0000_013B,
+            CommandCode::PcrEvent => 0x0000_013C,
+            CommandCode::PcrReset => 0x0000_013D,
+            CommandCode::ActivateCredential => 0x0000_0147,
+            CommandCode::Certify => 0x0000_0148,
+            CommandCode::PolicyNv => 0x0000_0149,
+            CommandCode::CertifyCreation => 0x0000_014A,
+            CommandCode::Duplicate => 0x0000_014B,
+            CommandCode::GetTime => 0x0000_014C,
+            CommandCode::GetSessionAuditDigest => 0x0000_014D,
+            CommandCode::NvRead => 0x0000_014E,
+            CommandCode::NvReadLock => 0x0000_014F,
+            CommandCode::ObjectChangeAuth => 0x0000_0150,
+            CommandCode::PolicySecret => 0x0000_0151,
+            CommandCode::Rewrap => 0x0000_0152,
+            CommandCode::Create => 0x0000_0153,
+            CommandCode::EcdhZGen => 0x0000_0154,
+            CommandCode::Hmac => 0x0000_0155,
+            CommandCode::Import => 0x0000_0156,
+            CommandCode::Load => 0x0000_0157,
+            CommandCode::Quote => 0x0000_0158,
+            CommandCode::RsaDecrypt => 0x0000_0159,
+            CommandCode::Sign => 0x0000_015D,
+            CommandCode::Unseal => 0x0000_015E,
+            CommandCode::PolicySigned => 0x0000_0160,
+            CommandCode::EncryptDecrypt => 0x0000_0164,
+            CommandCode::LoadExternal => 0x0000_0167,
+            CommandCode::MakeCredential => 0x0000_0168,
+            CommandCode::NvReadPublic => 0x0000_0169,
+            CommandCode::PolicyAuthorize => 0x0000_016A,
+            CommandCode::PolicyCommandCode => 0x0000_016C,
+            CommandCode::ReadPublic => 0x0000_0173,
+            CommandCode::RsaEncrypt => 0x0000_0174,
+            CommandCode::StartAuthSession => 0x0000_0176,
+            CommandCode::VerifySignature => 0x0000_0177,
+            CommandCode::GetCapability => 0x0000_017A,
+            CommandCode::PcrRead => 0x0000_017E,
+            CommandCode::PcrExtend => 0x0000_0182,
+            CommandCode::NvCertify => 0x0000_0184,
+            CommandCode::CreateLoaded => 0x0000_0191,
+            CommandCode::Other(cc) => *cc,
+        }
+    }
+}
+
+impl PartialEq for CommandCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_tpm_cc() == other.to_tpm_cc()
+    }
+}
+
+impl Eq for CommandCode {}
+
+impl Hash for CommandCode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_tpm_cc().hash(state)
+    }
+}
+
+impl From<u32> for CommandCode {
+    fn from(cc: u32) -> Self {
+        CommandCode::from_tpm_cc(cc)
+    }
+}
+
+/// Returns the Name of an entity that is referenced by its handle only
+///
+/// This is the case for PCRs, sessions and permanent handles like TPM_RH_OWNER.
+pub fn handle_name(handle: u32) -> Vec<u8> {
+    handle.to_be_bytes().to_vec()
+}
+
+/// A TPM command, reduced to the parts that go into its cpHash
+///
+/// The names are the Names of the entities in the handle area, in the order in
+/// which the handles appear in the command, and the parameters are the marshalled
+/// parameter area.
+#[derive(Debug, Clone)]
+pub struct Command {
+    code: CommandCode,
+    names: Vec<Vec<u8>>,
+    parameters: Vec<u8>,
+}
+
+impl Command {
+    pub fn new<PT: AsRef<[u8]>>(code: CommandCode, names: Vec<Vec<u8>>, parameters: PT) -> Self {
+        Command {
+            code,
+            names,
+            parameters: parameters.as_ref().to_vec(),
+        }
+    }
+
+    /// TPM2_NV_DefineSpace(authHandle, auth, publicInfo)
+    pub fn nv_define_space<AN, AT>(
+        auth_name: AN,
+        auth: AT,
+        public_info: &NvPublic,
+    ) -> Result<Self, Error>
+    where
+        AN: AsRef<[u8]>,
+        AT: AsRef<[u8]>,
+    {
+        let mut parameters = Vec::new();
+        Tpm2b::new(auth.as_ref())?.to_writer(&mut parameters)?;
+        Tpm2b::new(public_info.to_vec()?)?.to_writer(&mut parameters)?;
+
+        Ok(Command::new(
+            CommandCode::NvDefineSpace,
+            vec![auth_name.as_ref().to_vec()],
+            parameters,
+        ))
+    }
+
+    /// TPM2_NV_Write(authHandle, nvIndex, data, offset)
+    pub fn nv_write<AN, NN, DT>(
+        auth_name: AN,
+        nv_index_name: NN,
+        data: DT,
+        offset: u16,
+    ) -> Result<Self, Error>
+    where
+        AN: AsRef<[u8]>,
+        NN: AsRef<[u8]>,
+        DT: AsRef<[u8]>,
+    {
+        let mut parameters = Vec::new();
+        Tpm2b::new(data.as_ref())?.to_writer(&mut parameters)?;
+        parameters.write_u16::<BigEndian>(offset)?;
+
+        Ok(Command::new(
+            CommandCode::NvWrite,
+            vec![auth_name.as_ref().to_vec(), nv_index_name.as_ref().to_vec()],
+            parameters,
+        ))
+    }
+
+    /// TPM2_NV_Read(authHandle, nvIndex, size, offset)
+    pub fn nv_read<AN, NN>(
+        auth_name: AN,
+        nv_index_name: NN,
+        size: u16,
+        offset: u16,
+    ) -> Result<Self, Error>
+    where
+        AN: AsRef<[u8]>,
+        NN: AsRef<[u8]>,
+    {
+        let mut parameters = Vec::new();
+        parameters.write_u16::<BigEndian>(size)?;
+        parameters.write_u16::<BigEndian>(offset)?;
+
+        Ok(Command::new(
+            CommandCode::NvRead,
+            vec![auth_name.as_ref().to_vec(), nv_index_name.as_ref().to_vec()],
+            parameters,
+        ))
+    }
+
+    /// TPM2_Unseal(itemHandle)
+    pub fn unseal<IN: AsRef<[u8]>>(item_name: IN) -> Self {
+        Command::new(CommandCode::Unseal, vec![item_name.as_ref().to_vec()], [])
+    }
+
+    /// TPM2_Certify(objectHandle, signHandle, qualifyingData, inScheme)
+    pub fn certify<ON, SN, QT>(
+        object_name: ON,
+        sign_name: SN,
+        qualifying_data: QT,
+        in_scheme: SignatureScheme,
+    ) -> Result<Self, Error>
+    where
+        ON: AsRef<[u8]>,
+        SN: AsRef<[u8]>,
+        QT: AsRef<[u8]>,
+    {
+        let mut parameters = Vec::new();
+        Tpm2b::new(qualifying_data.as_ref())?.to_writer(&mut parameters)?;
+        in_scheme.to_writer(&mut parameters)?;
+
+        Ok(Command::new(
+            CommandCode::Certify,
+            vec![object_name.as_ref().to_vec(), sign_name.as_ref().to_vec()],
+            parameters,
+        ))
+    }
+
+    /// TPM2_Duplicate(objectHandle, newParentHandle, encryptionKeyIn, symmetricAlg)
+    pub fn duplicate<ON, PN, KT>(
+        object_name: ON,
+        new_parent_name: PN,
+        encryption_key_in: KT,
+        symmetric_alg: SymmetricDefinition,
+    ) -> Result<Self, Error>
+    where
+        ON: AsRef<[u8]>,
+        PN: AsRef<[u8]>,
+        KT: AsRef<[u8]>,
+    {
+        let mut parameters = Vec::new();
+        Tpm2b::new(encryption_key_in.as_ref())?.to_writer(&mut parameters)?;
+        symmetric_alg.to_writer(&mut parameters)?;
+
+        Ok(Command::new(
+            CommandCode::Duplicate,
+            vec![
+                object_name.as_ref().to_vec(),
+                new_parent_name.as_ref().to_vec(),
+            ],
+            parameters,
+        ))
+    }
+
+    pub fn code(&self) -> CommandCode {
+        self.code
+    }
+
+    pub fn names(&self) -> &[Vec<u8>] {
+        &self.names
+    }
+
+    pub fn parameters(&self) -> &[u8] {
+        &self.parameters
+    }
+
+    /// Computes cpHash = H(commandCode || name1 || ... || nameN || parameters)
+    pub fn cp_hash(&self, algo: DigestAlgorithm) -> Result<Vec<u8>, Error> {
+        let code = self.code.to_tpm_cc().to_be_bytes();
+        let mut parts: Vec<&[u8]> = vec![&code];
+        parts.extend(self.names.iter().map(Vec::as_slice));
+        parts.push(&self.parameters);
+        provider().hash(algo, &parts)
+    }
+}
+
+/// Computes rpHash = H(responseCode || commandCode || parameters)
+///
+/// Only successful responses carry an rpHash in practice, in which case the
+/// response_code is TPM_RC_SUCCESS (0).
+pub fn rp_hash<PT: AsRef<[u8]>>(
+    algo: DigestAlgorithm,
+    response_code: u32,
+    command_code: CommandCode,
+    parameters: PT,
+) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    buf.write_u32::<BigEndian>(response_code)?;
+    buf.write_u32::<BigEndian>(command_code.to_tpm_cc())?;
+    buf.write_all(parameters.as_ref())?;
+
+    provider().hash(algo, &[&buf])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_code_roundtrip() {
+        assert_eq!(CommandCode::from_tpm_cc(0x0000_0137), CommandCode::NvWrite);
+        assert_eq!(CommandCode::Unseal.to_tpm_cc(), 0x0000_015E);
+        for cc in 0x0000_011F..=0x0000_0200 {
+            assert_eq!(CommandCode::from_tpm_cc(cc).to_tpm_cc(), cc);
+        }
+
+        assert!(matches!(
+            CommandCode::from_tpm_cc(0x2000_0001),
+            CommandCode::Other(0x2000_0001)
+        ));
+        assert_eq!(CommandCode::Other(0x0000_015E), CommandCode::Unseal);
+        assert_ne!(CommandCode::Other(0x0000_015F), CommandCode::Unseal);
+    }
+
+    #[test]
+    fn test_cp_hash_unseal() {
+        let name =
+            hex::decode("000b0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20")
+                .unwrap();
+        let cmd = Command::unseal(&name);
+
+        assert_eq!(
+            cmd.cp_hash(DigestAlgorithm::Sha256).unwrap(),
+            hex::decode("B2E023FD7134F9DC3A414AB5B085DE72C96E88177321082F9C8DC8DCC0D1F74A")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_cp_hash_nv_write() {
+        let owner = handle_name(0x4000_0001);
+        let nv_name = handle_name(0x0150_0016);
+        let cmd = Command::nv_write(&owner, &nv_name, b"hello", 3).unwrap();
+
+        assert_eq!(
+            cmd.parameters(),
+            &hex::decode("000568656c6c6f0003").unwrap()[..],
+        );
+        assert_eq!(
+            cmd.cp_hash(DigestAlgorithm::Sha1).unwrap(),
+            hex::decode("7040CBA5BB3B665E37BF6BFC178676A0D94E65CB").unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_cp_hash_nv_define_space() {
+        let public =
+            NvPublic::from_reader(&hex::decode("01500016000b0206001200000008").unwrap()[..])
+                .unwrap();
+        let cmd = Command::nv_define_space(handle_name(0x4000_0001), b"pw", &public).unwrap();
+
+        assert_eq!(
+            cmd.parameters(),
+            &hex::decode("00027077000e01500016000b0206001200000008").unwrap()[..],
+        );
+    }
+
+    #[test]
+    fn test_cp_hash_duplicate() {
+        // A persistent object is referred to by the Name of its public area
+        let object_name =
+            hex::decode("000b2bc8000e0f60771e73654dcb628ced58c023970f4ede8a2fab411706f74ea3c1")
+                .unwrap();
+        let cmd = Command::duplicate(
+            &object_name,
+            handle_name(0x4000_0007),
+            [],
+            SymmetricDefinition::Aes { key_bits: 128 },
+        )
+        .unwrap();
+
+        assert_eq!(
+            cmd.parameters(),
+            &hex::decode("0000000600800043").unwrap()[..],
+        );
+        assert_eq!(
+            cmd.cp_hash(DigestAlgorithm::Sha256).unwrap(),
+            hex::decode("18669973019CA2DA8354C78ED4965F8952A251471E5EC2049AFFFC395BE043D5")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_cp_hash_any_command() {
+        // TPM2_GetCapability(TPM_CAP_TPM_PROPERTIES, TPM_PT_FAMILY_INDICATOR, 1)
+        let cmd = Command::new(
+            CommandCode::from_tpm_cc(0x0000_017A),
+            Vec::new(),
+            hex::decode("000000060000010000000001").unwrap(),
+        );
+        assert_eq!(cmd.code(), CommandCode::GetCapability);
+        assert_eq!(
+            cmd.cp_hash(DigestAlgorithm::Sha256).unwrap(),
+            hex::decode("30B76B2A43108344E85C6A51C1D924068A69467499518FEA3E18C77782E7EFD8")
+                .unwrap(),
+        );
+
+        // A vendor specific command
+        let cmd = Command::new(CommandCode::Other(0x2000_0001), Vec::new(), [1, 2]);
+        assert_eq!(
+            cmd.cp_hash(DigestAlgorithm::Sha256).unwrap(),
+            hex::decode("03A74B5B9B1663B3573FF003726E07850A9A73C6A12971EBE2DB6C03BE6EE363")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_rp_hash() {
+        assert_eq!(
+            rp_hash(DigestAlgorithm::Sha256, 0, CommandCode::Unseal, b"secret").unwrap(),
+            hex::decode("A5D46EF85D57C2CD7C4833B3969422DE370978E655B4E6273D6F666ED12DC14D")
+                .unwrap(),
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+
+use byteorder::BigEndian;
+
+use crate::{
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    objects::{Tpm2b, TPM_ALG_NULL},
+    pcrs::{PcrExtender, PcrSelection},
+    provider::provider,
+    DigestAlgorithm, Error,
+};
+
+const TPM_ST_CREATION: u16 = 0x8021;
+
+/// A TPMS_CREATION_DATA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreationData {
+    pub pcr_select: PcrSelection,
+    pub pcr_digest: Vec<u8>,
+    pub locality: u8,
+    /// None if the parent is a permanent handle, which makes parent_name the handle
+    pub parent_name_alg: Option<DigestAlgorithm>,
+    pub parent_name: Vec<u8>,
+    pub parent_qualified_name: Vec<u8>,
+    pub outside_info: Vec<u8>,
+}
+
+impl CreationData {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<CreationData, Error> {
+        let pcr_select = PcrSelection::from_reader(&mut reader)?;
+        let pcr_digest = reader.read_tpm2b()?;
+        let locality = reader.read_u8()?;
+        let parent_name_alg = match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => None,
+            alg_id => Some(DigestAlgorithm::from_tpm_alg_id(alg_id).ok_or(Error::UnsupportedAlgo)?),
+        };
+
+        Ok(CreationData {
+            pcr_select,
+            pcr_digest,
+            locality,
+            parent_name_alg,
+            parent_name: reader.read_tpm2b()?,
+            parent_qualified_name: reader.read_tpm2b()?,
+            outside_info: reader.read_tpm2b()?,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        self.pcr_select.to_writer(&mut writer)?;
+        Tpm2b::new(&self.pcr_digest[..])?.to_writer(&mut writer)?;
+        writer.write_u8(self.locality)?;
+        writer.write_u16::<BigEndian>(
+            self.parent_name_alg
+                .map(|algo| algo.to_tpm_alg_id())
+                .unwrap_or(TPM_ALG_NULL),
+        )?;
+        Tpm2b::new(&self.parent_name[..])?.to_writer(&mut writer)?;
+        Tpm2b::new(&self.parent_qualified_name[..])?.to_writer(&mut writer)?;
+        Tpm2b::new(&self.outside_info[..])?.to_writer(&mut writer)?;
+        Ok(())
+    }
+
+    /// Computes the creationHash, using the nameAlg of the created object
+    pub fn creation_hash(&self, name_alg: DigestAlgorithm) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.to_writer(&mut data)?;
+
+        provider().hash(name_alg, &[&data])
+    }
+
+    /// Checks that the PCRs had the values of the extender when the object was created
+    ///
+    /// The pcrDigest is computed with the nameAlg of the created object.
+    pub fn verify_pcr_digest(
+        &self,
+        extender: &PcrExtender,
+        name_alg: DigestAlgorithm,
+    ) -> Result<(), Error> {
+        if extender.pcr_digest(&self.pcr_select, name_alg)? != self.pcr_digest {
+            return Err(Error::PcrMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// A TPMT_TK_CREATION
+///
+/// The digest is an HMAC with a TPM-internal proof value, so the ticket can only be
+/// checked by the TPM that created it, using TPM2_CertifyCreation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreationTicket {
+    pub hierarchy: u32,
+    pub digest: Vec<u8>,
+}
+
+impl CreationTicket {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<CreationTicket, Error> {
+        if reader.read_u16::<BigEndian>()? != TPM_ST_CREATION {
+            return Err(Error::InvalidAttestation);
+        }
+        Ok(CreationTicket {
+            hierarchy: reader.read_u32::<BigEndian>()?,
+            digest: reader.read_tpm2b()?,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u16::<BigEndian>(TPM_ST_CREATION)?;
+        writer.write_u32::<BigEndian>(self.hierarchy)?;
+        Tpm2b::new(&self.digest[..])?.to_writer(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PcrExtenderBuilder;
+
+    // Creation data of a key created under a sha256 parent, with PCR 0 and 7 of the
+    // sha256 bank selected while they were both zero
+    const CREATION_DATA: &str = "00000001000b03810000\
+                                 0020f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b\
+                                 00000b\
+                                 0022000bb5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c\
+                                 0022000bb5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c\
+                                 0000";
+
+    #[test]
+    fn test_creation_data() {
+        let encoded = hex::decode(CREATION_DATA).unwrap();
+
+        let data = CreationData::from_reader(&encoded[..]).unwrap();
+        assert_eq!(
+            data.pcr_select.banks(),
+            &[(DigestAlgorithm::Sha256, vec![0, 7])]
+        );
+        assert_eq!(data.locality, 0);
+        assert_eq!(data.parent_name_alg, Some(DigestAlgorithm::Sha256));
+        assert_eq!(data.parent_name.len(), 34);
+        assert!(data.outside_info.is_empty());
+
+        let mut reencoded = Vec::new();
+        data.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+
+        assert_eq!(
+            data.creation_hash(DigestAlgorithm::Sha256).unwrap(),
+            provider()
+                .hash(DigestAlgorithm::Sha256, &[&encoded])
+                .unwrap()
+        );
+
+        // A TPM may send a sizeofSelect of 4 even when only low PCRs are selected
+        let encoded = hex::decode(CREATION_DATA.replacen("000b038100", "000b04810000", 1)).unwrap();
+        let data = CreationData::from_reader(&encoded[..]).unwrap();
+        assert_eq!(
+            data.pcr_select.banks(),
+            &[(DigestAlgorithm::Sha256, vec![0, 7])]
+        );
+        assert_eq!(
+            data.creation_hash(DigestAlgorithm::Sha256).unwrap(),
+            provider()
+                .hash(DigestAlgorithm::Sha256, &[&encoded])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_pcr_digest() {
+        let encoded = hex::decode(CREATION_DATA).unwrap();
+        let data = CreationData::from_reader(&encoded[..]).unwrap();
+
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        data.verify_pcr_digest(&extender, DigestAlgorithm::Sha256)
+            .unwrap();
+
+        extender.extend(7, b"separator").unwrap();
+        match data.verify_pcr_digest(&extender, DigestAlgorithm::Sha256) {
+            Err(Error::PcrMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_creation_ticket() {
+        let encoded = hex::decode("80224000000100040102030400").unwrap();
+        match CreationTicket::from_reader(&encoded[..]) {
+            Err(Error::InvalidAttestation) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let encoded = hex::decode("8021400000010004deadbeef").unwrap();
+        let ticket = CreationTicket::from_reader(&encoded[..]).unwrap();
+        assert_eq!(ticket.hierarchy, 0x4000_0001);
+        assert_eq!(ticket.digest, hex::decode("deadbeef").unwrap());
+
+        let mut reencoded = Vec::new();
+        ticket.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+    }
+}
@@ -1,96 +1,492 @@
-use openssl::{
-    encrypt::Encrypter,
-    hash::MessageDigest,
-    pkey::{HasPublic, Id as pkey_id, PKeyRef},
-    rand::rand_bytes,
-    rsa::Padding,
-};
+use alloc::vec::Vec;
+#[cfg(feature = "openssl")]
+use core::convert::TryFrom;
+
+#[cfg(feature = "openssl")]
+use openssl::pkey::{HasPrivate, PKey, PKeyRef};
 
-use crate::{crypto::kdf_a, Error};
+#[cfg(any(feature = "rand", feature = "openssl"))]
+use crate::provider::provider;
+use crate::{
+    crypto::{aes_cfb_decrypt, aes_cfb_encrypt, build_seed, kdf_a, recover_seed, verify_hmac},
+    objects::{SymmetricDefinition, Tpm2b, Tpm2bRef, TpmtPublic, TpmtSensitive},
+    provider::CryptoProvider,
+    Error,
+};
 
 const CREDENTIAL_LABEL_SYMKEY: &[u8] = b"STORAGE";
 const CREDENTIAL_LABEL_IDENTITY: &[u8] = b"IDENTITY";
 const CREDENTIAL_LABEL_INTEGRITY: &[u8] = b"INTEGRITY";
 
+/// The output of TPM2_MakeCredential
+#[derive(Debug, Clone)]
 pub struct Credential {
     id_object: Vec<u8>,
     encrypted_secret: Vec<u8>,
 }
 
-fn build_seed_rsa<KT, LT>(
-    encryption_pub: &PKeyRef<KT>,
-    oaep_md: MessageDigest,
-    label: LT,
-) -> Result<(Vec<u8>, Vec<u8>), Error>
-where
-    KT: HasPublic,
-    LT: AsRef<[u8]>,
-{
-    let mut encrypter = Encrypter::new(&encryption_pub)?;
-    encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
-    encrypter.set_rsa_oaep_md(oaep_md)?;
-    encrypter.set_rsa_mgf1_md(oaep_md)?;
-    encrypter.set_rsa_oaep_label(label.as_ref())?;
+impl Credential {
+    pub fn new<IT: AsRef<[u8]>, ST: AsRef<[u8]>>(id_object: IT, encrypted_secret: ST) -> Self {
+        Credential {
+            id_object: id_object.as_ref().to_vec(),
+            encrypted_secret: encrypted_secret.as_ref().to_vec(),
+        }
+    }
 
-    let mut seed = vec![0; oaep_md.size()];
-    rand_bytes(&mut seed)?;
+    /// The buffer of the credentialBlob TPM2B_ID_OBJECT
+    pub fn id_object(&self) -> &[u8] {
+        &self.id_object
+    }
 
-    let mut encrypted_seed = vec![0; encrypter.encrypt_len(&seed)?];
-    encrypter.encrypt(&seed, &mut encrypted_seed)?;
+    /// The buffer of the secret TPM2B_ENCRYPTED_SECRET
+    pub fn encrypted_secret(&self) -> &[u8] {
+        &self.encrypted_secret
+    }
+}
 
-    Ok((seed, encrypted_seed))
+fn symmetric_key_bits(encryption_key: &TpmtPublic) -> Result<u32, Error> {
+    match encryption_key.parameters.symmetric() {
+        SymmetricDefinition::Aes { key_bits } => Ok(key_bits as u32),
+        _ => Err(Error::InvalidParent),
+    }
 }
 
-fn build_seed<KT, LT>(
-    encryption_pub: &PKeyRef<KT>,
-    oaep_md: MessageDigest,
-    label: LT,
-) -> Result<(Vec<u8>, Vec<u8>), Error>
+/// Protects a credential value for the object with object_name, so that only the
+/// TPM holding the private part of encryption_key (usually the EK) can recover it.
+#[cfg(feature = "rand")]
+pub fn make_credential<CVT, ONT>(
+    credential_value: CVT,
+    encryption_key: &TpmtPublic,
+    object_name: ONT,
+) -> Result<Credential, Error>
 where
-    KT: HasPublic,
-    LT: AsRef<[u8]>,
+    CVT: AsRef<[u8]>,
+    ONT: AsRef<[u8]>,
 {
-    let key_id = encryption_pub.id();
-
-    if key_id == pkey_id::RSA {
-        build_seed_rsa(encryption_pub, oaep_md, label)
-    } else {
-        todo!();
-    }
+    make_credential_with(provider(), credential_value, encryption_key, object_name)
 }
 
-pub fn make_credential<CVT, KT, ONT>(
+/// Like make_credential, with the cryptographic operations done by provider
+pub fn make_credential_with<CVT, ONT>(
+    provider: &dyn CryptoProvider,
     credential_value: CVT,
-    encryption_namealg: MessageDigest,
-    encryption_pub: &PKeyRef<KT>,
+    encryption_key: &TpmtPublic,
     object_name: ONT,
 ) -> Result<Credential, Error>
 where
     CVT: AsRef<[u8]>,
-    KT: HasPublic,
     ONT: AsRef<[u8]>,
 {
+    let encryption_namealg = encryption_key.name_alg;
+    if credential_value.as_ref().len() > encryption_namealg.digest_size() {
+        return Err(Error::InvalidSize);
+    }
+
     let (seed, encrypted_seed) = build_seed(
-        encryption_pub,
+        provider,
+        encryption_key,
         encryption_namealg,
-        &CREDENTIAL_LABEL_IDENTITY,
+        CREDENTIAL_LABEL_IDENTITY,
     )?;
 
     let symkey = kdf_a(
+        provider,
         encryption_namealg,
         &seed,
-        &CREDENTIAL_LABEL_SYMKEY,
+        CREDENTIAL_LABEL_SYMKEY,
         &object_name,
-        &[],
-        encryption_namealg.size() as u32,
+        [],
+        symmetric_key_bits(encryption_key)?,
     )?;
     let hmac_key = kdf_a(
+        provider,
         encryption_namealg,
         &seed,
-        &CREDENTIAL_LABEL_INTEGRITY,
-        &[],
-        &[],
-        encryption_namealg.size() as u32,
+        CREDENTIAL_LABEL_INTEGRITY,
+        [],
+        [],
+        (encryption_namealg.digest_size() * 8) as u32,
     )?;
-    todo!();
+
+    let mut credential = Vec::new();
+    Tpm2b::new(credential_value.as_ref())?.to_writer(&mut credential)?;
+    let enc_identity = aes_cfb_encrypt(provider, &symkey, &credential)?;
+    let integrity = provider.hmac(
+        encryption_namealg,
+        &hmac_key,
+        &[&enc_identity, object_name.as_ref()],
+    )?;
+
+    let mut id_object = Vec::new();
+    Tpm2b::new(integrity)?.to_writer(&mut id_object)?;
+    id_object.extend_from_slice(&enc_identity);
+
+    Ok(Credential {
+        id_object,
+        encrypted_secret: encrypted_seed,
+    })
+}
+
+/// Recovers the credential value like TPM2_ActivateCredential, using the private
+/// part of the encryption key in software.
+///
+/// Returns Error::IntegrityCheckFailed if the credential was not made for this
+/// key and object_name, or was modified. Errors recovering the seed from a malformed
+/// secret are returned as they are.
+#[cfg(feature = "openssl")]
+pub fn activate_credential<KT, ONT>(
+    credential: &Credential,
+    decryption_key: &PKeyRef<KT>,
+    encryption_key: &TpmtPublic,
+    object_name: ONT,
+) -> Result<Vec<u8>, Error>
+where
+    KT: HasPrivate,
+    ONT: AsRef<[u8]>,
+{
+    if !PKey::try_from(encryption_key)?.public_eq(decryption_key) {
+        return Err(Error::InvalidParent);
+    }
+    let private = TpmtSensitive::from_private_key(decryption_key, &[], &[])?;
+
+    activate_credential_with(
+        provider(),
+        credential,
+        encryption_key,
+        &private,
+        object_name,
+    )
+}
+
+/// Like activate_credential, with the private part of encryption_key given as its
+/// sensitive area and the cryptographic operations done by provider
+///
+/// Returns Error::InvalidObject if private is not the sensitive area of
+/// encryption_key.
+pub fn activate_credential_with<ONT: AsRef<[u8]>>(
+    provider: &dyn CryptoProvider,
+    credential: &Credential,
+    encryption_key: &TpmtPublic,
+    private: &TpmtSensitive,
+    object_name: ONT,
+) -> Result<Vec<u8>, Error> {
+    let encryption_namealg = encryption_key.name_alg;
+
+    let seed = recover_seed(
+        provider,
+        encryption_key,
+        private,
+        encryption_namealg,
+        CREDENTIAL_LABEL_IDENTITY,
+        &credential.encrypted_secret,
+    )?;
+
+    let symkey = kdf_a(
+        provider,
+        encryption_namealg,
+        &seed,
+        CREDENTIAL_LABEL_SYMKEY,
+        &object_name,
+        [],
+        symmetric_key_bits(encryption_key)?,
+    )?;
+    let hmac_key = kdf_a(
+        provider,
+        encryption_namealg,
+        &seed,
+        CREDENTIAL_LABEL_INTEGRITY,
+        [],
+        [],
+        (encryption_namealg.digest_size() * 8) as u32,
+    )?;
+
+    let mut id_object = &credential.id_object[..];
+    let integrity = Tpm2bRef::from_bytes(&mut id_object)?;
+    verify_hmac(
+        provider,
+        encryption_namealg,
+        &hmac_key,
+        &[id_object, object_name.as_ref()],
+        integrity.contents(),
+    )?;
+
+    let decrypted = aes_cfb_decrypt(provider, &symkey, id_object)?;
+    let mut decrypted = &decrypted[..];
+    let credential_value = Tpm2bRef::from_bytes(&mut decrypted)?;
+    if !decrypted.is_empty() {
+        return Err(Error::InvalidSize);
+    }
+
+    Ok(credential_value.contents().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "openssl")]
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        rsa::Rsa,
+    };
+
+    use super::*;
+    #[cfg(feature = "rustcrypto")]
+    use crate::{
+        attestation::Signature,
+        objects::{EccCurve, EccScheme, KdfScheme, ObjectType},
+        provider::{DigestContext, EcdhOutput},
+        RustCryptoProvider,
+    };
+    use crate::{
+        objects::{ObjectAttributes, PublicParameters},
+        DigestAlgorithm,
+    };
+
+    #[cfg(feature = "openssl")]
+    fn with_aes_128(mut public: TpmtPublic) -> TpmtPublic {
+        match &mut public.parameters {
+            PublicParameters::Rsa { symmetric, .. } | PublicParameters::Ecc { symmetric, .. } => {
+                *symmetric = SymmetricDefinition::Aes { key_bits: 128 }
+            }
+            _ => unreachable!(),
+        }
+        public
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_credential_roundtrip_rsa() {
+        let ek = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ek_public = with_aes_128(
+            TpmtPublic::from_public_key(
+                &ek,
+                DigestAlgorithm::Sha256,
+                ObjectAttributes::from_bits(0x0003_00B2),
+            )
+            .unwrap(),
+        );
+        let name =
+            hex::decode("000b0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20")
+                .unwrap();
+
+        let credential = make_credential(b"challenge", &ek_public, &name).unwrap();
+        assert_eq!(credential.encrypted_secret().len(), 256);
+
+        assert_eq!(
+            activate_credential(&credential, &ek, &ek_public, &name).unwrap(),
+            b"challenge"
+        );
+
+        let mut other_name = name.clone();
+        other_name[2] ^= 0xFF;
+        match activate_credential(&credential, &ek, &ek_public, &other_name) {
+            Err(Error::IntegrityCheckFailed) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_credential_roundtrip_ecc() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ek = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let ek_public = with_aes_128(
+            TpmtPublic::from_public_key(
+                &ek,
+                DigestAlgorithm::Sha256,
+                ObjectAttributes::from_bits(0x0003_00B2),
+            )
+            .unwrap(),
+        );
+        let name = b"\x00\x0bnot a real name but long enough".to_vec();
+
+        let credential = make_credential(b"challenge", &ek_public, &name).unwrap();
+        assert_eq!(
+            activate_credential(&credential, &ek, &ek_public, &name).unwrap(),
+            b"challenge"
+        );
+
+        let mut id_object = credential.id_object().to_vec();
+        let last = id_object.len() - 1;
+        id_object[last] ^= 0x01;
+        let tampered = Credential::new(id_object, credential.encrypted_secret());
+        match activate_credential(&tampered, &ek, &ek_public, &name) {
+            Err(Error::IntegrityCheckFailed) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // A malformed secret is not reported as an integrity failure
+        let mut encrypted_secret = credential.encrypted_secret().to_vec();
+        encrypted_secret.push(0);
+        let malformed = Credential::new(credential.id_object(), encrypted_secret);
+        match activate_credential(&malformed, &ek, &ek_public, &name) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    /// A P-256 key with its public and sensitive areas
+    #[cfg(feature = "rustcrypto")]
+    fn p256_key(private: &str, x: &str, y: &str) -> (TpmtPublic, TpmtSensitive) {
+        let public = TpmtPublic {
+            name_alg: DigestAlgorithm::Sha256,
+            object_attributes: ObjectAttributes::from_bits(0x0003_00B2),
+            auth_policy: Vec::new(),
+            parameters: PublicParameters::Ecc {
+                symmetric: SymmetricDefinition::Aes { key_bits: 128 },
+                scheme: EccScheme::Null,
+                curve: EccCurve::NistP256,
+                kdf: KdfScheme::Null,
+                x: hex::decode(x).unwrap(),
+                y: hex::decode(y).unwrap(),
+            },
+        };
+        let sensitive = TpmtSensitive {
+            sensitive_type: ObjectType::Ecc,
+            auth_value: Vec::new(),
+            seed_value: Vec::new(),
+            sensitive: hex::decode(private).unwrap(),
+        };
+        (public, sensitive)
+    }
+
+    /// A provider that uses a fixed ephemeral key, like one backed by a device that
+    /// generates the keys itself
+    #[cfg(feature = "rustcrypto")]
+    struct FixedEphemeralProvider(TpmtPublic, TpmtSensitive);
+
+    #[cfg(feature = "rustcrypto")]
+    impl CryptoProvider for FixedEphemeralProvider {
+        fn hash(&self, algo: DigestAlgorithm, parts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.hash(algo, parts)
+        }
+
+        fn digest_context(
+            &self,
+            algo: DigestAlgorithm,
+        ) -> Result<alloc::boxed::Box<dyn DigestContext>, Error> {
+            RustCryptoProvider.digest_context(algo)
+        }
+
+        fn hmac(
+            &self,
+            algo: DigestAlgorithm,
+            key: &[u8],
+            parts: &[&[u8]],
+        ) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.hmac(algo, key, parts)
+        }
+
+        fn random_bytes(&self, _buf: &mut [u8]) -> Result<(), Error> {
+            Err(Error::RandomFailed)
+        }
+
+        fn rsa_oaep_encrypt(
+            &self,
+            _key: &TpmtPublic,
+            _algo: DigestAlgorithm,
+            _label: &[u8],
+            _plaintext: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            Err(Error::RandomFailed)
+        }
+
+        fn rsa_oaep_decrypt(
+            &self,
+            key: &TpmtPublic,
+            private: &TpmtSensitive,
+            algo: DigestAlgorithm,
+            label: &[u8],
+            ciphertext: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.rsa_oaep_decrypt(key, private, algo, label, ciphertext)
+        }
+
+        fn ecdh_ephemeral(&self, key: &TpmtPublic) -> Result<EcdhOutput, Error> {
+            let (x, y) = match &key.parameters {
+                PublicParameters::Ecc { x, y, .. } => (x, y),
+                _ => return Err(Error::UnsupportedAlgo),
+            };
+            let z = RustCryptoProvider.ecdh(&self.0, &self.1, x, y)?;
+            match &self.0.parameters {
+                PublicParameters::Ecc { x, y, .. } => Ok((z, x.clone(), y.clone())),
+                _ => Err(Error::UnsupportedAlgo),
+            }
+        }
+
+        fn ecdh(
+            &self,
+            key: &TpmtPublic,
+            private: &TpmtSensitive,
+            peer_x: &[u8],
+            peer_y: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.ecdh(key, private, peer_x, peer_y)
+        }
+
+        fn aes_cfb_encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.aes_cfb_encrypt(key, iv, data)
+        }
+
+        fn aes_cfb_decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+            RustCryptoProvider.aes_cfb_decrypt(key, iv, data)
+        }
+
+        fn verify_signature(
+            &self,
+            key: &TpmtPublic,
+            signature: &Signature,
+            message: &[u8],
+        ) -> Result<(), Error> {
+            RustCryptoProvider.verify_signature(key, signature, message)
+        }
+    }
+
+    #[cfg(feature = "rustcrypto")]
+    #[test]
+    fn test_credential_with_provider() {
+        let (ek_public, ek_private) = p256_key(
+            "10a274dd988474a66c2c1bb5a1cc45d6aa5abf5c6dab92bf35220f54f8b524f1",
+            "9b70b2c8f8318b6250c40050240acde5d77df07319591e8b124c47d11a916b1d",
+            "e14842f3852b41541cbd59e11a277ee663549d8e2a0d852ec0329ae8e68c0969",
+        );
+        let (ephemeral_public, ephemeral_private) = p256_key(
+            "5ef85851a3078f8fbd802a3cac3c91807af8951e61f6de6477e443de2e94d5c5",
+            "0df494da095ee10508fd01dd242ccc08693c9cecee3ef982dff9dc3e5ac05cc4",
+            "e88e3199657ee8a4f4a2fbbb10f50cd94c34258a55d397e962e8fbb141a0df45",
+        );
+        let name = b"\x00\x0bnot a real name but long enough".to_vec();
+
+        // Without an RNG of its own, the provider can still make ECC credentials
+        let provider = FixedEphemeralProvider(ephemeral_public.clone(), ephemeral_private.clone());
+        let credential = make_credential_with(&provider, b"challenge", &ek_public, &name).unwrap();
+        assert_eq!(
+            activate_credential_with(
+                &RustCryptoProvider,
+                &credential,
+                &ek_public,
+                &ek_private,
+                &name
+            )
+            .unwrap(),
+            b"challenge"
+        );
+
+        match activate_credential_with(
+            &RustCryptoProvider,
+            &credential,
+            &ek_public,
+            &ephemeral_private,
+            &name,
+        ) {
+            Err(Error::InvalidObject) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        #[cfg(not(feature = "rand"))]
+        match make_credential_with(&RustCryptoProvider, b"challenge", &ek_public, &name) {
+            Err(Error::RandomFailed) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
 }
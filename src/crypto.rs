@@ -1,30 +1,269 @@
-use openssl::hash::MessageDigest;
+use alloc::{vec, vec::Vec};
+
+#[cfg(feature = "backported_kdf")]
 use openssl_kdf::{Kdf, KdfKbMode, KdfMacType, KdfType};
 
-use crate::Error;
+use crate::{
+    objects::{ObjectType, PublicParameters, Tpm2b, Tpm2bRef, TpmtPublic, TpmtSensitive},
+    provider::{constant_time_eq, CryptoProvider},
+    DigestAlgorithm, Error,
+};
 
 #[cfg(feature = "backported_kdf")]
 pub(crate) fn kdf_a<KT: AsRef<[u8]>, LT: AsRef<[u8]>, CUT: AsRef<[u8]>, CVT: AsRef<[u8]>>(
-    md: MessageDigest,
+    _provider: &dyn CryptoProvider,
+    algo: DigestAlgorithm,
     key: KT,
     label: LT,
-    contextU: CUT,
-    contextV: CVT,
+    context_u: CUT,
+    context_v: CVT,
     bits: u32,
 ) -> Result<Vec<u8>, Error> {
     let mut context: Vec<u8> =
-        Vec::with_capacity(contextU.as_ref().len() + contextV.as_ref().len() - 4);
-    context.extend_from_slice(&contextU.as_ref()[2..]);
-    context.extend_from_slice(&contextV.as_ref()[2..]);
+        Vec::with_capacity(context_u.as_ref().len() + context_v.as_ref().len());
+    context.extend_from_slice(context_u.as_ref());
+    context.extend_from_slice(context_v.as_ref());
     let context = context;
 
     let kdf = Kdf::new(KdfType::KeyBased)?;
     kdf.set_kb_mode(KdfKbMode::Counter)?;
     kdf.set_kb_mac_type(KdfMacType::Hmac)?;
-    kdf.set_digest(md)?;
+    kdf.set_digest(algo.openssl_md()?)?;
     kdf.set_salt(label.as_ref())?;
     kdf.set_kb_info(&context)?;
     kdf.set_key(key.as_ref())?;
 
     Ok(kdf.derive((bits / 8) as usize)?)
 }
+
+/// KDFa from TPM 2.0 Part 1, 11.4.10.2 (SP800-108 in counter mode with HMAC)
+#[cfg(not(feature = "backported_kdf"))]
+pub(crate) fn kdf_a<KT: AsRef<[u8]>, LT: AsRef<[u8]>, CUT: AsRef<[u8]>, CVT: AsRef<[u8]>>(
+    provider: &dyn CryptoProvider,
+    algo: DigestAlgorithm,
+    key: KT,
+    label: LT,
+    context_u: CUT,
+    context_v: CVT,
+    bits: u32,
+) -> Result<Vec<u8>, Error> {
+    let bytes = (bits / 8) as usize;
+    let mut out = Vec::with_capacity(bytes + algo.digest_size());
+
+    let mut counter: u32 = 1;
+    while out.len() < bytes {
+        out.extend_from_slice(&provider.hmac(
+            algo,
+            key.as_ref(),
+            &[
+                &counter.to_be_bytes(),
+                label.as_ref(),
+                &[0],
+                context_u.as_ref(),
+                context_v.as_ref(),
+                &bits.to_be_bytes(),
+            ],
+        )?);
+        counter += 1;
+    }
+    out.truncate(bytes);
+
+    Ok(out)
+}
+
+/// KDFe from TPM 2.0 Part 1, 11.4.10.3 (the SP800-56A concatenation KDF)
+///
+/// The label is used without terminating NUL, that is added here.
+pub(crate) fn kdf_e(
+    provider: &dyn CryptoProvider,
+    algo: DigestAlgorithm,
+    z: &[u8],
+    label: &[u8],
+    party_u: &[u8],
+    party_v: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, Error> {
+    let bytes = (bits / 8) as usize;
+    let mut out = Vec::with_capacity(bytes + algo.digest_size());
+
+    let mut counter: u32 = 1;
+    while out.len() < bytes {
+        out.extend_from_slice(&provider.hash(
+            algo,
+            &[&counter.to_be_bytes(), z, label, &[0], party_u, party_v],
+        )?);
+        counter += 1;
+    }
+    out.truncate(bytes);
+
+    Ok(out)
+}
+
+/// AES-CFB encryption with an all-zero IV, as used for wrapping objects and credentials
+pub(crate) fn aes_cfb_encrypt(
+    provider: &dyn CryptoProvider,
+    key: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    provider.aes_cfb_encrypt(key, &[0; 16], data)
+}
+
+pub(crate) fn aes_cfb_decrypt(
+    provider: &dyn CryptoProvider,
+    key: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    provider.aes_cfb_decrypt(key, &[0; 16], data)
+}
+
+/// Checks an HMAC over parts against an expected value, in constant time
+pub(crate) fn verify_hmac(
+    provider: &dyn CryptoProvider,
+    algo: DigestAlgorithm,
+    key: &[u8],
+    parts: &[&[u8]],
+    expected: &[u8],
+) -> Result<(), Error> {
+    let computed = provider.hmac(algo, key, parts)?;
+    if !constant_time_eq(&computed, expected) {
+        return Err(Error::IntegrityCheckFailed);
+    }
+    Ok(())
+}
+
+fn label_with_nul(label: &[u8]) -> Vec<u8> {
+    let mut label = label.to_vec();
+    label.push(0);
+    label
+}
+
+/// Creates a random seed and the secret that the TPM can recover it from
+///
+/// For RSA keys the secret is the OAEP-encrypted seed, for ECC keys it is the
+/// TPMS_ECC_POINT of an ephemeral key, from which the seed is derived with KDFe.
+pub(crate) fn build_seed<LT: AsRef<[u8]>>(
+    provider: &dyn CryptoProvider,
+    encryption_key: &TpmtPublic,
+    name_alg: DigestAlgorithm,
+    label: LT,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    match encryption_key.object_type() {
+        ObjectType::Rsa => {
+            let mut seed = vec![0; name_alg.digest_size()];
+            provider.random_bytes(&mut seed)?;
+            let encrypted_seed = provider.rsa_oaep_encrypt(
+                encryption_key,
+                name_alg,
+                &label_with_nul(label.as_ref()),
+                &seed,
+            )?;
+            Ok((seed, encrypted_seed))
+        }
+        ObjectType::Ecc => {
+            let (z, ephemeral_x, ephemeral_y) = provider.ecdh_ephemeral(encryption_key)?;
+            // partyVInfo is the x coordinate of the key, padded like the ephemeral one
+            let parent_x = padded_x(encryption_key, ephemeral_x.len())?;
+
+            let seed = kdf_e(
+                provider,
+                name_alg,
+                &z,
+                label.as_ref(),
+                &ephemeral_x,
+                &parent_x,
+                (name_alg.digest_size() * 8) as u32,
+            )?;
+
+            let mut encrypted_seed = Vec::new();
+            Tpm2b::new(ephemeral_x)?.to_writer(&mut encrypted_seed)?;
+            Tpm2b::new(ephemeral_y)?.to_writer(&mut encrypted_seed)?;
+            Ok((seed, encrypted_seed))
+        }
+        _ => Err(Error::UnsupportedAlgo),
+    }
+}
+
+/// The x coordinate of an ECC key, left-padded to size
+fn padded_x(key: &TpmtPublic, size: usize) -> Result<Vec<u8>, Error> {
+    match &key.parameters {
+        PublicParameters::Ecc { x, .. } if x.len() <= size => {
+            let mut padded = vec![0; size - x.len()];
+            padded.extend_from_slice(x);
+            Ok(padded)
+        }
+        _ => Err(Error::InvalidObject),
+    }
+}
+
+/// Recovers a seed created by build_seed, using the private key with the public
+/// area decryption_key and the sensitive area private
+pub(crate) fn recover_seed<LT: AsRef<[u8]>>(
+    provider: &dyn CryptoProvider,
+    decryption_key: &TpmtPublic,
+    private: &TpmtSensitive,
+    name_alg: DigestAlgorithm,
+    label: LT,
+    encrypted_seed: &[u8],
+) -> Result<Vec<u8>, Error> {
+    match decryption_key.object_type() {
+        ObjectType::Rsa => provider.rsa_oaep_decrypt(
+            decryption_key,
+            private,
+            name_alg,
+            &label_with_nul(label.as_ref()),
+            encrypted_seed,
+        ),
+        ObjectType::Ecc => {
+            let mut encrypted_seed = encrypted_seed;
+            let ephemeral_x = Tpm2bRef::from_bytes(&mut encrypted_seed)?;
+            let ephemeral_y = Tpm2bRef::from_bytes(&mut encrypted_seed)?;
+            if !encrypted_seed.is_empty() {
+                return Err(Error::InvalidSize);
+            }
+
+            let z = provider.ecdh(
+                decryption_key,
+                private,
+                ephemeral_x.contents(),
+                ephemeral_y.contents(),
+            )?;
+            let own_x = padded_x(decryption_key, z.len())?;
+
+            kdf_e(
+                provider,
+                name_alg,
+                &z,
+                label.as_ref(),
+                ephemeral_x.contents(),
+                &own_x,
+                (name_alg.digest_size() * 8) as u32,
+            )
+        }
+        _ => Err(Error::UnsupportedAlgo),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::provider;
+
+    #[test]
+    fn test_kdf_a() {
+        let key = hex::decode("00112233445566778899aabbccddeeff").unwrap();
+
+        assert_eq!(
+            kdf_a(
+                provider(),
+                DigestAlgorithm::Sha256,
+                &key,
+                b"STORAGE",
+                hex::decode("000b").unwrap(),
+                hex::decode("0102").unwrap(),
+                128,
+            )
+            .unwrap(),
+            hex::decode("EC38057286FD58BCBCC43D06618FFC4E").unwrap(),
+        );
+    }
+}
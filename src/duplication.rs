@@ -0,0 +1,993 @@
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "openssl")]
+use core::convert::TryFrom;
+
+#[cfg(feature = "openssl")]
+use openssl::pkey::{HasPrivate, PKey, PKeyRef};
+
+#[cfg(any(feature = "rand", feature = "openssl"))]
+use crate::provider::provider;
+use crate::{
+    crypto::{aes_cfb_decrypt, aes_cfb_encrypt, build_seed, kdf_a, recover_seed, verify_hmac},
+    objects::{
+        KeyedHashScheme, ObjectAttributes, ObjectType, PublicParameters, SymmetricDefinition,
+        Tpm2b, Tpm2bRef, TpmtPublic, TpmtSensitive,
+    },
+    provider::{constant_time_eq, CryptoProvider},
+    DigestAlgorithm, Error,
+};
+
+const DUPLICATION_LABEL_SEED: &[u8] = b"DUPLICATE";
+const DUPLICATION_LABEL_SYMKEY: &[u8] = b"STORAGE";
+const DUPLICATION_LABEL_INTEGRITY: &[u8] = b"INTEGRITY";
+
+/// The largest amount of data a KEYEDHASH object can hold (MAX_SYM_DATA)
+const MAX_SEALED_DATA: usize = 128;
+
+/// Checks that TPM2_Import accepts an object with these attributes: it can not be
+/// fixed to a TPM or parent, and encryptedDuplication needs an inner wrapper.
+fn validate_import_attributes(
+    attributes: ObjectAttributes,
+    encryption_key: Option<&[u8]>,
+) -> Result<(), Error> {
+    attributes.validate()?;
+    if attributes.intersects(ObjectAttributes::FIXED_TPM | ObjectAttributes::FIXED_PARENT) {
+        return Err(Error::InvalidObjectAttributes);
+    }
+    if attributes.contains(ObjectAttributes::ENCRYPTED_DUPLICATION) && encryption_key.is_none() {
+        return Err(Error::InvalidObjectAttributes);
+    }
+    Ok(())
+}
+
+/// An object wrapped to a new parent, in the form expected by TPM2_Import
+#[derive(Debug)]
+pub struct DuplicationBlob {
+    public: TpmtPublic,
+    duplicate: Vec<u8>,
+    in_sym_seed: Vec<u8>,
+    symmetric_alg: SymmetricDefinition,
+}
+
+impl DuplicationBlob {
+    /// The objectPublic parameter
+    pub fn public(&self) -> &TpmtPublic {
+        &self.public
+    }
+
+    /// The buffer of the duplicate TPM2B_PRIVATE parameter
+    pub fn duplicate(&self) -> &[u8] {
+        &self.duplicate
+    }
+
+    /// The buffer of the inSymSeed TPM2B_ENCRYPTED_SECRET parameter
+    pub fn in_sym_seed(&self) -> &[u8] {
+        &self.in_sym_seed
+    }
+
+    /// The symmetricAlg parameter, which is Null if no inner wrapper was applied
+    pub fn symmetric_alg(&self) -> SymmetricDefinition {
+        self.symmetric_alg
+    }
+}
+
+/// Wraps an object for import under the given parent
+///
+/// If an encryption_key is given, an inner wrapper is applied with AES-CFB using
+/// that key, and the same key must be passed as encryptionKey to TPM2_Import.
+#[cfg(feature = "rand")]
+pub fn wrap_for_import(
+    parent: &TpmtPublic,
+    public: &TpmtPublic,
+    sensitive: &TpmtSensitive,
+    encryption_key: Option<&[u8]>,
+) -> Result<DuplicationBlob, Error> {
+    wrap_for_import_with(provider(), parent, public, sensitive, encryption_key)
+}
+
+/// Like wrap_for_import, with the cryptographic operations done by provider
+pub fn wrap_for_import_with(
+    provider: &dyn CryptoProvider,
+    parent: &TpmtPublic,
+    public: &TpmtPublic,
+    sensitive: &TpmtSensitive,
+    encryption_key: Option<&[u8]>,
+) -> Result<DuplicationBlob, Error> {
+    if public.object_type() != sensitive.sensitive_type {
+        return Err(Error::InvalidObject);
+    }
+    let parent_key_bits = match parent.parameters.symmetric() {
+        SymmetricDefinition::Aes { key_bits } => key_bits,
+        _ => return Err(Error::InvalidParent),
+    };
+    let parent_alg = parent.name_alg;
+    let name = public.name()?;
+
+    let sensitive = sensitive.to_tpm2b_vec()?;
+    let (dup_sensitive, symmetric_alg) = match encryption_key {
+        None => (sensitive, SymmetricDefinition::Null),
+        Some(encryption_key) => {
+            let inner_integrity = provider.hash(public.name_alg, &[&sensitive, &name])?;
+
+            let mut inner = Vec::new();
+            Tpm2b::new(inner_integrity)?.to_writer(&mut inner)?;
+            inner.extend_from_slice(&sensitive);
+
+            (
+                aes_cfb_encrypt(provider, encryption_key, &inner)?,
+                SymmetricDefinition::Aes {
+                    key_bits: (encryption_key.len() * 8) as u16,
+                },
+            )
+        }
+    };
+
+    let (seed, in_sym_seed) = build_seed(provider, parent, parent_alg, DUPLICATION_LABEL_SEED)?;
+    let duplicate = outer_wrap(
+        provider,
+        parent_alg,
+        parent_key_bits,
+        &seed,
+        &name,
+        &dup_sensitive,
+    )?;
+
+    Ok(DuplicationBlob {
+        public: public.clone(),
+        duplicate,
+        in_sym_seed,
+        symmetric_alg,
+    })
+}
+
+/// Applies the outer wrapper for a parent with the given nameAlg and symmetric key size
+fn outer_wrap(
+    provider: &dyn CryptoProvider,
+    parent_alg: DigestAlgorithm,
+    parent_key_bits: u16,
+    seed: &[u8],
+    name: &[u8],
+    dup_sensitive: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let symkey = kdf_a(
+        provider,
+        parent_alg,
+        seed,
+        DUPLICATION_LABEL_SYMKEY,
+        name,
+        [],
+        parent_key_bits as u32,
+    )?;
+    let hmac_key = kdf_a(
+        provider,
+        parent_alg,
+        seed,
+        DUPLICATION_LABEL_INTEGRITY,
+        [],
+        [],
+        (parent_alg.digest_size() * 8) as u32,
+    )?;
+
+    let encrypted = aes_cfb_encrypt(provider, &symkey, dup_sensitive)?;
+    let outer_hmac = provider.hmac(parent_alg, &hmac_key, &[&encrypted, name])?;
+
+    let mut duplicate = Vec::with_capacity(2 + outer_hmac.len() + encrypted.len());
+    Tpm2b::new(outer_hmac)?.to_writer(&mut duplicate)?;
+    duplicate.extend_from_slice(&encrypted);
+
+    Ok(duplicate)
+}
+
+/// Recovers the sensitive area of an object from the output of TPM2_Duplicate
+///
+/// The parent_key is the private key of the new parent the object was duplicated
+/// to, and public is the public area of the duplicated object. If an inner wrapper
+/// was applied, its encryption_key must be provided and its integrity is checked.
+#[cfg(feature = "openssl")]
+pub fn unwrap_duplicate<KT: HasPrivate>(
+    parent_key: &PKeyRef<KT>,
+    parent: &TpmtPublic,
+    public: &TpmtPublic,
+    duplicate: &[u8],
+    in_sym_seed: &[u8],
+    encryption_key: Option<&[u8]>,
+) -> Result<TpmtSensitive, Error> {
+    if !PKey::try_from(parent)?.public_eq(parent_key) {
+        return Err(Error::InvalidParent);
+    }
+    let parent_sensitive = TpmtSensitive::from_private_key(parent_key, &[], &[])?;
+
+    unwrap_duplicate_with(
+        provider(),
+        parent,
+        &parent_sensitive,
+        public,
+        duplicate,
+        in_sym_seed,
+        encryption_key,
+    )
+}
+
+/// Like unwrap_duplicate, with the private part of the new parent given as its
+/// sensitive area and the cryptographic operations done by provider
+///
+/// Returns Error::InvalidObject if parent_sensitive is not the sensitive area of
+/// parent.
+pub fn unwrap_duplicate_with(
+    provider: &dyn CryptoProvider,
+    parent: &TpmtPublic,
+    parent_sensitive: &TpmtSensitive,
+    public: &TpmtPublic,
+    duplicate: &[u8],
+    in_sym_seed: &[u8],
+    encryption_key: Option<&[u8]>,
+) -> Result<TpmtSensitive, Error> {
+    let parent_key_bits = match parent.parameters.symmetric() {
+        SymmetricDefinition::Aes { key_bits } => key_bits,
+        _ => return Err(Error::InvalidParent),
+    };
+    let parent_alg = parent.name_alg;
+    let name = public.name()?;
+
+    let seed = recover_seed(
+        provider,
+        parent,
+        parent_sensitive,
+        parent_alg,
+        DUPLICATION_LABEL_SEED,
+        in_sym_seed,
+    )?;
+    let symkey = kdf_a(
+        provider,
+        parent_alg,
+        &seed,
+        DUPLICATION_LABEL_SYMKEY,
+        &name,
+        [],
+        parent_key_bits as u32,
+    )?;
+    let hmac_key = kdf_a(
+        provider,
+        parent_alg,
+        &seed,
+        DUPLICATION_LABEL_INTEGRITY,
+        [],
+        [],
+        (parent_alg.digest_size() * 8) as u32,
+    )?;
+
+    let mut duplicate = duplicate;
+    let outer_hmac = Tpm2bRef::from_bytes(&mut duplicate)?;
+    verify_hmac(
+        provider,
+        parent_alg,
+        &hmac_key,
+        &[duplicate, &name],
+        outer_hmac.contents(),
+    )?;
+    let dup_sensitive = aes_cfb_decrypt(provider, &symkey, duplicate)?;
+
+    let sensitive = match encryption_key {
+        None => dup_sensitive,
+        Some(encryption_key) => {
+            let inner = aes_cfb_decrypt(provider, encryption_key, &dup_sensitive)?;
+            let mut inner = &inner[..];
+            // A wrong key yields garbage, which should not be reported as a parse error
+            let inner_integrity =
+                Tpm2bRef::from_bytes(&mut inner).map_err(|_| Error::IntegrityCheckFailed)?;
+
+            let computed = provider.hash(public.name_alg, &[inner, &name])?;
+            if !constant_time_eq(&computed, inner_integrity.contents()) {
+                return Err(Error::IntegrityCheckFailed);
+            }
+            inner.to_vec()
+        }
+    };
+
+    let mut sensitive = &sensitive[..];
+    let sensitive = Tpm2bRef::from_bytes(&mut sensitive)?;
+    let mut sensitive = sensitive.contents();
+    let sensitive = TpmtSensitive::from_reader(&mut sensitive)?;
+    if sensitive.sensitive_type != public.object_type() {
+        return Err(Error::InvalidObject);
+    }
+
+    Ok(sensitive)
+}
+
+/// Builds a sealed data object (a KEYEDHASH object without scheme) for import
+#[derive(Debug)]
+pub struct SealedObjectBuilder {
+    name_alg: DigestAlgorithm,
+    object_attributes: ObjectAttributes,
+    auth_value: Vec<u8>,
+    auth_policy: Vec<u8>,
+    data: Vec<u8>,
+    encryption_key: Option<Vec<u8>>,
+}
+
+impl Default for SealedObjectBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SealedObjectBuilder {
+    pub fn new() -> Self {
+        SealedObjectBuilder {
+            name_alg: DigestAlgorithm::Sha256,
+            object_attributes: ObjectAttributes::USER_WITH_AUTH,
+            auth_value: Vec::new(),
+            auth_policy: Vec::new(),
+            data: Vec::new(),
+            encryption_key: None,
+        }
+    }
+
+    pub fn set_name_alg(&mut self, val: DigestAlgorithm) -> &mut Self {
+        self.name_alg = val;
+        self
+    }
+
+    /// Sets the TPMA_OBJECT attributes, which default to userWithAuth
+    ///
+    /// Imported objects can not have fixedTPM or fixedParent set, and
+    /// encryptedDuplication requires an encryption key.
+    pub fn set_object_attributes(&mut self, val: ObjectAttributes) -> &mut Self {
+        self.object_attributes = val;
+        self
+    }
+
+    pub fn set_auth_value<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.auth_value = val.as_ref().to_vec();
+        self
+    }
+
+    pub fn set_auth_policy<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.auth_policy = val.as_ref().to_vec();
+        self
+    }
+
+    pub fn set_data<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.data = val.as_ref().to_vec();
+        self
+    }
+
+    /// Applies an inner wrapper with an AES key of 16, 24 or 32 bytes
+    pub fn set_encryption_key<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.encryption_key = Some(val.as_ref().to_vec());
+        self
+    }
+
+    #[cfg(feature = "rand")]
+    pub fn build(&self, parent: &TpmtPublic) -> Result<DuplicationBlob, Error> {
+        self.build_with(provider(), parent)
+    }
+
+    /// Like build, with the cryptographic operations done by provider
+    pub fn build_with(
+        &self,
+        provider: &dyn CryptoProvider,
+        parent: &TpmtPublic,
+    ) -> Result<DuplicationBlob, Error> {
+        validate_import_attributes(self.object_attributes, self.encryption_key.as_deref())?;
+        let digest_size = self.name_alg.digest_size();
+        if self.data.len() > MAX_SEALED_DATA {
+            return Err(Error::InvalidSize);
+        }
+        if self.auth_value.len() > digest_size
+            || !(self.auth_policy.is_empty() || self.auth_policy.len() == digest_size)
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        let mut seed_value = vec![0; digest_size];
+        provider.random_bytes(&mut seed_value)?;
+
+        let unique = provider.hash(self.name_alg, &[&seed_value, &self.data])?;
+
+        let public = TpmtPublic {
+            name_alg: self.name_alg,
+            object_attributes: self.object_attributes,
+            auth_policy: self.auth_policy.clone(),
+            parameters: PublicParameters::KeyedHash {
+                scheme: KeyedHashScheme::Null,
+                unique,
+            },
+        };
+        let sensitive = TpmtSensitive {
+            sensitive_type: ObjectType::KeyedHash,
+            auth_value: self.auth_value.clone(),
+            seed_value,
+            sensitive: self.data.clone(),
+        };
+
+        wrap_for_import_with(
+            provider,
+            parent,
+            &public,
+            &sensitive,
+            self.encryption_key.as_deref(),
+        )
+    }
+}
+
+/// Wraps an existing RSA or EC private key for import
+///
+/// This takes OpenSSL keys, so it needs the openssl feature. With other providers,
+/// build the public and sensitive areas of the key and use wrap_for_import_with.
+#[cfg(feature = "openssl")]
+#[derive(Debug)]
+pub struct KeyImportBuilder {
+    name_alg: DigestAlgorithm,
+    object_attributes: ObjectAttributes,
+    auth_value: Vec<u8>,
+    auth_policy: Vec<u8>,
+    symmetric: Option<SymmetricDefinition>,
+    encryption_key: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "openssl")]
+impl Default for KeyImportBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl KeyImportBuilder {
+    pub fn new() -> Self {
+        KeyImportBuilder {
+            name_alg: DigestAlgorithm::Sha256,
+            object_attributes: ObjectAttributes::USER_WITH_AUTH | ObjectAttributes::SIGN_ENCRYPT,
+            auth_value: Vec::new(),
+            auth_policy: Vec::new(),
+            symmetric: None,
+            encryption_key: None,
+        }
+    }
+
+    pub fn set_name_alg(&mut self, val: DigestAlgorithm) -> &mut Self {
+        self.name_alg = val;
+        self
+    }
+
+    /// Sets the TPMA_OBJECT attributes, which default to userWithAuth and sign
+    ///
+    /// Imported objects can not have fixedTPM or fixedParent set, and
+    /// encryptedDuplication requires an encryption key.
+    pub fn set_object_attributes(&mut self, val: ObjectAttributes) -> &mut Self {
+        self.object_attributes = val;
+        self
+    }
+
+    pub fn set_auth_value<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.auth_value = val.as_ref().to_vec();
+        self
+    }
+
+    pub fn set_auth_policy<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.auth_policy = val.as_ref().to_vec();
+        self
+    }
+
+    /// Sets the symmetric algorithm a storage key protects its children with
+    ///
+    /// Storage keys (restricted and decrypt) default to AES-128-CFB, and all other
+    /// keys must use Null.
+    pub fn set_symmetric(&mut self, val: SymmetricDefinition) -> &mut Self {
+        self.symmetric = Some(val);
+        self
+    }
+
+    /// Applies an inner wrapper with an AES key of 16, 24 or 32 bytes
+    pub fn set_encryption_key<T: AsRef<[u8]>>(&mut self, val: T) -> &mut Self {
+        self.encryption_key = Some(val.as_ref().to_vec());
+        self
+    }
+
+    /// Returns the public and sensitive areas the key would be imported as
+    pub fn build_object<T: HasPrivate>(
+        &self,
+        key: &PKeyRef<T>,
+    ) -> Result<(TpmtPublic, TpmtSensitive), Error> {
+        validate_import_attributes(self.object_attributes, self.encryption_key.as_deref())?;
+        let digest_size = self.name_alg.digest_size();
+        if self.auth_value.len() > digest_size
+            || !(self.auth_policy.is_empty() || self.auth_policy.len() == digest_size)
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        let storage = self
+            .object_attributes
+            .contains(ObjectAttributes::RESTRICTED | ObjectAttributes::DECRYPT);
+        let symmetric = match (storage, self.symmetric) {
+            (true, None) => SymmetricDefinition::Aes { key_bits: 128 },
+            (true, Some(SymmetricDefinition::Null)) => return Err(Error::InvalidObject),
+            (false, Some(symmetric)) if symmetric != SymmetricDefinition::Null => {
+                return Err(Error::InvalidObject)
+            }
+            (_, symmetric) => symmetric.unwrap_or(SymmetricDefinition::Null),
+        };
+
+        let mut public = TpmtPublic::from_public_key(key, self.name_alg, self.object_attributes)?;
+        public.auth_policy = self.auth_policy.clone();
+        match &mut public.parameters {
+            PublicParameters::Rsa { symmetric: sym, .. }
+            | PublicParameters::Ecc { symmetric: sym, .. } => *sym = symmetric,
+            _ => return Err(Error::UnsupportedAlgo),
+        }
+
+        // Only storage keys use the seed, to derive the protection of their children
+        let mut seed_value = Vec::new();
+        if storage {
+            seed_value.resize(digest_size, 0);
+            provider().random_bytes(&mut seed_value)?;
+        }
+        let sensitive = TpmtSensitive::from_private_key(key, &self.auth_value, &seed_value)?;
+
+        Ok((public, sensitive))
+    }
+
+    pub fn build<T: HasPrivate>(
+        &self,
+        key: &PKeyRef<T>,
+        parent: &TpmtPublic,
+    ) -> Result<DuplicationBlob, Error> {
+        let (public, sensitive) = self.build_object(key)?;
+
+        wrap_for_import(parent, &public, &sensitive, self.encryption_key.as_deref())
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use openssl::{
+        bn::{BigNum, BigNumContext},
+        ec::{EcGroup, EcKey, EcPoint},
+        encrypt::Decrypter,
+        nid::Nid,
+        pkey::{PKey, Private},
+        rsa::{Padding, Rsa},
+        symm::{decrypt, Cipher},
+    };
+
+    use super::*;
+    use crate::objects::{RsaScheme, TpmtPublic};
+
+    fn storage_parent() -> (PKey<Private>, TpmtPublic) {
+        let rsa = Rsa::generate(2048).unwrap();
+        let public = TpmtPublic {
+            name_alg: DigestAlgorithm::Sha256,
+            object_attributes: ObjectAttributes::from_bits(0x0003_0072),
+            auth_policy: Vec::new(),
+            parameters: PublicParameters::Rsa {
+                symmetric: SymmetricDefinition::Aes { key_bits: 128 },
+                scheme: RsaScheme::Null,
+                key_bits: 2048,
+                exponent: 0,
+                modulus: rsa.n().to_vec(),
+            },
+        };
+        (PKey::from_rsa(rsa).unwrap(), public)
+    }
+
+    #[test]
+    fn test_sealed_object_rsa_parent() {
+        let (parent_key, parent) = storage_parent();
+
+        let blob = SealedObjectBuilder::new()
+            .set_auth_value(b"password")
+            .set_data(b"super secret")
+            .build(&parent)
+            .unwrap();
+
+        let mut decrypter = Decrypter::new(&parent_key).unwrap();
+        decrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+        decrypter
+            .set_rsa_oaep_md(openssl::hash::MessageDigest::sha256())
+            .unwrap();
+        decrypter
+            .set_rsa_mgf1_md(openssl::hash::MessageDigest::sha256())
+            .unwrap();
+        decrypter.set_rsa_oaep_label(b"DUPLICATE\0").unwrap();
+        let mut seed = vec![0; decrypter.decrypt_len(blob.in_sym_seed()).unwrap()];
+        let len = decrypter.decrypt(blob.in_sym_seed(), &mut seed).unwrap();
+        seed.truncate(len);
+
+        let md = DigestAlgorithm::Sha256;
+        let name = blob.public().name().unwrap();
+        let mut duplicate = blob.duplicate();
+        let outer_hmac = Tpm2b::from_reader(&mut duplicate).unwrap();
+
+        let hmac_key = kdf_a(provider(), md, &seed, "INTEGRITY", [], [], 256).unwrap();
+        assert_eq!(
+            provider().hmac(md, &hmac_key, &[duplicate, &name]).unwrap(),
+            outer_hmac.contents(),
+        );
+
+        let symkey = kdf_a(provider(), md, &seed, "STORAGE", &name, [], 128).unwrap();
+        let decrypted =
+            decrypt(Cipher::aes_128_cfb128(), &symkey, Some(&[0; 16]), duplicate).unwrap();
+        let mut decrypted = &decrypted[..];
+        let sensitive = Tpm2b::from_reader(&mut decrypted).unwrap();
+        let sensitive = TpmtSensitive::from_reader(sensitive.contents()).unwrap();
+
+        assert_eq!(sensitive.sensitive_type, ObjectType::KeyedHash);
+        assert_eq!(sensitive.auth_value, b"password");
+        assert_eq!(sensitive.sensitive, b"super secret");
+    }
+
+    #[test]
+    fn test_import_ec_key_ec_parent() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let parent_key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut parent = TpmtPublic::from_public_key(
+            &parent_key,
+            DigestAlgorithm::Sha256,
+            ObjectAttributes::from_bits(0x0003_0072),
+        )
+        .unwrap();
+        if let PublicParameters::Ecc { symmetric, .. } = &mut parent.parameters {
+            *symmetric = SymmetricDefinition::Aes { key_bits: 128 };
+        }
+
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let blob = KeyImportBuilder::new()
+            .set_encryption_key(&[0x42; 16][..])
+            .build(&key, &parent)
+            .unwrap();
+
+        let public = PKey::try_from(blob.public()).unwrap();
+        assert!(public.public_eq(&key));
+        assert_eq!(
+            blob.symmetric_alg(),
+            SymmetricDefinition::Aes { key_bits: 128 }
+        );
+
+        // The inSymSeed is the TPMS_ECC_POINT of the ephemeral key
+        let mut in_sym_seed = blob.in_sym_seed();
+        assert_eq!(
+            Tpm2b::from_reader(&mut in_sym_seed)
+                .unwrap()
+                .contents()
+                .len(),
+            32
+        );
+        assert_eq!(
+            Tpm2b::from_reader(&mut in_sym_seed)
+                .unwrap()
+                .contents()
+                .len(),
+            32
+        );
+        assert!(in_sym_seed.is_empty());
+    }
+
+    #[test]
+    fn test_import_rsa_key_object() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let (public, sensitive) = KeyImportBuilder::new()
+            .set_auth_value(b"password")
+            .build_object(&key)
+            .unwrap();
+
+        assert!(PKey::try_from(&public).unwrap().public_eq(&key));
+        assert_eq!(sensitive.sensitive_type, ObjectType::Rsa);
+        assert_eq!(sensitive.sensitive.len(), 128);
+        assert!(sensitive.seed_value.is_empty());
+        match public.parameters {
+            PublicParameters::Rsa {
+                key_bits, exponent, ..
+            } => {
+                assert_eq!(key_bits, 2048);
+                assert_eq!(exponent, 0);
+            }
+            _ => panic!("Not an RSA key"),
+        }
+    }
+
+    #[test]
+    fn test_unwrap_rsa_key_rsa_parent() {
+        let (parent_key, parent) = storage_parent();
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let blob = KeyImportBuilder::new()
+            .set_auth_value(b"password")
+            .build(&key, &parent)
+            .unwrap();
+        let sensitive = unwrap_duplicate(
+            &parent_key,
+            &parent,
+            blob.public(),
+            blob.duplicate(),
+            blob.in_sym_seed(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(sensitive.auth_value, b"password");
+        let recovered = sensitive
+            .to_private_key(blob.public())
+            .unwrap()
+            .rsa()
+            .unwrap();
+        let key = key.rsa().unwrap();
+        assert_eq!(recovered.n(), key.n());
+        assert_eq!(recovered.p(), key.p());
+        assert_eq!(recovered.q(), key.q());
+    }
+
+    #[test]
+    fn test_unwrap_ec_key_ec_parent_inner() {
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let parent_key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut parent = TpmtPublic::from_public_key(
+            &parent_key,
+            DigestAlgorithm::Sha384,
+            ObjectAttributes::from_bits(0x0003_0072),
+        )
+        .unwrap();
+        if let PublicParameters::Ecc { symmetric, .. } = &mut parent.parameters {
+            *symmetric = SymmetricDefinition::Aes { key_bits: 256 };
+        }
+
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let blob = KeyImportBuilder::new()
+            .set_encryption_key(&[0x42; 32][..])
+            .build(&key, &parent)
+            .unwrap();
+
+        let sensitive = unwrap_duplicate(
+            &parent_key,
+            &parent,
+            blob.public(),
+            blob.duplicate(),
+            blob.in_sym_seed(),
+            Some(&[0x42; 32]),
+        )
+        .unwrap();
+        let recovered = sensitive.to_private_key(blob.public()).unwrap();
+        assert_eq!(
+            recovered.ec_key().unwrap().private_key(),
+            key.ec_key().unwrap().private_key()
+        );
+
+        match unwrap_duplicate(
+            &parent_key,
+            &parent,
+            blob.public(),
+            blob.duplicate(),
+            blob.in_sym_seed(),
+            Some(&[0x43; 32]),
+        ) {
+            Err(Error::IntegrityCheckFailed) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "rustcrypto")]
+    #[test]
+    fn test_unwrap_with_provider() {
+        use crate::RustCryptoProvider;
+
+        let (parent_key, parent) = storage_parent();
+        let parent_sensitive = TpmtSensitive::from_private_key(&parent_key, &[], &[]).unwrap();
+
+        let blob = SealedObjectBuilder::new()
+            .set_data(b"super secret")
+            .set_encryption_key([0x42; 16])
+            .build_with(&RustCryptoProvider, &parent)
+            .unwrap();
+        let sensitive = unwrap_duplicate_with(
+            &RustCryptoProvider,
+            &parent,
+            &parent_sensitive,
+            blob.public(),
+            blob.duplicate(),
+            blob.in_sym_seed(),
+            Some(&[0x42; 16]),
+        )
+        .unwrap();
+        assert_eq!(sensitive.sensitive, b"super secret");
+
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other = TpmtSensitive::from_private_key(&other, &[], &[]).unwrap();
+        match unwrap_duplicate_with(
+            &RustCryptoProvider,
+            &parent,
+            &other,
+            blob.public(),
+            blob.duplicate(),
+            blob.in_sym_seed(),
+            Some(&[0x42; 16]),
+        ) {
+            Err(Error::InvalidObject) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_unwrap_tampered() {
+        let (parent_key, parent) = storage_parent();
+
+        let blob = SealedObjectBuilder::new()
+            .set_data(b"super secret")
+            .build(&parent)
+            .unwrap();
+        let mut duplicate = blob.duplicate().to_vec();
+        let last = duplicate.len() - 1;
+        duplicate[last] ^= 0x01;
+
+        match unwrap_duplicate(
+            &parent_key,
+            &parent,
+            blob.public(),
+            &duplicate,
+            blob.in_sym_seed(),
+            None,
+        ) {
+            Err(Error::IntegrityCheckFailed) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_import_storage_key_symmetric() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let storage = ObjectAttributes::from_bits(0x0003_0040);
+
+        let (public, sensitive) = KeyImportBuilder::new()
+            .set_object_attributes(storage)
+            .build_object(&key)
+            .unwrap();
+        assert_eq!(
+            public.parameters.symmetric(),
+            SymmetricDefinition::Aes { key_bits: 128 }
+        );
+        assert_eq!(sensitive.seed_value.len(), 32);
+
+        let (public, _) = KeyImportBuilder::new()
+            .set_object_attributes(storage)
+            .set_symmetric(SymmetricDefinition::Aes { key_bits: 256 })
+            .build_object(&key)
+            .unwrap();
+        assert_eq!(
+            public.parameters.symmetric(),
+            SymmetricDefinition::Aes { key_bits: 256 }
+        );
+
+        match KeyImportBuilder::new()
+            .set_object_attributes(storage)
+            .set_symmetric(SymmetricDefinition::Null)
+            .build_object(&key)
+        {
+            Err(Error::InvalidObject) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match KeyImportBuilder::new()
+            .set_symmetric(SymmetricDefinition::Aes { key_bits: 128 })
+            .build_object(&key)
+        {
+            Err(Error::InvalidObject) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_import_key_attributes() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        for attributes in [
+            ObjectAttributes::SIGN_ENCRYPT | ObjectAttributes::FIXED_TPM,
+            ObjectAttributes::SIGN_ENCRYPT | ObjectAttributes::FIXED_PARENT,
+            ObjectAttributes::SIGN_ENCRYPT | ObjectAttributes::ENCRYPTED_DUPLICATION,
+            ObjectAttributes::RESTRICTED
+                | ObjectAttributes::DECRYPT
+                | ObjectAttributes::SIGN_ENCRYPT,
+        ] {
+            match KeyImportBuilder::new()
+                .set_object_attributes(attributes)
+                .build_object(&key)
+            {
+                Err(Error::InvalidObjectAttributes) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        KeyImportBuilder::new()
+            .set_object_attributes(
+                ObjectAttributes::SIGN_ENCRYPT | ObjectAttributes::ENCRYPTED_DUPLICATION,
+            )
+            .set_encryption_key(&[0x42; 16][..])
+            .build_object(&key)
+            .unwrap();
+    }
+
+    // Computed independently from the Part 1 definitions of KDFa and the outer wrapper
+    // with pyca/cryptography, for the P-256 key of RFC 6979 A.2.5 with fixed seeds.
+    const STORAGE_KEY_PUBLIC: &str = "0023000b000300400000000600800043001000030010002060fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb600207903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+    const STORAGE_KEY_DUPLICATE: &str = "0020ab15573d1722627279534aeae3768a8972b88187ca1485bcf1c29e7be502c72ac5461b4c5106b10090b33ee9d453b471a6e3161e6e6cf145e4d52cc19f952a3e4096ec33d5f89dd5d5c96ca6cd9d17c086e3171056e4f53833092c543249a76635b197d60e8e23929075bd89790046b76ff8";
+
+    #[test]
+    fn test_import_storage_key_vector() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let d = BigNum::from_hex_str(
+            "C9AFA9D845BA75166B5C215767B1D6934E50C3DB36E89B127B8A622B120F6721",
+        )
+        .unwrap();
+        let mut point = EcPoint::new(&group).unwrap();
+        let mut ctx = BigNumContext::new().unwrap();
+        point.mul_generator2(&group, &d, &mut ctx).unwrap();
+        let key =
+            PKey::from_ec_key(EcKey::from_private_components(&group, &d, &point).unwrap()).unwrap();
+
+        let (public, mut sensitive) = KeyImportBuilder::new()
+            .set_object_attributes(ObjectAttributes::from_bits(0x0003_0040))
+            .set_auth_value(b"password")
+            .build_object(&key)
+            .unwrap();
+        sensitive.seed_value = (0x20..0x40).collect();
+
+        let mut marshalled = Vec::new();
+        public.to_writer(&mut marshalled).unwrap();
+        assert_eq!(hex::encode(marshalled), STORAGE_KEY_PUBLIC);
+
+        let seed: Vec<u8> = (0x40..0x60).collect();
+        let duplicate = outer_wrap(
+            provider(),
+            DigestAlgorithm::Sha256,
+            128,
+            &seed,
+            &public.name().unwrap(),
+            &sensitive.to_tpm2b_vec().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(hex::encode(duplicate), STORAGE_KEY_DUPLICATE);
+    }
+
+    #[test]
+    fn test_sealed_object_attributes() {
+        let (_, parent) = storage_parent();
+
+        for attributes in [
+            ObjectAttributes::USER_WITH_AUTH | ObjectAttributes::FIXED_TPM,
+            ObjectAttributes::USER_WITH_AUTH | ObjectAttributes::FIXED_PARENT,
+            ObjectAttributes::USER_WITH_AUTH | ObjectAttributes::ENCRYPTED_DUPLICATION,
+            ObjectAttributes::from_bits(1 << 3),
+        ] {
+            match SealedObjectBuilder::new()
+                .set_object_attributes(attributes)
+                .set_data(b"super secret")
+                .build(&parent)
+            {
+                Err(Error::InvalidObjectAttributes) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        let blob = SealedObjectBuilder::new()
+            .set_object_attributes(
+                ObjectAttributes::USER_WITH_AUTH | ObjectAttributes::ENCRYPTED_DUPLICATION,
+            )
+            .set_data(b"super secret")
+            .set_encryption_key(&[0x42; 16][..])
+            .build(&parent)
+            .unwrap();
+        assert!(blob
+            .public()
+            .object_attributes
+            .contains(ObjectAttributes::ENCRYPTED_DUPLICATION));
+    }
+
+    #[test]
+    fn test_sealed_object_too_large() {
+        let (_, parent) = storage_parent();
+
+        SealedObjectBuilder::new()
+            .set_data(&[0; 129][..])
+            .build(&parent)
+            .unwrap_err();
+    }
+}
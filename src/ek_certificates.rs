@@ -0,0 +1,717 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use openssl::{
+    pkey::PKey,
+    stack::Stack,
+    x509::{
+        store::{X509Store, X509StoreBuilder},
+        verify::X509VerifyFlags,
+        X509Ref, X509StoreContext, X509,
+    },
+};
+
+use crate::{io::take, objects::TpmtPublic, Error};
+
+const OID_TPM_MANUFACTURER: &str = "2.23.133.2.1";
+const OID_TPM_MODEL: &str = "2.23.133.2.2";
+const OID_TPM_VERSION: &str = "2.23.133.2.3";
+
+const DER_BOOLEAN: u8 = 0x01;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_UTF8_STRING: u8 = 0x0C;
+const DER_IA5_STRING: u8 = 0x16;
+const DER_VISIBLE_STRING: u8 = 0x1A;
+const DER_BMP_STRING: u8 = 0x1E;
+const DER_SEQUENCE: u8 = 0x30;
+/// The explicit [3] tag of the extensions in a TBSCertificate
+const DER_EXTENSIONS: u8 = 0xA3;
+
+/// The encoded extendedKeyUsage (2.5.29.37) OID
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+/// The encoded certificatePolicies (2.5.29.32) OID
+const OID_CERTIFICATE_POLICIES: &[u8] = &[0x55, 0x1D, 0x20];
+/// The encoded tcg-kp-EKCertificate (2.23.133.8.1) OID
+const OID_EK_CERTIFICATE_USAGE: &[u8] = &[0x67, 0x81, 0x05, 0x08, 0x01];
+/// The encoded id-qt-cps (1.3.6.1.5.5.7.2.1) OID
+const OID_QT_CPS: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x02, 0x01];
+/// The encoded id-qt-unotice (1.3.6.1.5.5.7.2.2) OID
+const OID_QT_UNOTICE: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x02, 0x02];
+
+/// The user notice the TCG EK Credential Profile requires in the certificate policies
+const TCG_EK_USER_NOTICE: &str = "TCG Trusted Platform Endorsement";
+
+/// A TPM manufacturer, as identified by its TCG vendor ID
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum TpmManufacturer {
+    Amd,
+    Atmel,
+    Broadcom,
+    Ibm,
+    Infineon,
+    Intel,
+    Lenovo,
+    Microsoft,
+    Nationz,
+    Nuvoton,
+    Qualcomm,
+    StMicro,
+    Google,
+    Other(u32),
+}
+
+impl TpmManufacturer {
+    pub fn from_vendor_id(vendor_id: u32) -> Self {
+        match vendor_id {
+            0x414D_4400 => TpmManufacturer::Amd,
+            0x4154_4D4C => TpmManufacturer::Atmel,
+            0x4252_434D => TpmManufacturer::Broadcom,
+            0x4942_4D00 => TpmManufacturer::Ibm,
+            0x4946_5800 => TpmManufacturer::Infineon,
+            0x494E_5443 => TpmManufacturer::Intel,
+            0x4C45_4E00 => TpmManufacturer::Lenovo,
+            0x4D53_4654 => TpmManufacturer::Microsoft,
+            0x4E53_4D20 => TpmManufacturer::Nationz,
+            0x4E54_4300 => TpmManufacturer::Nuvoton,
+            0x5143_4F4D => TpmManufacturer::Qualcomm,
+            0x5354_4D20 => TpmManufacturer::StMicro,
+            0x474F_4F47 => TpmManufacturer::Google,
+            other => TpmManufacturer::Other(other),
+        }
+    }
+
+    pub fn vendor_id(&self) -> u32 {
+        match self {
+            TpmManufacturer::Amd => 0x414D_4400,
+            TpmManufacturer::Atmel => 0x4154_4D4C,
+            TpmManufacturer::Broadcom => 0x4252_434D,
+            TpmManufacturer::Ibm => 0x4942_4D00,
+            TpmManufacturer::Infineon => 0x4946_5800,
+            TpmManufacturer::Intel => 0x494E_5443,
+            TpmManufacturer::Lenovo => 0x4C45_4E00,
+            TpmManufacturer::Microsoft => 0x4D53_4654,
+            TpmManufacturer::Nationz => 0x4E53_4D20,
+            TpmManufacturer::Nuvoton => 0x4E54_4300,
+            TpmManufacturer::Qualcomm => 0x5143_4F4D,
+            TpmManufacturer::StMicro => 0x5354_4D20,
+            TpmManufacturer::Google => 0x474F_4F47,
+            TpmManufacturer::Other(vendor_id) => *vendor_id,
+        }
+    }
+}
+
+/// Parses the "id:XXXXXXXX" format used for the TPM manufacturer attribute
+fn parse_vendor_id(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix("id:")
+        .or_else(|| value.strip_prefix("ID:"))
+        .unwrap_or(value);
+    u32::from_str_radix(value, 16).ok()
+}
+
+/// Splits a DER TLV off the start of buf, returning its tag and contents
+fn der_tlv<'a>(buf: &mut &'a [u8]) -> Result<(u8, &'a [u8]), Error> {
+    let header = take(buf, 2).map_err(|_| Error::InvalidEkCertificate)?;
+    let len = match header[1] {
+        len if len & 0x80 == 0 => len as usize,
+        len if (1..=4).contains(&(len & 0x7F)) => take(buf, (len & 0x7F) as usize)
+            .map_err(|_| Error::InvalidEkCertificate)?
+            .iter()
+            .fold(0, |acc, b| acc << 8 | *b as usize),
+        _ => return Err(Error::InvalidEkCertificate),
+    };
+    let contents = take(buf, len).map_err(|_| Error::InvalidEkCertificate)?;
+    Ok((header[0], contents))
+}
+
+/// Splits a DER TLV with the given tag off the start of buf, returning its contents
+fn der_expect<'a>(buf: &mut &'a [u8], tag: u8) -> Result<&'a [u8], Error> {
+    match der_tlv(buf)? {
+        (found, contents) if found == tag => Ok(contents),
+        _ => Err(Error::InvalidEkCertificate),
+    }
+}
+
+/// Formats an encoded OID in dotted notation
+fn oid_to_string(oid: &[u8]) -> Result<String, Error> {
+    if oid.last().map(|b| b & 0x80 != 0).unwrap_or(true) {
+        return Err(Error::InvalidEkCertificate);
+    }
+    let mut arcs = Vec::new();
+    let mut arc: u64 = 0;
+    for b in oid {
+        if arc > u64::MAX >> 7 {
+            return Err(Error::InvalidEkCertificate);
+        }
+        arc = arc << 7 | (b & 0x7F) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first.to_string());
+                arc -= first * 40;
+            }
+            arcs.push(arc.to_string());
+            arc = 0;
+        }
+    }
+    Ok(arcs.join("."))
+}
+
+/// Decodes a DisplayText or IA5String
+fn der_string(tag: u8, contents: &[u8]) -> Result<String, Error> {
+    match tag {
+        DER_UTF8_STRING | DER_IA5_STRING | DER_VISIBLE_STRING => {
+            String::from_utf8(contents.to_vec()).map_err(|_| Error::InvalidEkCertificate)
+        }
+        DER_BMP_STRING if contents.len().is_multiple_of(2) => {
+            let units: Vec<u16> = contents
+                .chunks(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16(&units).map_err(|_| Error::InvalidEkCertificate)
+        }
+        _ => Err(Error::InvalidEkCertificate),
+    }
+}
+
+/// The encoded extnID and the extnValue of a certificate extension
+type Extension<'a> = (&'a [u8], &'a [u8]);
+
+/// The extensions of a DER certificate
+fn certificate_extensions(mut der: &[u8]) -> Result<Vec<Extension<'_>>, Error> {
+    let mut certificate = der_expect(&mut der, DER_SEQUENCE)?;
+    let mut tbs_certificate = der_expect(&mut certificate, DER_SEQUENCE)?;
+
+    let mut extensions = Vec::new();
+    while !tbs_certificate.is_empty() {
+        let (tag, mut contents) = der_tlv(&mut tbs_certificate)?;
+        if tag != DER_EXTENSIONS {
+            continue;
+        }
+        let mut list = der_expect(&mut contents, DER_SEQUENCE)?;
+        while !list.is_empty() {
+            let mut extension = der_expect(&mut list, DER_SEQUENCE)?;
+            let oid = der_expect(&mut extension, DER_OID)?;
+            let (mut tag, mut value) = der_tlv(&mut extension)?;
+            if tag == DER_BOOLEAN {
+                let next = der_tlv(&mut extension)?;
+                tag = next.0;
+                value = next.1;
+            }
+            if tag != DER_OCTET_STRING {
+                return Err(Error::InvalidEkCertificate);
+            }
+            extensions.push((oid, value));
+        }
+    }
+    Ok(extensions)
+}
+
+/// Whether an extendedKeyUsage extension value contains the key purpose
+fn has_key_purpose(mut value: &[u8], purpose: &[u8]) -> Result<bool, Error> {
+    let mut purposes = der_expect(&mut value, DER_SEQUENCE)?;
+    while !purposes.is_empty() {
+        if der_expect(&mut purposes, DER_OID)? == purpose {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// A policy of the certificatePolicies extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificatePolicy {
+    /// The policy OID in dotted notation
+    pub identifier: String,
+    /// The URI of the certification practice statement
+    pub cps_uri: Option<String>,
+    /// The explicit text of the user notice
+    pub user_notice: Option<String>,
+}
+
+impl CertificatePolicy {
+    fn parse_policies(mut value: &[u8]) -> Result<Vec<CertificatePolicy>, Error> {
+        let mut list = der_expect(&mut value, DER_SEQUENCE)?;
+        let mut policies = Vec::new();
+        while !list.is_empty() {
+            let mut info = der_expect(&mut list, DER_SEQUENCE)?;
+            let mut policy = CertificatePolicy {
+                identifier: oid_to_string(der_expect(&mut info, DER_OID)?)?,
+                cps_uri: None,
+                user_notice: None,
+            };
+
+            let mut qualifiers = if info.is_empty() {
+                info
+            } else {
+                der_expect(&mut info, DER_SEQUENCE)?
+            };
+            while !qualifiers.is_empty() {
+                let mut qualifier = der_expect(&mut qualifiers, DER_SEQUENCE)?;
+                let id = der_expect(&mut qualifier, DER_OID)?;
+                if id == OID_QT_CPS {
+                    let uri = der_expect(&mut qualifier, DER_IA5_STRING)?;
+                    policy.cps_uri = Some(der_string(DER_IA5_STRING, uri)?);
+                } else if id == OID_QT_UNOTICE {
+                    let mut notice = der_expect(&mut qualifier, DER_SEQUENCE)?;
+                    while !notice.is_empty() {
+                        // Skip the noticeRef
+                        let (tag, contents) = der_tlv(&mut notice)?;
+                        if tag != DER_SEQUENCE {
+                            policy.user_notice = Some(der_string(tag, contents)?);
+                        }
+                    }
+                }
+            }
+            policies.push(policy);
+        }
+        Ok(policies)
+    }
+}
+
+/// The TCG-specific contents of an EK certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EkCertificateInfo {
+    pub manufacturer: TpmManufacturer,
+    pub model: Option<String>,
+    pub version: Option<String>,
+    /// Whether the extended key usage contains tcg-kp-EKCertificate
+    pub ek_certificate_usage: bool,
+    /// The policies of the certificatePolicies extension
+    pub policies: Vec<CertificatePolicy>,
+}
+
+impl EkCertificateInfo {
+    /// Parses the TPM manufacturer, model and version from the directoryName in the
+    /// Subject Alternative Name of an EK certificate.
+    pub fn from_certificate(cert: &X509Ref) -> Result<Self, Error> {
+        let mut manufacturer = None;
+        let mut model = None;
+        let mut version = None;
+
+        for name in cert
+            .subject_alt_names()
+            .ok_or(Error::InvalidEkCertificate)?
+        {
+            let dir_name = match name.directory_name() {
+                Some(dir_name) => dir_name,
+                None => continue,
+            };
+
+            for entry in dir_name.entries() {
+                let value = String::from_utf8_lossy(entry.data().as_slice()).into_owned();
+                match &entry.object().to_string()[..] {
+                    OID_TPM_MANUFACTURER => manufacturer = parse_vendor_id(&value),
+                    OID_TPM_MODEL => model = Some(value),
+                    OID_TPM_VERSION => version = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        let der = cert.to_der()?;
+        let mut ek_certificate_usage = false;
+        let mut policies = Vec::new();
+        for (oid, value) in certificate_extensions(&der)? {
+            if oid == OID_EXTENDED_KEY_USAGE {
+                ek_certificate_usage = has_key_purpose(value, OID_EK_CERTIFICATE_USAGE)?;
+            } else if oid == OID_CERTIFICATE_POLICIES {
+                policies = CertificatePolicy::parse_policies(value)?;
+            }
+        }
+
+        Ok(EkCertificateInfo {
+            manufacturer: TpmManufacturer::from_vendor_id(
+                manufacturer.ok_or(Error::InvalidEkCertificate)?,
+            ),
+            model,
+            version,
+            ek_certificate_usage,
+            policies,
+        })
+    }
+
+    /// The policy with the user notice the TCG EK Credential Profile requires
+    pub fn tcg_ek_policy(&self) -> Option<&CertificatePolicy> {
+        self.policies
+            .iter()
+            .find(|policy| policy.user_notice.as_deref() == Some(TCG_EK_USER_NOTICE))
+    }
+}
+
+/// Verifies EK certificates against the CA roots of their manufacturer
+pub struct EkCertificateVerifier {
+    stores: BTreeMap<TpmManufacturer, X509Store>,
+    intermediates: Vec<X509>,
+    require_ek_certificate_usage: bool,
+}
+
+impl EkCertificateVerifier {
+    /// Verifies the EK certificate and checks that it certifies the given EK
+    ///
+    /// The chain can contain intermediate certificates that were sent along with
+    /// the EK certificate. Only the roots configured for the manufacturer named in
+    /// the certificate are trusted.
+    pub fn verify(
+        &self,
+        cert: &X509Ref,
+        chain: &[X509],
+        ek_public: &TpmtPublic,
+    ) -> Result<EkCertificateInfo, Error> {
+        let info = EkCertificateInfo::from_certificate(cert)?;
+        if self.require_ek_certificate_usage && !info.ek_certificate_usage {
+            return Err(Error::InvalidEkCertificate);
+        }
+
+        let store = self
+            .stores
+            .get(&info.manufacturer)
+            .ok_or(Error::UnknownManufacturer)?;
+
+        let mut untrusted = Stack::new()?;
+        for intermediate in self.intermediates.iter().chain(chain.iter()) {
+            untrusted.push(intermediate.clone())?;
+        }
+
+        let mut context = X509StoreContext::new()?;
+        if !context.init(store, cert, &untrusted, |c| c.verify_cert())? {
+            return Err(Error::UntrustedEkCertificate);
+        }
+
+        if !PKey::try_from(ek_public)?.public_eq(&*cert.public_key()?) {
+            return Err(Error::EkMismatch);
+        }
+
+        Ok(info)
+    }
+}
+
+pub struct EkCertificateVerifierBuilder {
+    roots: BTreeMap<TpmManufacturer, Vec<X509>>,
+    intermediates: Vec<X509>,
+    require_ek_certificate_usage: bool,
+    check_time: bool,
+}
+
+impl Default for EkCertificateVerifierBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EkCertificateVerifierBuilder {
+    pub fn new() -> Self {
+        EkCertificateVerifierBuilder {
+            roots: BTreeMap::new(),
+            intermediates: Vec::new(),
+            require_ek_certificate_usage: false,
+            check_time: true,
+        }
+    }
+
+    /// Trusts a CA root for the EK certificates of a manufacturer
+    pub fn add_root(&mut self, manufacturer: TpmManufacturer, root: X509) -> &mut Self {
+        self.roots.entry(manufacturer).or_default().push(root);
+        self
+    }
+
+    /// Trusts all CA roots in a PEM bundle for the EK certificates of a manufacturer
+    pub fn add_roots_pem(
+        &mut self,
+        manufacturer: TpmManufacturer,
+        pem: &[u8],
+    ) -> Result<&mut Self, Error> {
+        for root in X509::stack_from_pem(pem)? {
+            self.add_root(manufacturer, root);
+        }
+        Ok(self)
+    }
+
+    /// Adds an intermediate CA, which is only trusted if it chains up to a root
+    pub fn add_intermediate(&mut self, intermediate: X509) -> &mut Self {
+        self.intermediates.push(intermediate);
+        self
+    }
+
+    /// Requires the tcg-kp-EKCertificate extended key usage, which defaults to off
+    /// as not all manufacturers include it.
+    pub fn set_require_ek_certificate_usage(&mut self, val: bool) -> &mut Self {
+        self.require_ek_certificate_usage = val;
+        self
+    }
+
+    /// Sets whether certificate validity periods are checked, which defaults to on
+    pub fn set_check_time(&mut self, val: bool) -> &mut Self {
+        self.check_time = val;
+        self
+    }
+
+    pub fn build(&self) -> Result<EkCertificateVerifier, Error> {
+        let mut stores = BTreeMap::new();
+        for (manufacturer, roots) in &self.roots {
+            let mut store = X509StoreBuilder::new()?;
+            for root in roots {
+                store.add_cert(root.clone())?;
+            }
+            if !self.check_time {
+                store.set_flags(X509VerifyFlags::NO_CHECK_TIME)?;
+            }
+            stores.insert(*manufacturer, store.build());
+        }
+
+        Ok(EkCertificateVerifier {
+            stores,
+            intermediates: self.intermediates.clone(),
+            require_ek_certificate_usage: self.require_ek_certificate_usage,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use openssl::{
+        asn1::{Asn1Object, Asn1OctetString, Asn1Time},
+        bn::BigNum,
+        hash::MessageDigest,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        x509::{
+            extension::{BasicConstraints, ExtendedKeyUsage, SubjectAlternativeName},
+            X509Builder, X509Extension, X509NameBuilder,
+        },
+    };
+
+    use super::*;
+    use crate::{
+        objects::{ObjectAttributes, PublicParameters, SymmetricDefinition},
+        DigestAlgorithm,
+    };
+
+    fn build_cert(
+        subject_key: &PKey<Private>,
+        issuer_key: &PKey<Private>,
+        issuer: Option<&X509>,
+        vendor_id: Option<&str>,
+        extensions: Vec<X509Extension>,
+    ) -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", if issuer.is_none() { "CA" } else { "EK" })
+            .unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder
+            .set_issuer_name(issuer.map(|i| i.subject_name()).unwrap_or(&name))
+            .unwrap();
+        builder.set_pubkey(subject_key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+
+        if issuer.is_none() {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+        }
+
+        if let Some(vendor_id) = vendor_id {
+            let mut dir_name = X509NameBuilder::new().unwrap();
+            dir_name
+                .append_entry_by_text(OID_TPM_MANUFACTURER, vendor_id)
+                .unwrap();
+            dir_name
+                .append_entry_by_text(OID_TPM_MODEL, "SLB9670")
+                .unwrap();
+            dir_name
+                .append_entry_by_text(OID_TPM_VERSION, "id:00070055")
+                .unwrap();
+            let san = SubjectAlternativeName::new()
+                .critical()
+                .dir_name2(dir_name.build())
+                .build(&builder.x509v3_context(issuer.map(|i| &**i), None))
+                .unwrap();
+            builder.append_extension(san).unwrap();
+        }
+        for extension in extensions {
+            builder.append_extension(extension).unwrap();
+        }
+
+        builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    fn ek_public(key: &PKey<Private>) -> TpmtPublic {
+        let mut public = TpmtPublic::from_public_key(
+            key,
+            DigestAlgorithm::Sha256,
+            ObjectAttributes::from_bits(0x0003_00B2),
+        )
+        .unwrap();
+        if let PublicParameters::Rsa { symmetric, .. } = &mut public.parameters {
+            *symmetric = SymmetricDefinition::Aes { key_bits: 128 };
+        }
+        public
+    }
+
+    #[test]
+    fn test_ek_certificate_chain() {
+        let ca_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ca = build_cert(&ca_key, &ca_key, None, None, Vec::new());
+        let ek_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ek_cert = build_cert(&ek_key, &ca_key, Some(&ca), Some("id:49465800"), Vec::new());
+
+        let info = EkCertificateInfo::from_certificate(&ek_cert).unwrap();
+        assert_eq!(info.manufacturer, TpmManufacturer::Infineon);
+        assert_eq!(info.model.as_deref(), Some("SLB9670"));
+        assert_eq!(info.version.as_deref(), Some("id:00070055"));
+        assert!(!info.ek_certificate_usage);
+        assert!(info.policies.is_empty());
+        assert!(info.tcg_ek_policy().is_none());
+
+        let verifier = EkCertificateVerifierBuilder::new()
+            .add_root(TpmManufacturer::Infineon, ca.clone())
+            .build()
+            .unwrap();
+        assert_eq!(
+            verifier.verify(&ek_cert, &[], &ek_public(&ek_key)).unwrap(),
+            info
+        );
+
+        let other_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        match verifier.verify(&ek_cert, &[], &ek_public(&other_key)) {
+            Err(Error::EkMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // The same root is not trusted for other manufacturers
+        let verifier = EkCertificateVerifierBuilder::new()
+            .add_root(TpmManufacturer::StMicro, ca)
+            .build()
+            .unwrap();
+        match verifier.verify(&ek_cert, &[], &ek_public(&ek_key)) {
+            Err(Error::UnknownManufacturer) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_ek_certificate_untrusted() {
+        let ca_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ca = build_cert(&ca_key, &ca_key, None, None, Vec::new());
+        let other_ca_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other_ca = build_cert(&other_ca_key, &other_ca_key, None, None, Vec::new());
+        let ek_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ek_cert = build_cert(
+            &ek_key,
+            &other_ca_key,
+            Some(&other_ca),
+            Some("id:4E544300"),
+            Vec::new(),
+        );
+
+        let verifier = EkCertificateVerifierBuilder::new()
+            .add_root(TpmManufacturer::Nuvoton, ca)
+            .build()
+            .unwrap();
+        match verifier.verify(&ek_cert, &[], &ek_public(&ek_key)) {
+            Err(Error::UntrustedEkCertificate) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    fn der_extension(oid: &str, value: &str) -> X509Extension {
+        X509Extension::new_from_der(
+            &Asn1Object::from_str(oid).unwrap(),
+            false,
+            &Asn1OctetString::new_from_bytes(&hex::decode(value).unwrap()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ek_certificate_extensions() {
+        let ca_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ca = build_cert(&ca_key, &ca_key, None, None, Vec::new());
+        let ek_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        // A policy with a CPS URI and the TCG user notice, and one without qualifiers
+        let policies = der_extension(
+            "2.5.29.32",
+            "3067305e06056781050c013055302306082b06010505070201161768747470733a2f2f6578616d\
+             706c652e636f6d2f637073302e06082b0601050507020230220c20544347205472757374656420\
+             506c6174666f726d20456e646f7273656d656e74300506032a0304",
+        );
+        let usage = ExtendedKeyUsage::new()
+            .server_auth()
+            .other("2.23.133.8.1")
+            .build()
+            .unwrap();
+        let ek_cert = build_cert(
+            &ek_key,
+            &ca_key,
+            Some(&ca),
+            Some("id:53544D20"),
+            vec![usage, policies],
+        );
+
+        let info = EkCertificateInfo::from_certificate(&ek_cert).unwrap();
+        assert!(info.ek_certificate_usage);
+        assert_eq!(
+            info.policies,
+            vec![
+                CertificatePolicy {
+                    identifier: "2.23.133.12.1".to_string(),
+                    cps_uri: Some("https://example.com/cps".to_string()),
+                    user_notice: Some(TCG_EK_USER_NOTICE.to_string()),
+                },
+                CertificatePolicy {
+                    identifier: "1.2.3.4".to_string(),
+                    cps_uri: None,
+                    user_notice: None,
+                },
+            ]
+        );
+        assert_eq!(info.tcg_ek_policy(), Some(&info.policies[0]));
+
+        let verifier = EkCertificateVerifierBuilder::new()
+            .add_root(TpmManufacturer::StMicro, ca.clone())
+            .set_require_ek_certificate_usage(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            verifier.verify(&ek_cert, &[], &ek_public(&ek_key)).unwrap(),
+            info
+        );
+
+        // The tcg-kp-EKCertificate OID only counts in the extended key usage
+        let usage = ExtendedKeyUsage::new().server_auth().build().unwrap();
+        let elsewhere = der_extension("1.3.6.1.4.1.99999.1", "300706056781050801");
+        let ek_cert = build_cert(
+            &ek_key,
+            &ca_key,
+            Some(&ca),
+            Some("id:53544D20"),
+            vec![usage, elsewhere],
+        );
+        assert!(
+            !EkCertificateInfo::from_certificate(&ek_cert)
+                .unwrap()
+                .ek_certificate_usage
+        );
+        match verifier.verify(&ek_cert, &[], &ek_public(&ek_key)) {
+            Err(Error::InvalidEkCertificate) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use alloc::vec;
+
+use crate::{
+    objects::{
+        EccCurve, EccScheme, KdfScheme, ObjectAttributes, PublicParameters, RsaScheme,
+        SymmetricDefinition, TpmtPublic,
+    },
+    DigestAlgorithm,
+};
+
+/// fixedTPM | fixedParent | sensitiveDataOrigin | adminWithPolicy | restricted | decrypt
+const EK_ATTRIBUTES_LOW: ObjectAttributes = ObjectAttributes::from_bits(0x0003_00B2);
+/// The low range attributes with userWithAuth added
+const EK_ATTRIBUTES_HIGH: ObjectAttributes = ObjectAttributes::from_bits(0x0003_00F2);
+
+/// PolicySecret(TPM_RH_ENDORSEMENT)
+const POLICY_A_SHA256: &[u8] = &[
+    0x83, 0x71, 0x97, 0x67, 0x44, 0x84, 0xB3, 0xF8, 0x1A, 0x90, 0xCC, 0x8D, 0x46, 0xA5, 0xD7, 0x24,
+    0xFD, 0x52, 0xD7, 0x6E, 0x06, 0x52, 0x0B, 0x64, 0xF2, 0xA1, 0xDA, 0x1B, 0x33, 0x14, 0x69, 0xAA,
+];
+const POLICY_B_SHA256: &[u8] = &[
+    0xCA, 0x3D, 0x0A, 0x99, 0xA2, 0xB9, 0x39, 0x06, 0xF7, 0xA3, 0x34, 0x24, 0x14, 0xEF, 0xCF, 0xB3,
+    0xA3, 0x85, 0xD4, 0x4C, 0xD1, 0xFD, 0x45, 0x90, 0x89, 0xD1, 0x9B, 0x50, 0x71, 0xC0, 0xB7, 0xA0,
+];
+const POLICY_B_SHA384: &[u8] = &[
+    0xB2, 0x6E, 0x7D, 0x28, 0xD1, 0x1A, 0x50, 0xBC, 0x53, 0xD8, 0x82, 0xBC, 0xF5, 0xFD, 0x3A, 0x1A,
+    0x07, 0x41, 0x48, 0xBB, 0x35, 0xD3, 0xB4, 0xE4, 0xCB, 0x1C, 0x0A, 0xD9, 0xBD, 0xE4, 0x19, 0xCA,
+    0xCB, 0x47, 0xBA, 0x09, 0x69, 0x96, 0x46, 0x15, 0x0F, 0x9F, 0xC0, 0x00, 0xF3, 0xF8, 0x0E, 0x12,
+];
+const POLICY_B_SHA512: &[u8] = &[
+    0xB8, 0x22, 0x1C, 0xA6, 0x9E, 0x85, 0x50, 0xA4, 0x91, 0x4D, 0xE3, 0xFA, 0xA6, 0xA1, 0x8C, 0x07,
+    0x2C, 0xC0, 0x12, 0x08, 0x07, 0x3A, 0x92, 0x8D, 0x5D, 0x66, 0xD5, 0x9E, 0xF7, 0x9E, 0x49, 0xA4,
+    0x29, 0xC4, 0x1A, 0x6B, 0x26, 0x95, 0x71, 0xD5, 0x7E, 0xDB, 0x25, 0xFB, 0xDB, 0x18, 0x38, 0x42,
+    0x56, 0x08, 0xB4, 0x13, 0xCD, 0x61, 0x6A, 0x5F, 0x6D, 0xB5, 0xB6, 0x07, 0x1A, 0xF9, 0x9B, 0xEA,
+];
+
+/// An EK template from the TCG EK Credential Profile
+///
+/// H-5 (ECC SM2 P256) is not included, as SM4 symmetric definitions are not supported.
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
+pub enum EkTemplate {
+    /// RSA 2048, the default low range template
+    L1,
+    /// ECC NIST P256, the default low range template
+    L2,
+    /// RSA 2048
+    H1,
+    /// ECC NIST P256
+    H2,
+    /// ECC NIST P384
+    H3,
+    /// ECC NIST P521
+    H4,
+    /// RSA 3072
+    H6,
+    /// RSA 4096
+    H7,
+}
+
+impl EkTemplate {
+    pub const ALL: &'static [EkTemplate] = &[
+        EkTemplate::L1,
+        EkTemplate::L2,
+        EkTemplate::H1,
+        EkTemplate::H2,
+        EkTemplate::H3,
+        EkTemplate::H4,
+        EkTemplate::H6,
+        EkTemplate::H7,
+    ];
+
+    /// The public area the TPM creates the EK from, including the unique field
+    pub fn public(&self) -> TpmtPublic {
+        let (name_alg, object_attributes, auth_policy) = match self {
+            EkTemplate::L1 | EkTemplate::L2 => {
+                (DigestAlgorithm::Sha256, EK_ATTRIBUTES_LOW, POLICY_A_SHA256)
+            }
+            EkTemplate::H1 | EkTemplate::H2 => {
+                (DigestAlgorithm::Sha256, EK_ATTRIBUTES_HIGH, POLICY_B_SHA256)
+            }
+            EkTemplate::H3 | EkTemplate::H6 | EkTemplate::H7 => {
+                (DigestAlgorithm::Sha384, EK_ATTRIBUTES_HIGH, POLICY_B_SHA384)
+            }
+            EkTemplate::H4 => (DigestAlgorithm::Sha512, EK_ATTRIBUTES_HIGH, POLICY_B_SHA512),
+        };
+        // The low range templates fill unique with zeroes, the high range leave it empty
+        let unique_size = |size: usize| match self {
+            EkTemplate::L1 | EkTemplate::L2 => size,
+            _ => 0,
+        };
+        let symmetric = match self {
+            EkTemplate::L1 | EkTemplate::L2 | EkTemplate::H1 | EkTemplate::H2 => {
+                SymmetricDefinition::Aes { key_bits: 128 }
+            }
+            _ => SymmetricDefinition::Aes { key_bits: 256 },
+        };
+
+        let rsa = |key_bits: u16| PublicParameters::Rsa {
+            symmetric,
+            scheme: RsaScheme::Null,
+            key_bits,
+            exponent: 0,
+            modulus: vec![0; unique_size(key_bits as usize / 8)],
+        };
+        let ecc = |curve: EccCurve| PublicParameters::Ecc {
+            symmetric,
+            scheme: EccScheme::Null,
+            curve,
+            kdf: KdfScheme::Null,
+            x: vec![0; unique_size(curve.key_size())],
+            y: vec![0; unique_size(curve.key_size())],
+        };
+
+        let parameters = match self {
+            EkTemplate::L1 | EkTemplate::H1 => rsa(2048),
+            EkTemplate::H6 => rsa(3072),
+            EkTemplate::H7 => rsa(4096),
+            EkTemplate::L2 | EkTemplate::H2 => ecc(EccCurve::NistP256),
+            EkTemplate::H3 => ecc(EccCurve::NistP384),
+            EkTemplate::H4 => ecc(EccCurve::NistP521),
+        };
+
+        TpmtPublic {
+            name_alg,
+            object_attributes,
+            auth_policy: auth_policy.to_vec(),
+            parameters,
+        }
+    }
+
+    /// Checks whether the EK public area was created from this template, comparing
+    /// everything apart from the unique field.
+    pub fn matches(&self, public: &TpmtPublic) -> bool {
+        without_unique(public) == without_unique(&self.public())
+    }
+
+    /// Finds the template the EK public area was created from
+    pub fn identify(public: &TpmtPublic) -> Option<EkTemplate> {
+        EkTemplate::ALL
+            .iter()
+            .copied()
+            .find(|template| template.matches(public))
+    }
+}
+
+fn without_unique(public: &TpmtPublic) -> TpmtPublic {
+    let mut public = public.clone();
+    match &mut public.parameters {
+        PublicParameters::Rsa { modulus, .. } => modulus.clear(),
+        PublicParameters::Ecc { x, y, .. } => {
+            x.clear();
+            y.clear();
+        }
+        PublicParameters::KeyedHash { unique, .. } | PublicParameters::SymCipher { unique, .. } => {
+            unique.clear()
+        }
+    }
+    public
+}
+
+#[cfg(all(test, feature = "openssl"))]
+mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::PKey,
+        rsa::Rsa,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_template_encoding() {
+        // The L-1 template as passed to TPM2_CreatePrimary by tpm2_createek
+        let mut expected = hex::decode(
+            "0001000b000300b20020837197674484b3f81a90cc8d46a5d724fd52d76e06520b64f2a1da1b331469aa\
+             00060080004300100800000000000100",
+        )
+        .unwrap();
+        expected.extend_from_slice(&[0; 256]);
+        assert_eq!(EkTemplate::L1.public().to_vec().unwrap(), expected);
+
+        let expected = hex::decode(
+            "0023000b000300f20020ca3d0a99a2b93906f7a3342414efcfb3a385d44cd1fd459089d19b5071c0b7a0\
+             00060080004300100003001000000000",
+        )
+        .unwrap();
+        assert_eq!(EkTemplate::H2.public().to_vec().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_identify() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut public =
+            TpmtPublic::from_public_key(&key, DigestAlgorithm::Sha256, EK_ATTRIBUTES_LOW).unwrap();
+        public.object_attributes = EK_ATTRIBUTES_LOW;
+        public.auth_policy = POLICY_A_SHA256.to_vec();
+        if let PublicParameters::Rsa { symmetric, .. } = &mut public.parameters {
+            *symmetric = SymmetricDefinition::Aes { key_bits: 128 };
+        }
+        assert_eq!(EkTemplate::identify(&public), Some(EkTemplate::L1));
+
+        public.object_attributes = EK_ATTRIBUTES_HIGH;
+        assert_eq!(EkTemplate::identify(&public), None);
+        public.auth_policy = POLICY_B_SHA256.to_vec();
+        assert_eq!(EkTemplate::identify(&public), Some(EkTemplate::H1));
+
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut public =
+            TpmtPublic::from_public_key(&key, DigestAlgorithm::Sha384, EK_ATTRIBUTES_HIGH).unwrap();
+        public.object_attributes = EK_ATTRIBUTES_HIGH;
+        public.auth_policy = POLICY_B_SHA384.to_vec();
+        assert_eq!(EkTemplate::identify(&public), None);
+        if let PublicParameters::Ecc { symmetric, .. } = &mut public.parameters {
+            *symmetric = SymmetricDefinition::Aes { key_bits: 256 };
+        }
+        assert_eq!(EkTemplate::identify(&public), Some(EkTemplate::H3));
+    }
+}
@@ -0,0 +1,357 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    io::{take, Read, ReadBytesExt, Write, WriteBytesExt},
+    DigestAlgorithm, Error, ReplayEvent,
+};
+
+/// The size of the SHA-1 template digest of each entry
+pub(crate) const TEMPLATE_DIGEST_SIZE: usize = 20;
+
+/// The kernel rejects template names of this length or longer (MAX_TEMPLATE_NAME_LEN)
+const MAX_TEMPLATE_NAME_LEN: usize = 15;
+
+/// A sanity limit on the template data, so that a corrupt length can not make
+/// from_reader allocate arbitrary amounts of memory
+const MAX_TEMPLATE_DATA_LEN: usize = 1 << 24;
+
+/// The legacy template, whose data is not prefixed with its length
+const LEGACY_TEMPLATE_NAME: &str = "ima";
+
+fn take_u32(buf: &mut &[u8]) -> Result<u32, Error> {
+    Ok(LittleEndian::read_u32(take(buf, 4)?))
+}
+
+/// An entry of the binary IMA measurement list, borrowing from the list
+///
+/// The list is expected in the canonical little-endian format, which is also the
+/// native format on x86 and ARM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImaEventRef<'a> {
+    pub pcr: u32,
+    pub template_digest: &'a [u8],
+    pub template_name: &'a str,
+    pub template_data: &'a [u8],
+}
+
+impl<'a> ImaEventRef<'a> {
+    /// Parses an entry from the start of buf without copying, and advances buf past it
+    pub fn from_bytes(buf: &mut &'a [u8]) -> Result<ImaEventRef<'a>, Error> {
+        let mut rest = *buf;
+
+        let pcr = take_u32(&mut rest)?;
+        let template_digest = take(&mut rest, TEMPLATE_DIGEST_SIZE)?;
+        let name_len = take_u32(&mut rest)? as usize;
+        if name_len == 0 || name_len >= MAX_TEMPLATE_NAME_LEN {
+            return Err(Error::InvalidImaEvent);
+        }
+        let template_name =
+            core::str::from_utf8(take(&mut rest, name_len)?).map_err(|_| Error::InvalidImaEvent)?;
+
+        let template_data = if template_name == LEGACY_TEMPLATE_NAME {
+            // The file digest without length, followed by the file name with length
+            let data = rest;
+            take(&mut rest, TEMPLATE_DIGEST_SIZE)?;
+            let file_name_len = take_u32(&mut rest)? as usize;
+            take(&mut rest, file_name_len)?;
+            &data[..data.len() - rest.len()]
+        } else {
+            let data_len = take_u32(&mut rest)? as usize;
+            take(&mut rest, data_len)?
+        };
+
+        *buf = rest;
+        Ok(ImaEventRef {
+            pcr,
+            template_digest,
+            template_name,
+            template_data,
+        })
+    }
+
+    /// Whether the entry records a violation, which has an all-zero digest but
+    /// extends the PCR with all ones
+    pub fn is_violation(&self) -> bool {
+        self.template_digest.iter().all(|b| *b == 0)
+    }
+
+    /// The event to replay into the PCR banks with PcrExtender::replay
+    ///
+    /// The log only records the SHA-1 digest, the other banks are extended with the
+    /// hash of the template data. The kernel hashes legacy ima template entries
+    /// differently, so those only replay correctly into the SHA-1 bank.
+    pub fn replay_event(&self) -> ReplayEvent<'a> {
+        if self.is_violation() {
+            ReplayEvent::Invalidation { pcr: self.pcr }
+        } else {
+            ReplayEvent::KnownDigest {
+                pcr: self.pcr,
+                algo: DigestAlgorithm::Sha1,
+                digest: self.template_digest,
+                data: self.template_data,
+            }
+        }
+    }
+
+    /// The fields of the template data, such as the digest and file name of ima-ng
+    ///
+    /// Returns Error::InvalidImaEvent for the legacy ima template, whose fields are
+    /// not all prefixed with their length.
+    pub fn template_fields(&self) -> Result<Vec<&'a [u8]>, Error> {
+        if self.template_name == LEGACY_TEMPLATE_NAME {
+            return Err(Error::InvalidImaEvent);
+        }
+        let mut data = self.template_data;
+        let mut fields = Vec::new();
+        while !data.is_empty() {
+            let len = take_u32(&mut data)? as usize;
+            fields.push(take(&mut data, len)?);
+        }
+        Ok(fields)
+    }
+}
+
+/// An iterator over the entries of a binary IMA measurement list
+///
+/// Iteration stops after the first entry that fails to parse.
+#[derive(Debug, Clone)]
+pub struct ImaEventIter<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for ImaEventIter<'a> {
+    type Item = Result<ImaEventRef<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let res = ImaEventRef::from_bytes(&mut self.buf);
+        if res.is_err() {
+            self.buf = &[];
+        }
+        Some(res)
+    }
+}
+
+/// Parses a binary IMA measurement list, as read from
+/// /sys/kernel/security/ima/binary_runtime_measurements, without copying
+pub fn parse_ima_log(log: &[u8]) -> ImaEventIter<'_> {
+    ImaEventIter { buf: log }
+}
+
+/// An entry of the binary IMA measurement list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImaEvent {
+    pub pcr: u32,
+    pub template_digest: Vec<u8>,
+    pub template_name: String,
+    pub template_data: Vec<u8>,
+}
+
+impl ImaEvent {
+    /// Parses an entry from the start of buf, and advances buf past it
+    pub fn from_bytes(buf: &mut &[u8]) -> Result<ImaEvent, Error> {
+        ImaEventRef::from_bytes(buf).map(ImaEvent::from)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<ImaEvent, Error> {
+        let pcr = reader.read_u32::<LittleEndian>()?;
+        let mut template_digest = vec![0; TEMPLATE_DIGEST_SIZE];
+        reader.read_exact(&mut template_digest)?;
+
+        let name_len = reader.read_u32::<LittleEndian>()? as usize;
+        if name_len == 0 || name_len >= MAX_TEMPLATE_NAME_LEN {
+            return Err(Error::InvalidImaEvent);
+        }
+        let mut template_name = vec![0; name_len];
+        reader.read_exact(&mut template_name)?;
+        let template_name = String::from_utf8(template_name).map_err(|_| Error::InvalidImaEvent)?;
+
+        let template_data = if template_name == LEGACY_TEMPLATE_NAME {
+            let mut data = vec![0; TEMPLATE_DIGEST_SIZE + 4];
+            reader.read_exact(&mut data)?;
+            let file_name_len = LittleEndian::read_u32(&data[TEMPLATE_DIGEST_SIZE..]) as usize;
+            if file_name_len > MAX_TEMPLATE_DATA_LEN {
+                return Err(Error::InvalidImaEvent);
+            }
+            data.resize(data.len() + file_name_len, 0);
+            reader.read_exact(&mut data[TEMPLATE_DIGEST_SIZE + 4..])?;
+            data
+        } else {
+            let data_len = reader.read_u32::<LittleEndian>()? as usize;
+            if data_len > MAX_TEMPLATE_DATA_LEN {
+                return Err(Error::InvalidImaEvent);
+            }
+            let mut data = vec![0; data_len];
+            reader.read_exact(&mut data)?;
+            data
+        };
+
+        Ok(ImaEvent {
+            pcr,
+            template_digest,
+            template_name,
+            template_data,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        if self.template_digest.len() != TEMPLATE_DIGEST_SIZE {
+            return Err(Error::InvalidSize);
+        }
+        writer.write_u32::<LittleEndian>(self.pcr)?;
+        writer.write_all(&self.template_digest)?;
+        writer.write_u32::<LittleEndian>(self.template_name.len() as u32)?;
+        writer.write_all(self.template_name.as_bytes())?;
+        if self.template_name != LEGACY_TEMPLATE_NAME {
+            writer.write_u32::<LittleEndian>(self.template_data.len() as u32)?;
+        }
+        writer.write_all(&self.template_data)?;
+
+        Ok(())
+    }
+}
+
+impl From<ImaEventRef<'_>> for ImaEvent {
+    fn from(event: ImaEventRef<'_>) -> Self {
+        ImaEvent {
+            pcr: event.pcr,
+            template_digest: event.template_digest.to_vec(),
+            template_name: event.template_name.into(),
+            template_data: event.template_data.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provider::provider, PcrExtenderBuilder};
+
+    // The boot_aggregate entry of an ima-ng list
+    const BOOT_AGGREGATE: &str = "0a0000004c68b17f29b5b18e86b4cdfc8cce464b3053c55806000000696d612d6e673f000000280000007368613235363a0011111111111111111111111111111111111111111111111111111111111111110f000000626f6f745f61676772656761746500";
+    // An entry of the legacy ima template
+    const LEGACY: &str = "0a000000f1a54c9ffc439f2dfef79c3aa079cccdeeb62fec03000000696d612222222222222222222222222222222222222222050000002f696e6974";
+
+    #[test]
+    fn test_parse_ima_ng() {
+        let encoded = hex::decode(BOOT_AGGREGATE).unwrap();
+        let mut buf = &encoded[..];
+        let event = ImaEventRef::from_bytes(&mut buf).unwrap();
+        assert!(buf.is_empty());
+
+        assert_eq!(event.pcr, 10);
+        assert_eq!(event.template_name, "ima-ng");
+        assert!(!event.is_violation());
+        assert_eq!(
+            provider()
+                .hash(DigestAlgorithm::Sha1, &[event.template_data])
+                .unwrap(),
+            event.template_digest
+        );
+        let fields = event.template_fields().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(&fields[0][..8], b"sha256:\0");
+        assert_eq!(fields[1], b"boot_aggregate\0");
+
+        let owned = ImaEvent::from_reader(&encoded[..]).unwrap();
+        assert_eq!(owned, ImaEvent::from(event));
+        let mut reencoded = Vec::new();
+        owned.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+    }
+
+    #[test]
+    fn test_parse_legacy_ima() {
+        let encoded = hex::decode(LEGACY).unwrap();
+        let event = ImaEventRef::from_bytes(&mut &encoded[..]).unwrap();
+        assert_eq!(event.template_name, "ima");
+        assert_eq!(event.template_data.len(), 20 + 4 + 5);
+        match event.template_fields() {
+            Err(Error::InvalidImaEvent) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let owned = ImaEvent::from_reader(&encoded[..]).unwrap();
+        assert_eq!(owned, ImaEvent::from(event));
+        let mut reencoded = Vec::new();
+        owned.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+    }
+
+    #[test]
+    fn test_parse_ima_log() {
+        let mut log = hex::decode(BOOT_AGGREGATE).unwrap();
+        log.extend_from_slice(&hex::decode(LEGACY).unwrap());
+        let events = parse_ima_log(&log).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].template_name, "ima");
+
+        let truncated = &log[..log.len() - 1];
+        let mut events = parse_ima_log(truncated);
+        assert!(events.next().unwrap().is_ok());
+        match events.next() {
+            Some(Err(Error::UnexpectedEnd)) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_replay_ima_log() {
+        let mut log = hex::decode(BOOT_AGGREGATE).unwrap();
+        let mut violation = ImaEvent::from_bytes(&mut &log[..]).unwrap();
+        violation.template_digest = vec![0; TEMPLATE_DIGEST_SIZE];
+        violation.to_writer(&mut log).unwrap();
+
+        let events = parse_ima_log(&log)
+            .map(|event| event.map(|event| event.replay_event()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        match events[1] {
+            ReplayEvent::Invalidation { pcr: 10 } => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        extender.replay(&events).unwrap();
+
+        let mut expected = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        expected
+            .extend_event(
+                10,
+                &[
+                    (
+                        DigestAlgorithm::Sha1,
+                        &hex::decode("4c68b17f29b5b18e86b4cdfc8cce464b3053c558").unwrap(),
+                    ),
+                    (
+                        DigestAlgorithm::Sha256,
+                        &hex::decode(
+                            "f68a3cf2158858375e26c6688fdbb941448d07926c0f8ae694c13286bbc1a515",
+                        )
+                        .unwrap(),
+                    ),
+                ],
+            )
+            .unwrap();
+        expected
+            .extend_event(
+                10,
+                &[
+                    (DigestAlgorithm::Sha1, &[0xff; 20]),
+                    (DigestAlgorithm::Sha256, &[0xff; 32]),
+                ],
+            )
+            .unwrap();
+        assert_eq!(extender.values(), expected.values());
+    }
+}
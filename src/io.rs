@@ -0,0 +1,184 @@
+// The traits structures are marshalled with, implemented for byte slices and vectors
+// so that they work without std. With the std feature, StdReader and StdWriter adapt
+// any std::io reader or writer to them.
+
+use alloc::{vec, vec::Vec};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::{Error, Tpm2bRef};
+
+/// Splits len bytes off the start of buf, for parsing without copying
+pub(crate) fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if len > buf.len() {
+        return Err(Error::UnexpectedEnd);
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// A source of bytes, implemented for byte slices
+pub trait Read {
+    /// Reads exactly enough bytes to fill buf, or returns Error::UnexpectedEnd
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Reads a TPM2B and returns its contents
+    fn read_tpm2b(&mut self) -> Result<Vec<u8>, Error> {
+        let mut size = [0; 2];
+        self.read_exact(&mut size)?;
+        let mut contents = vec![0; BigEndian::read_u16(&size) as usize];
+        self.read_exact(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+impl Read for &[u8] {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        buf.copy_from_slice(take(self, buf.len())?);
+        Ok(())
+    }
+
+    // Copies the contents once, straight out of the slice
+    fn read_tpm2b(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(Tpm2bRef::from_bytes(self)?.contents().to_vec())
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        (**self).read_exact(buf)
+    }
+
+    fn read_tpm2b(&mut self) -> Result<Vec<u8>, Error> {
+        (**self).read_tpm2b()
+    }
+}
+
+/// A sink for bytes, implemented for vectors
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error>;
+}
+
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        (**self).write_all(buf)
+    }
+}
+
+/// Reads structures from a std::io reader, reporting a truncated input as
+/// Error::UnexpectedEnd like byte slices do
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StdReader<R>(pub R);
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Read for StdReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.0.read_exact(buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::UnexpectedEnd,
+            _ => Error::IoError(e),
+        })
+    }
+}
+
+/// Writes structures to a std::io writer
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StdWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for StdWriter<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        Ok(self.0.write_all(buf)?)
+    }
+}
+
+/// The subset of byteorder::ReadBytesExt the crate uses
+pub trait ReadBytesExt: Read {
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u16<B: ByteOrder>(&mut self) -> Result<u16, Error> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u16(&buf))
+    }
+
+    fn read_u32<B: ByteOrder>(&mut self) -> Result<u32, Error> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u32(&buf))
+    }
+
+    fn read_u64<B: ByteOrder>(&mut self) -> Result<u64, Error> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u64(&buf))
+    }
+}
+
+impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+/// The subset of byteorder::WriteBytesExt the crate uses
+pub trait WriteBytesExt: Write {
+    fn write_u8(&mut self, n: u8) -> Result<(), Error> {
+        self.write_all(&[n])
+    }
+
+    fn write_u16<B: ByteOrder>(&mut self, n: u16) -> Result<(), Error> {
+        let mut buf = [0; 2];
+        B::write_u16(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    fn write_u32<B: ByteOrder>(&mut self, n: u32) -> Result<(), Error> {
+        let mut buf = [0; 4];
+        B::write_u32(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    fn write_u64<B: ByteOrder>(&mut self, n: u64) -> Result<(), Error> {
+        let mut buf = [0; 8];
+        B::write_u64(&mut buf, n);
+        self.write_all(&buf)
+    }
+}
+
+impl<W: Write + ?Sized> WriteBytesExt for W {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Tpm2b;
+
+    #[test]
+    fn test_std_adapters() {
+        let mut buf = Vec::new();
+        Tpm2b::new(&b"hello"[..])
+            .unwrap()
+            .to_writer(StdWriter(&mut buf))
+            .unwrap();
+
+        let mut reader = StdReader(std::io::Cursor::new(&buf));
+        assert_eq!(
+            Tpm2b::from_reader(&mut reader).unwrap().contents(),
+            b"hello"
+        );
+
+        match Tpm2b::from_reader(StdReader(&buf[..4])) {
+            Err(Error::UnexpectedEnd) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
@@ -1,54 +1,126 @@
-use std::str::FromStr;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use openssl::hash::MessageDigest;
+extern crate alloc;
+
+use core::{convert::TryFrom, str::FromStr};
+
+#[cfg(feature = "openssl")]
+use openssl::{hash::MessageDigest, nid::Nid};
 use thiserror::Error;
 
+#[cfg(not(any(feature = "openssl", feature = "rustcrypto")))]
+compile_error!("Either the openssl or the rustcrypto feature must be enabled");
+
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Invalid PCR number requested")]
     InvalidPcr,
     #[error("Unused algorithm requested")]
     UnusedAlgo,
+    #[cfg(feature = "openssl")]
     #[error("Cryptographic error occured")]
     Crypto(#[from] openssl::error::ErrorStack),
     #[error("Invalid parameter size")]
     InvalidSize,
     #[error("Unsupported algorithm requested")]
     UnsupportedAlgo,
+    #[error("Random number generation failed")]
+    RandomFailed,
+    #[error("Decryption failed")]
+    DecryptionFailed,
+    #[cfg(feature = "std")]
     #[error("I/O Error")]
     IoError(#[from] std::io::Error),
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Public and sensitive areas do not describe the same object")]
+    InvalidObject,
+    #[error("Key can not be used as a storage parent")]
+    InvalidParent,
+    #[error("Integrity check failed")]
+    IntegrityCheckFailed,
+    #[error("Invalid EK certificate")]
+    InvalidEkCertificate,
+    #[error("No trusted roots for the TPM manufacturer")]
+    UnknownManufacturer,
+    #[error("EK certificate is not trusted")]
+    UntrustedEkCertificate,
+    #[error("EK certificate does not match the EK")]
+    EkMismatch,
+    #[error("Invalid attestation structure")]
+    InvalidAttestation,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Attested data does not match the expected values")]
+    AttestationMismatch,
+    #[error("PCR digest does not match the expected PCR values")]
+    PcrMismatch,
+    #[error("Invalid NV index attributes")]
+    InvalidNvAttributes,
+    #[error("Invalid object attributes")]
+    InvalidObjectAttributes,
+    #[error("Invalid format of PCR values")]
+    InvalidPcrFormat,
+    #[error("Digests do not match the configured PCR banks")]
+    BankMismatch,
+    #[error("Invalid IMA measurement list entry")]
+    InvalidImaEvent,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Copy, Clone, PartialOrd, Ord)]
 #[non_exhaustive]
-#[cfg_attr(any(feature = "serialize", test), derive(serde::Serialize))]
+#[cfg_attr(
+    any(feature = "serialize", test),
+    derive(serde::Serialize, serde::Deserialize)
+)]
 #[cfg_attr(any(feature = "serialize", test), serde(rename_all = "lowercase"))]
 pub enum DigestAlgorithm {
     Sha1,
     Sha256,
     Sha384,
     Sha512,
+    Sm3_256,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
 }
 
 impl DigestAlgorithm {
-    pub fn openssl_md(&self) -> MessageDigest {
+    /// The OpenSSL digest, which fails with UnsupportedAlgo if OpenSSL was built
+    /// without it
+    #[cfg(feature = "openssl")]
+    pub fn openssl_md(&self) -> Result<MessageDigest, Error> {
         match self {
-            DigestAlgorithm::Sha1 => MessageDigest::sha1(),
-            DigestAlgorithm::Sha256 => MessageDigest::sha256(),
-            DigestAlgorithm::Sha384 => MessageDigest::sha384(),
-            DigestAlgorithm::Sha512 => MessageDigest::sha512(),
+            DigestAlgorithm::Sha1 => Ok(MessageDigest::sha1()),
+            DigestAlgorithm::Sha256 => Ok(MessageDigest::sha256()),
+            DigestAlgorithm::Sha384 => Ok(MessageDigest::sha384()),
+            DigestAlgorithm::Sha512 => Ok(MessageDigest::sha512()),
+            DigestAlgorithm::Sm3_256 => {
+                MessageDigest::from_nid(Nid::SM3).ok_or(Error::UnsupportedAlgo)
+            }
+            DigestAlgorithm::Sha3_256 => Ok(MessageDigest::sha3_256()),
+            DigestAlgorithm::Sha3_384 => Ok(MessageDigest::sha3_384()),
+            DigestAlgorithm::Sha3_512 => Ok(MessageDigest::sha3_512()),
         }
     }
 
-    pub fn from_tpm_alg_id(alg_id: u16) -> Option<Self> {
-        match alg_id {
-            0x0004 => Some(DigestAlgorithm::Sha1),
-            0x000B => Some(DigestAlgorithm::Sha256),
-            0x000C => Some(DigestAlgorithm::Sha384),
-            0x000D => Some(DigestAlgorithm::Sha512),
-            _ => None,
+    pub fn digest_size(&self) -> usize {
+        match self {
+            DigestAlgorithm::Sha1 => 20,
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sm3_256 | DigestAlgorithm::Sha3_256 => 32,
+            DigestAlgorithm::Sha384 | DigestAlgorithm::Sha3_384 => 48,
+            DigestAlgorithm::Sha512 | DigestAlgorithm::Sha3_512 => 64,
         }
     }
+
+    pub fn from_tpm_alg_id(alg_id: u16) -> Option<Self> {
+        DigestAlgorithm::try_from(AlgorithmId::from(alg_id)).ok()
+    }
+
+    pub fn to_tpm_alg_id(&self) -> u16 {
+        AlgorithmId::from(*self).to_tpm_alg_id()
+    }
 }
 
 impl FromStr for DigestAlgorithm {
@@ -61,15 +133,98 @@ impl FromStr for DigestAlgorithm {
             "sha256" => Ok(DigestAlgorithm::Sha256),
             "sha384" => Ok(DigestAlgorithm::Sha384),
             "sha512" => Ok(DigestAlgorithm::Sha512),
+            "sm3_256" => Ok(DigestAlgorithm::Sm3_256),
+            "sha3_256" => Ok(DigestAlgorithm::Sha3_256),
+            "sha3_384" => Ok(DigestAlgorithm::Sha3_384),
+            "sha3_512" => Ok(DigestAlgorithm::Sha3_512),
             _ => Err(Error::UnsupportedAlgo),
         }
     }
 }
 
+mod io;
+pub use io::{Read, Write};
+#[cfg(feature = "std")]
+pub use io::{StdReader, StdWriter};
+
+mod algorithms;
+pub use algorithms::{AlgorithmCategory, AlgorithmId};
+
 mod pcrs;
-pub use pcrs::{PcrExtender, PcrExtenderBuilder};
+pub use pcrs::{PcrEvent, PcrExtender, PcrExtenderBuilder, PcrSelection, ReplayEvent};
+
+mod pcr_diff;
+pub use pcr_diff::{PcrComparison, PcrDiff};
 
 mod objects;
+pub use objects::{
+    EccCurve, EccScheme, KdfScheme, KeyedHashScheme, ObjectAttributes, ObjectType,
+    PublicParameters, RsaScheme, SignatureScheme, SymmetricDefinition, Tpm2b, Tpm2bPublic,
+    Tpm2bRef, TpmtPublic, TpmtSensitive,
+};
+
+mod commands;
+pub use commands::{handle_name, rp_hash, Command, CommandCode};
 
 mod credentials;
+#[cfg(feature = "openssl")]
+pub use credentials::activate_credential;
+#[cfg(feature = "rand")]
+pub use credentials::make_credential;
+pub use credentials::{activate_credential_with, make_credential_with, Credential};
+
 mod crypto;
+
+mod provider;
+pub use provider::{CryptoProvider, DigestContext, EcdhOutput};
+
+#[cfg(feature = "openssl")]
+mod openssl_provider;
+#[cfg(feature = "openssl")]
+pub use openssl_provider::OpensslProvider;
+
+#[cfg(feature = "rustcrypto")]
+mod rustcrypto_provider;
+#[cfg(feature = "rustcrypto")]
+pub use rustcrypto_provider::RustCryptoProvider;
+
+mod duplication;
+#[cfg(feature = "rand")]
+pub use duplication::wrap_for_import;
+#[cfg(feature = "openssl")]
+pub use duplication::{unwrap_duplicate, KeyImportBuilder};
+pub use duplication::{
+    unwrap_duplicate_with, wrap_for_import_with, DuplicationBlob, SealedObjectBuilder,
+};
+
+#[cfg(feature = "openssl")]
+mod ek_certificates;
+#[cfg(feature = "openssl")]
+pub use ek_certificates::{
+    CertificatePolicy, EkCertificateInfo, EkCertificateVerifier, EkCertificateVerifierBuilder,
+    TpmManufacturer,
+};
+
+mod ek_templates;
+pub use ek_templates::EkTemplate;
+
+mod attestation;
+pub use attestation::{
+    verify_attestation, verify_certify, verify_certify_creation, verify_nv_certify,
+    verify_nv_certify_range, Attestation, Attested, ClockInfo, Signature,
+};
+
+mod creation;
+pub use creation::{CreationData, CreationTicket};
+
+mod quote_history;
+pub use quote_history::{FreshnessIssue, QuoteHistory};
+
+mod nv;
+pub use nv::{NvAttributes, NvIndexEmulator, NvPublic, NvType};
+
+mod ak_policy;
+pub use ak_policy::{AkPolicy, AkViolation};
+
+mod ima;
+pub use ima::{parse_ima_log, ImaEvent, ImaEventIter, ImaEventRef};
@@ -0,0 +1,628 @@
+use alloc::{vec, vec::Vec};
+use core::ops::{BitOr, BitOrAssign};
+
+use byteorder::BigEndian;
+
+use crate::{
+    attestation::Attestation,
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    objects::Tpm2b,
+    provider::provider,
+    DigestAlgorithm, Error,
+};
+
+const TPMA_NV_TPM_NT_SHIFT: u32 = 4;
+const TPMA_NV_TPM_NT_MASK: u32 = 0xF << TPMA_NV_TPM_NT_SHIFT;
+/// Bits 8, 9 and 20 to 24 are reserved
+const TPMA_NV_RESERVED: u32 = 0x01F0_0300;
+
+/// The first handle of the NV index range
+const NV_INDEX_FIRST: u32 = 0x0100_0000;
+const NV_INDEX_LAST: u32 = 0x01FF_FFFF;
+
+/// A TPM_NT value, the type of an NV index
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
+pub enum NvType {
+    Ordinary,
+    Counter,
+    Bits,
+    Extend,
+    PinFail,
+    PinPass,
+}
+
+impl NvType {
+    pub fn from_tpm_nt(nt: u8) -> Option<Self> {
+        match nt {
+            0x0 => Some(NvType::Ordinary),
+            0x1 => Some(NvType::Counter),
+            0x2 => Some(NvType::Bits),
+            0x4 => Some(NvType::Extend),
+            0x8 => Some(NvType::PinFail),
+            0x9 => Some(NvType::PinPass),
+            _ => None,
+        }
+    }
+
+    pub fn to_tpm_nt(&self) -> u8 {
+        match self {
+            NvType::Ordinary => 0x0,
+            NvType::Counter => 0x1,
+            NvType::Bits => 0x2,
+            NvType::Extend => 0x4,
+            NvType::PinFail => 0x8,
+            NvType::PinPass => 0x9,
+        }
+    }
+}
+
+/// A TPMA_NV
+///
+/// The TPM_NT field is not a flag, and is accessed with nv_type and with_nv_type.
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone, Default)]
+pub struct NvAttributes(u32);
+
+impl NvAttributes {
+    pub const PPWRITE: NvAttributes = NvAttributes(1 << 0);
+    pub const OWNERWRITE: NvAttributes = NvAttributes(1 << 1);
+    pub const AUTHWRITE: NvAttributes = NvAttributes(1 << 2);
+    pub const POLICYWRITE: NvAttributes = NvAttributes(1 << 3);
+    pub const POLICY_DELETE: NvAttributes = NvAttributes(1 << 10);
+    pub const WRITELOCKED: NvAttributes = NvAttributes(1 << 11);
+    pub const WRITEALL: NvAttributes = NvAttributes(1 << 12);
+    pub const WRITEDEFINE: NvAttributes = NvAttributes(1 << 13);
+    pub const WRITE_STCLEAR: NvAttributes = NvAttributes(1 << 14);
+    pub const GLOBALLOCK: NvAttributes = NvAttributes(1 << 15);
+    pub const PPREAD: NvAttributes = NvAttributes(1 << 16);
+    pub const OWNERREAD: NvAttributes = NvAttributes(1 << 17);
+    pub const AUTHREAD: NvAttributes = NvAttributes(1 << 18);
+    pub const POLICYREAD: NvAttributes = NvAttributes(1 << 19);
+    pub const NO_DA: NvAttributes = NvAttributes(1 << 25);
+    pub const ORDERLY: NvAttributes = NvAttributes(1 << 26);
+    pub const CLEAR_STCLEAR: NvAttributes = NvAttributes(1 << 27);
+    pub const READLOCKED: NvAttributes = NvAttributes(1 << 28);
+    pub const WRITTEN: NvAttributes = NvAttributes(1 << 29);
+    pub const PLATFORMCREATE: NvAttributes = NvAttributes(1 << 30);
+    pub const READ_STCLEAR: NvAttributes = NvAttributes(1 << 31);
+
+    /// No attributes, with the TPM_NT field set to ordinary
+    pub fn empty() -> Self {
+        NvAttributes(0)
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        NvAttributes(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: NvAttributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether any of the attributes in other is set
+    pub fn intersects(&self, other: NvAttributes) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn set(&mut self, other: NvAttributes, value: bool) -> &mut Self {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+        self
+    }
+
+    /// The TPM_NT field, or None if it holds a reserved value
+    pub fn nv_type(&self) -> Option<NvType> {
+        NvType::from_tpm_nt(((self.0 & TPMA_NV_TPM_NT_MASK) >> TPMA_NV_TPM_NT_SHIFT) as u8)
+    }
+
+    pub fn with_nv_type(self, nv_type: NvType) -> Self {
+        NvAttributes(
+            (self.0 & !TPMA_NV_TPM_NT_MASK)
+                | ((nv_type.to_tpm_nt() as u32) << TPMA_NV_TPM_NT_SHIFT),
+        )
+    }
+}
+
+impl BitOr for NvAttributes {
+    type Output = NvAttributes;
+
+    fn bitor(self, rhs: NvAttributes) -> NvAttributes {
+        NvAttributes(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for NvAttributes {
+    fn bitor_assign(&mut self, rhs: NvAttributes) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A TPMS_NV_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvPublic {
+    pub nv_index: u32,
+    pub name_alg: DigestAlgorithm,
+    pub attributes: NvAttributes,
+    pub auth_policy: Vec<u8>,
+    pub data_size: u16,
+}
+
+impl NvPublic {
+    pub fn nv_type(&self) -> Option<NvType> {
+        self.attributes.nv_type()
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<NvPublic, Error> {
+        Ok(NvPublic {
+            nv_index: reader.read_u32::<BigEndian>()?,
+            name_alg: DigestAlgorithm::from_tpm_alg_id(reader.read_u16::<BigEndian>()?)
+                .ok_or(Error::UnsupportedAlgo)?,
+            attributes: NvAttributes(reader.read_u32::<BigEndian>()?),
+            auth_policy: reader.read_tpm2b()?,
+            data_size: reader.read_u16::<BigEndian>()?,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u32::<BigEndian>(self.nv_index)?;
+        writer.write_u16::<BigEndian>(self.name_alg.to_tpm_alg_id())?;
+        writer.write_u32::<BigEndian>(self.attributes.0)?;
+        Tpm2b::new(&self.auth_policy[..])?.to_writer(&mut writer)?;
+        writer.write_u16::<BigEndian>(self.data_size)?;
+        Ok(())
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Computes the Name of the index: nameAlg || H_nameAlg(TPMS_NV_PUBLIC)
+    ///
+    /// The Name changes when the index is first written, as that sets TPMA_NV_WRITTEN.
+    pub fn name(&self) -> Result<Vec<u8>, Error> {
+        let mut name = self.name_alg.to_tpm_alg_id().to_be_bytes().to_vec();
+        name.extend_from_slice(&provider().hash(self.name_alg, &[&self.to_vec()?])?);
+        Ok(name)
+    }
+
+    /// Checks the public area against the rules of TPM2_NV_DefineSpace
+    ///
+    /// Returns Error::InvalidNvAttributes for attribute combinations the TPM would
+    /// reject, and Error::InvalidSize if the data size does not fit the index type.
+    pub fn validate(&self) -> Result<(), Error> {
+        let attrs = self.attributes;
+        let nv_type = attrs.nv_type().ok_or(Error::InvalidNvAttributes)?;
+
+        if self.nv_index < NV_INDEX_FIRST || self.nv_index > NV_INDEX_LAST {
+            return Err(Error::InvalidNvAttributes);
+        }
+        if attrs.0 & TPMA_NV_RESERVED != 0 {
+            return Err(Error::InvalidNvAttributes);
+        }
+        // The state attributes are set by the TPM, not by the caller
+        if attrs.intersects(
+            NvAttributes::WRITELOCKED | NvAttributes::READLOCKED | NvAttributes::WRITTEN,
+        ) {
+            return Err(Error::InvalidNvAttributes);
+        }
+        // At least one way to write and to read the index is needed
+        if !attrs.intersects(
+            NvAttributes::PPWRITE
+                | NvAttributes::OWNERWRITE
+                | NvAttributes::AUTHWRITE
+                | NvAttributes::POLICYWRITE,
+        ) || !attrs.intersects(
+            NvAttributes::PPREAD
+                | NvAttributes::OWNERREAD
+                | NvAttributes::AUTHREAD
+                | NvAttributes::POLICYREAD,
+        ) {
+            return Err(Error::InvalidNvAttributes);
+        }
+        // Only the platform can delete an index with a policy
+        if attrs.contains(NvAttributes::POLICY_DELETE)
+            && !attrs.contains(NvAttributes::PLATFORMCREATE)
+        {
+            return Err(Error::InvalidNvAttributes);
+        }
+
+        match nv_type {
+            NvType::Ordinary => {}
+            NvType::Counter | NvType::Bits | NvType::PinFail | NvType::PinPass => {
+                if self.data_size != 8 {
+                    return Err(Error::InvalidSize);
+                }
+            }
+            NvType::Extend => {
+                if self.data_size as usize != self.name_alg.digest_size() {
+                    return Err(Error::InvalidSize);
+                }
+            }
+        }
+        // A counter can not go back to zero
+        if nv_type == NvType::Counter && attrs.contains(NvAttributes::CLEAR_STCLEAR) {
+            return Err(Error::InvalidNvAttributes);
+        }
+        if nv_type == NvType::PinFail && !attrs.contains(NvAttributes::NO_DA) {
+            return Err(Error::InvalidNvAttributes);
+        }
+        // The authValue of a PIN index can only be used to read it
+        if (nv_type == NvType::PinFail || nv_type == NvType::PinPass)
+            && attrs.intersects(
+                NvAttributes::AUTHWRITE | NvAttributes::GLOBALLOCK | NvAttributes::WRITEDEFINE,
+            )
+        {
+            return Err(Error::InvalidNvAttributes);
+        }
+
+        Ok(())
+    }
+}
+
+/// The contents of an NV index, emulated offline
+///
+/// This computes the data the TPM holds after a sequence of NV_Extend, NV_Increment,
+/// NV_SetBits or NV_Write commands, to compare against NV_Certify output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvIndexEmulator {
+    public: NvPublic,
+    data: Vec<u8>,
+    counter_start: u64,
+}
+
+impl NvIndexEmulator {
+    /// Emulates a newly defined index, which must be a valid define-time public area
+    ///
+    /// Extend and bit field indices start out as zero. Ordinary indices start out
+    /// as 0xFF, like the reference implementation.
+    pub fn new(public: NvPublic) -> Result<Self, Error> {
+        public.validate()?;
+        let fill = match public.nv_type() {
+            Some(NvType::Ordinary) => 0xFF,
+            Some(NvType::Extend) | Some(NvType::Counter) | Some(NvType::Bits) => 0x00,
+            _ => return Err(Error::InvalidNvAttributes),
+        };
+        let data = vec![fill; public.data_size as usize];
+
+        Ok(NvIndexEmulator {
+            public,
+            data,
+            counter_start: 0,
+        })
+    }
+
+    /// Sets the value a counter starts from on its first increment
+    ///
+    /// The TPM starts new counters at the highest value any counter ever had, so this
+    /// needs to be read from the TPM.
+    pub fn set_counter_start(&mut self, value: u64) -> &mut Self {
+        self.counter_start = value;
+        self
+    }
+
+    fn require_type(&self, nv_type: NvType) -> Result<(), Error> {
+        if self.public.nv_type() != Some(nv_type) {
+            return Err(Error::InvalidNvAttributes);
+        }
+        Ok(())
+    }
+
+    fn is_written(&self) -> bool {
+        self.public.attributes.contains(NvAttributes::WRITTEN)
+    }
+
+    fn set_written(&mut self) {
+        self.public.attributes |= NvAttributes::WRITTEN;
+    }
+
+    fn read_u64(&self) -> u64 {
+        self.data
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+    }
+
+    /// NV_Extend: data = H_nameAlg(data || extend_data)
+    pub fn extend(&mut self, extend_data: &[u8]) -> Result<(), Error> {
+        self.require_type(NvType::Extend)?;
+
+        self.data = provider().hash(self.public.name_alg, &[&self.data, extend_data])?;
+        self.set_written();
+        Ok(())
+    }
+
+    /// NV_Increment
+    pub fn increment(&mut self) -> Result<(), Error> {
+        self.require_type(NvType::Counter)?;
+
+        let value = if self.is_written() {
+            self.read_u64()
+        } else {
+            self.counter_start
+        };
+        self.data = value.wrapping_add(1).to_be_bytes().to_vec();
+        self.set_written();
+        Ok(())
+    }
+
+    /// NV_SetBits: data = data | bits
+    pub fn set_bits(&mut self, bits: u64) -> Result<(), Error> {
+        self.require_type(NvType::Bits)?;
+
+        self.data = (self.read_u64() | bits).to_be_bytes().to_vec();
+        self.set_written();
+        Ok(())
+    }
+
+    /// NV_Write
+    pub fn write(&mut self, offset: u16, write_data: &[u8]) -> Result<(), Error> {
+        self.require_type(NvType::Ordinary)?;
+
+        let offset = offset as usize;
+        if offset + write_data.len() > self.data.len() {
+            return Err(Error::InvalidSize);
+        }
+        if self.public.attributes.contains(NvAttributes::WRITEALL)
+            && write_data.len() != self.data.len()
+        {
+            return Err(Error::InvalidSize);
+        }
+        self.data[offset..offset + write_data.len()].copy_from_slice(write_data);
+        self.set_written();
+        Ok(())
+    }
+
+    /// The public area, with TPMA_NV_WRITTEN set once the index was written
+    pub fn public(&self) -> &NvPublic {
+        &self.public
+    }
+
+    /// The data of the index, or None if it was never written and can't be read
+    pub fn contents(&self) -> Option<&[u8]> {
+        if self.is_written() {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+
+    /// Checks that a verified NV_Certify attestation certifies the whole emulated index
+    ///
+    /// The attestation must hold all the data from offset 0, or its nvDigest with
+    /// hash_alg, the hash algorithm of the signature over the attestation. Partial
+    /// ranges are rejected, as they say nothing about the rest of the index.
+    pub fn verify_certified(
+        &self,
+        attestation: &Attestation,
+        hash_alg: DigestAlgorithm,
+    ) -> Result<(), Error> {
+        let contents = self.contents().ok_or(Error::AttestationMismatch)?;
+        if attestation
+            .attested
+            .certifies_nv_data(&self.public.name()?, contents, hash_alg)?
+        {
+            Ok(())
+        } else {
+            Err(Error::AttestationMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attestation::{Attested, ClockInfo};
+
+    fn counter() -> NvPublic {
+        NvPublic {
+            nv_index: 0x0150_0016,
+            name_alg: DigestAlgorithm::Sha256,
+            attributes: (NvAttributes::OWNERWRITE
+                | NvAttributes::OWNERREAD
+                | NvAttributes::AUTHREAD
+                | NvAttributes::NO_DA)
+                .with_nv_type(NvType::Counter),
+            auth_policy: Vec::new(),
+            data_size: 8,
+        }
+    }
+
+    #[test]
+    fn test_nv_public_encoding() {
+        let public = counter();
+        assert_eq!(public.attributes.bits(), 0x0206_0012);
+        assert_eq!(public.nv_type(), Some(NvType::Counter));
+
+        let encoded = public.to_vec().unwrap();
+        assert_eq!(
+            encoded,
+            hex::decode("01500016000b0206001200000008").unwrap()
+        );
+        assert_eq!(NvPublic::from_reader(&encoded[..]).unwrap(), public);
+
+        let mut name = vec![0x00, 0x0b];
+        name.extend_from_slice(
+            &provider()
+                .hash(DigestAlgorithm::Sha256, &[&encoded])
+                .unwrap(),
+        );
+        assert_eq!(public.name().unwrap(), name);
+    }
+
+    #[test]
+    fn test_nv_type() {
+        let attrs = NvAttributes::from_bits(0x0000_0070);
+        assert_eq!(attrs.nv_type(), None);
+
+        let attrs = attrs.with_nv_type(NvType::Extend);
+        assert_eq!(attrs.nv_type(), Some(NvType::Extend));
+        assert_eq!(attrs.bits(), 0x0000_0040);
+    }
+
+    #[test]
+    fn test_validate() {
+        counter().validate().unwrap();
+
+        let mut public = counter();
+        public.attributes |= NvAttributes::CLEAR_STCLEAR;
+        match public.validate() {
+            Err(Error::InvalidNvAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut public = counter();
+        public.data_size = 4;
+        match public.validate() {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut public = counter();
+        public.attributes.set(NvAttributes::OWNERREAD, false);
+        public.attributes.set(NvAttributes::AUTHREAD, false);
+        match public.validate() {
+            Err(Error::InvalidNvAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut public = counter();
+        public.attributes = public.attributes.with_nv_type(NvType::Extend);
+        match public.validate() {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        public.data_size = 32;
+        public.validate().unwrap();
+
+        let mut public = counter();
+        public.attributes |= NvAttributes::WRITTEN;
+        match public.validate() {
+            Err(Error::InvalidNvAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut public = counter();
+        public.nv_index = 0x8100_0001;
+        match public.validate() {
+            Err(Error::InvalidNvAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_emulate_extend() {
+        let mut public = counter();
+        public.attributes = public.attributes.with_nv_type(NvType::Extend);
+        public.data_size = 32;
+
+        let mut index = NvIndexEmulator::new(public).unwrap();
+        assert_eq!(index.contents(), None);
+        index.extend(b"testing 42").unwrap();
+        assert_eq!(
+            index.contents().unwrap(),
+            &hex::decode("f3bf33a1a97493fadc8b6d7cc7f54c09b94d14554186e94791755cb0efb6fae6")
+                .unwrap()[..],
+        );
+        assert!(index.public().attributes.contains(NvAttributes::WRITTEN));
+
+        match index.increment() {
+            Err(Error::InvalidNvAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_emulate_counter_and_bits() {
+        let mut index = NvIndexEmulator::new(counter()).unwrap();
+        index.set_counter_start(41);
+        index.increment().unwrap();
+        assert_eq!(index.contents().unwrap(), &42u64.to_be_bytes());
+        index.increment().unwrap();
+        assert_eq!(index.contents().unwrap(), &43u64.to_be_bytes());
+
+        let mut public = counter();
+        public.attributes = public.attributes.with_nv_type(NvType::Bits);
+        let mut index = NvIndexEmulator::new(public).unwrap();
+        index.set_bits(0x0100).unwrap();
+        index.set_bits(0x0001).unwrap();
+        assert_eq!(index.contents().unwrap(), &0x0101u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_emulate_verify_certified() {
+        let mut index = NvIndexEmulator::new(counter()).unwrap();
+        index.increment().unwrap();
+
+        let mut attestation = Attestation {
+            qualified_signer: Vec::new(),
+            extra_data: Vec::new(),
+            clock_info: ClockInfo {
+                clock: 0,
+                reset_count: 0,
+                restart_count: 0,
+                safe: true,
+            },
+            firmware_version: 0,
+            attested: Attested::Nv {
+                index_name: index.public().name().unwrap(),
+                offset: 0,
+                nv_contents: vec![0, 0, 0, 0, 0, 0, 0, 1],
+            },
+        };
+        index
+            .verify_certified(&attestation, DigestAlgorithm::Sha256)
+            .unwrap();
+
+        // Part of the data, or none of it, does not certify the index
+        for (offset, nv_contents) in [(6, vec![0, 1]), (0, vec![0; 7]), (0, Vec::new())] {
+            attestation.attested = Attested::Nv {
+                index_name: index.public().name().unwrap(),
+                offset,
+                nv_contents,
+            };
+            match index.verify_certified(&attestation, DigestAlgorithm::Sha256) {
+                Err(Error::AttestationMismatch) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        // The Name before the first write does not include TPMA_NV_WRITTEN
+        attestation.attested = Attested::Nv {
+            index_name: counter().name().unwrap(),
+            offset: 0,
+            nv_contents: vec![0, 0, 0, 0, 0, 0, 0, 1],
+        };
+        match index.verify_certified(&attestation, DigestAlgorithm::Sha256) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // NV_Certify of the whole index certifies the digest of the contents
+        attestation.attested = Attested::NvDigest {
+            index_name: index.public().name().unwrap(),
+            nv_digest: provider()
+                .hash(DigestAlgorithm::Sha256, &[index.contents().unwrap()])
+                .unwrap(),
+        };
+        index
+            .verify_certified(&attestation, DigestAlgorithm::Sha256)
+            .unwrap();
+        match index.verify_certified(&attestation, DigestAlgorithm::Sha384) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        index.increment().unwrap();
+        match index.verify_certified(&attestation, DigestAlgorithm::Sha256) {
+            Err(Error::AttestationMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
@@ -1,21 +1,47 @@
-use std::convert::TryFrom;
-use std::io::{Read, Write};
+use alloc::vec::Vec;
+#[cfg(feature = "openssl")]
+use core::convert::TryFrom;
+use core::ops::{BitOr, BitOrAssign};
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use openssl::pkey::{PKey, Public};
+use byteorder::{BigEndian, ByteOrder};
+#[cfg(feature = "openssl")]
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::{HasPrivate, HasPublic, Id as pkey_id, PKey, PKeyRef, Private, Public},
+    rsa::Rsa,
+};
 
-use crate::Error;
+use crate::{
+    io::{take, Read, ReadBytesExt, Write, WriteBytesExt},
+    provider::provider,
+    AlgorithmId, DigestAlgorithm, Error,
+};
 
 #[derive(Debug)]
 pub struct Tpm2b(Vec<u8>);
 
 impl Tpm2b {
+    pub fn new<T: Into<Vec<u8>>>(contents: T) -> Result<Tpm2b, Error> {
+        let contents = contents.into();
+        if contents.len() > u16::MAX as usize {
+            return Err(Error::InvalidSize);
+        }
+        Ok(Tpm2b(contents))
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        &self.0
+    }
+
     pub fn from_reader<R: Read>(mut reader: R) -> Result<Tpm2b, Error> {
-        let size = reader.read_u16::<BigEndian>()? as usize;
-        let mut contents: Vec<u8> = vec![0; size];
-        reader.read_exact(&mut contents)?;
+        Ok(Tpm2b(reader.read_tpm2b()?))
+    }
 
-        Ok(Tpm2b(contents))
+    /// Parses a TPM2B from the start of buf, and advances buf past it
+    pub fn from_bytes(buf: &mut &[u8]) -> Result<Tpm2b, Error> {
+        Tpm2bRef::from_bytes(buf).map(Tpm2b::from)
     }
 
     pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
@@ -26,20 +52,1112 @@ impl Tpm2b {
     }
 }
 
-pub struct Tpm2bPublic {}
+/// A TPM2B that borrows its contents from the buffer it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tpm2bRef<'a>(&'a [u8]);
+
+impl<'a> Tpm2bRef<'a> {
+    pub fn new(contents: &'a [u8]) -> Result<Tpm2bRef<'a>, Error> {
+        if contents.len() > u16::MAX as usize {
+            return Err(Error::InvalidSize);
+        }
+        Ok(Tpm2bRef(contents))
+    }
+
+    pub fn contents(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Parses a TPM2B from the start of buf without copying, and advances buf past it
+    pub fn from_bytes(buf: &mut &'a [u8]) -> Result<Tpm2bRef<'a>, Error> {
+        let mut rest = *buf;
+        let size = BigEndian::read_u16(take(&mut rest, 2)?) as usize;
+        let contents = take(&mut rest, size)?;
+        *buf = rest;
+        Ok(Tpm2bRef(contents))
+    }
+}
+
+impl From<Tpm2bRef<'_>> for Tpm2b {
+    fn from(tpm2b: Tpm2bRef<'_>) -> Self {
+        Tpm2b(tpm2b.0.to_vec())
+    }
+}
+
+pub(crate) const TPM_ALG_RSA: u16 = AlgorithmId::Rsa.to_tpm_alg_id();
+pub(crate) const TPM_ALG_KEYEDHASH: u16 = AlgorithmId::KeyedHash.to_tpm_alg_id();
+pub(crate) const TPM_ALG_HMAC: u16 = AlgorithmId::Hmac.to_tpm_alg_id();
+pub(crate) const TPM_ALG_AES: u16 = AlgorithmId::Aes.to_tpm_alg_id();
+pub(crate) const TPM_ALG_MGF1: u16 = AlgorithmId::Mgf1.to_tpm_alg_id();
+pub(crate) const TPM_ALG_NULL: u16 = AlgorithmId::Null.to_tpm_alg_id();
+pub(crate) const TPM_ALG_RSASSA: u16 = AlgorithmId::RsaSsa.to_tpm_alg_id();
+pub(crate) const TPM_ALG_RSAES: u16 = AlgorithmId::RsaEs.to_tpm_alg_id();
+pub(crate) const TPM_ALG_RSAPSS: u16 = AlgorithmId::RsaPss.to_tpm_alg_id();
+pub(crate) const TPM_ALG_OAEP: u16 = AlgorithmId::Oaep.to_tpm_alg_id();
+pub(crate) const TPM_ALG_ECDSA: u16 = AlgorithmId::EcDsa.to_tpm_alg_id();
+pub(crate) const TPM_ALG_ECDH: u16 = AlgorithmId::EcDh.to_tpm_alg_id();
+pub(crate) const TPM_ALG_ECDAA: u16 = AlgorithmId::EcDaa.to_tpm_alg_id();
+pub(crate) const TPM_ALG_SM2: u16 = AlgorithmId::Sm2.to_tpm_alg_id();
+pub(crate) const TPM_ALG_ECSCHNORR: u16 = AlgorithmId::EcSchnorr.to_tpm_alg_id();
+pub(crate) const TPM_ALG_KDF1_SP800_56A: u16 = AlgorithmId::Kdf1Sp800_56a.to_tpm_alg_id();
+pub(crate) const TPM_ALG_KDF2: u16 = AlgorithmId::Kdf2.to_tpm_alg_id();
+pub(crate) const TPM_ALG_KDF1_SP800_108: u16 = AlgorithmId::Kdf1Sp800_108.to_tpm_alg_id();
+pub(crate) const TPM_ALG_ECC: u16 = AlgorithmId::Ecc.to_tpm_alg_id();
+pub(crate) const TPM_ALG_SYMCIPHER: u16 = AlgorithmId::SymCipher.to_tpm_alg_id();
+pub(crate) const TPM_ALG_CFB: u16 = AlgorithmId::Cfb.to_tpm_alg_id();
+
+pub(crate) fn read_hash_alg<R: Read>(mut reader: R) -> Result<DigestAlgorithm, Error> {
+    DigestAlgorithm::from_tpm_alg_id(reader.read_u16::<BigEndian>()?).ok_or(Error::UnsupportedAlgo)
+}
+
+/// A TPMT_SIG_SCHEME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignatureScheme {
+    Null,
+    RsaSsa(DigestAlgorithm),
+    RsaPss(DigestAlgorithm),
+    EcDsa(DigestAlgorithm),
+}
+
+impl SignatureScheme {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<SignatureScheme, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(SignatureScheme::Null),
+            TPM_ALG_RSASSA => Ok(SignatureScheme::RsaSsa(read_hash_alg(reader)?)),
+            TPM_ALG_RSAPSS => Ok(SignatureScheme::RsaPss(read_hash_alg(reader)?)),
+            TPM_ALG_ECDSA => Ok(SignatureScheme::EcDsa(read_hash_alg(reader)?)),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (scheme, hash_alg) = match self {
+            SignatureScheme::Null => (TPM_ALG_NULL, None),
+            SignatureScheme::RsaSsa(algo) => (TPM_ALG_RSASSA, Some(algo)),
+            SignatureScheme::RsaPss(algo) => (TPM_ALG_RSAPSS, Some(algo)),
+            SignatureScheme::EcDsa(algo) => (TPM_ALG_ECDSA, Some(algo)),
+        };
+        writer.write_u16::<BigEndian>(scheme)?;
+        if let Some(algo) = hash_alg {
+            writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A TPMT_SYM_DEF_OBJECT
+///
+/// Objects can only use AES in CFB mode, so only the key size is variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SymmetricDefinition {
+    Null,
+    Aes { key_bits: u16 },
+}
+
+impl SymmetricDefinition {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<SymmetricDefinition, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(SymmetricDefinition::Null),
+            TPM_ALG_AES => {
+                let key_bits = reader.read_u16::<BigEndian>()?;
+                if reader.read_u16::<BigEndian>()? != TPM_ALG_CFB {
+                    return Err(Error::UnsupportedAlgo);
+                }
+                Ok(SymmetricDefinition::Aes { key_bits })
+            }
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        match self {
+            SymmetricDefinition::Null => writer.write_u16::<BigEndian>(TPM_ALG_NULL)?,
+            SymmetricDefinition::Aes { key_bits } => {
+                writer.write_u16::<BigEndian>(TPM_ALG_AES)?;
+                writer.write_u16::<BigEndian>(*key_bits)?;
+                writer.write_u16::<BigEndian>(TPM_ALG_CFB)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A TPMI_ALG_PUBLIC
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
+pub enum ObjectType {
+    Rsa,
+    KeyedHash,
+    Ecc,
+    SymCipher,
+}
+
+impl ObjectType {
+    pub fn from_tpm_alg_id(alg_id: u16) -> Option<Self> {
+        match alg_id {
+            TPM_ALG_RSA => Some(ObjectType::Rsa),
+            TPM_ALG_KEYEDHASH => Some(ObjectType::KeyedHash),
+            TPM_ALG_ECC => Some(ObjectType::Ecc),
+            TPM_ALG_SYMCIPHER => Some(ObjectType::SymCipher),
+            _ => None,
+        }
+    }
+
+    pub fn to_tpm_alg_id(&self) -> u16 {
+        match self {
+            ObjectType::Rsa => TPM_ALG_RSA,
+            ObjectType::KeyedHash => TPM_ALG_KEYEDHASH,
+            ObjectType::Ecc => TPM_ALG_ECC,
+            ObjectType::SymCipher => TPM_ALG_SYMCIPHER,
+        }
+    }
+}
+
+/// A TPMA_OBJECT
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone, Default)]
+pub struct ObjectAttributes(u32);
+
+impl ObjectAttributes {
+    pub const FIXED_TPM: ObjectAttributes = ObjectAttributes(1 << 1);
+    pub const ST_CLEAR: ObjectAttributes = ObjectAttributes(1 << 2);
+    pub const FIXED_PARENT: ObjectAttributes = ObjectAttributes(1 << 4);
+    pub const SENSITIVE_DATA_ORIGIN: ObjectAttributes = ObjectAttributes(1 << 5);
+    pub const USER_WITH_AUTH: ObjectAttributes = ObjectAttributes(1 << 6);
+    pub const ADMIN_WITH_POLICY: ObjectAttributes = ObjectAttributes(1 << 7);
+    pub const NO_DA: ObjectAttributes = ObjectAttributes(1 << 10);
+    pub const ENCRYPTED_DUPLICATION: ObjectAttributes = ObjectAttributes(1 << 11);
+    pub const RESTRICTED: ObjectAttributes = ObjectAttributes(1 << 16);
+    pub const DECRYPT: ObjectAttributes = ObjectAttributes(1 << 17);
+    /// The sign attribute, which is also the encrypt attribute for symmetric keys
+    pub const SIGN_ENCRYPT: ObjectAttributes = ObjectAttributes(1 << 18);
+    pub const X509_SIGN: ObjectAttributes = ObjectAttributes(1 << 19);
+
+    const RESERVED: u32 = 0xFFF0_F309;
+
+    pub fn empty() -> Self {
+        ObjectAttributes(0)
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        ObjectAttributes(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: ObjectAttributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether any of the attributes in other is set
+    pub fn intersects(&self, other: ObjectAttributes) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn set(&mut self, other: ObjectAttributes, value: bool) -> &mut Self {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+        self
+    }
+
+    /// Whether the object can not be duplicated, neither directly nor through a parent
+    pub fn is_fixed(&self) -> bool {
+        self.contains(ObjectAttributes::FIXED_TPM | ObjectAttributes::FIXED_PARENT)
+    }
+
+    /// Whether the key only signs digests the TPM computed itself, like attestations
+    pub fn is_restricted_signing(&self) -> bool {
+        self.contains(ObjectAttributes::RESTRICTED | ObjectAttributes::SIGN_ENCRYPT)
+            && !self.contains(ObjectAttributes::DECRYPT)
+    }
+
+    /// Whether the key is a storage parent, that only decrypts TPM-formatted objects
+    pub fn is_restricted_decryption(&self) -> bool {
+        self.contains(ObjectAttributes::RESTRICTED | ObjectAttributes::DECRYPT)
+            && !self.contains(ObjectAttributes::SIGN_ENCRYPT)
+    }
+
+    /// Checks the attributes against the consistency rules of TPM2_Create
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.0 & ObjectAttributes::RESERVED != 0 {
+            return Err(Error::InvalidObjectAttributes);
+        }
+        // An object that can be moved to another parent can leave the TPM with it
+        if self.contains(ObjectAttributes::FIXED_TPM)
+            && !self.contains(ObjectAttributes::FIXED_PARENT)
+        {
+            return Err(Error::InvalidObjectAttributes);
+        }
+        if self.contains(ObjectAttributes::RESTRICTED)
+            && self.contains(ObjectAttributes::DECRYPT)
+                == self.contains(ObjectAttributes::SIGN_ENCRYPT)
+        {
+            return Err(Error::InvalidObjectAttributes);
+        }
+        if self.contains(ObjectAttributes::ENCRYPTED_DUPLICATION | ObjectAttributes::FIXED_PARENT) {
+            return Err(Error::InvalidObjectAttributes);
+        }
+        Ok(())
+    }
+
+    /// Checks that the attributes are valid for an attestation key: a restricted
+    /// signing key that was generated by and can not leave the TPM.
+    pub fn validate_attestation_key(&self) -> Result<(), Error> {
+        self.validate()?;
+        if !self.is_restricted_signing()
+            || !self.is_fixed()
+            || !self.contains(ObjectAttributes::SENSITIVE_DATA_ORIGIN)
+        {
+            return Err(Error::InvalidObjectAttributes);
+        }
+        Ok(())
+    }
+}
+
+impl BitOr for ObjectAttributes {
+    type Output = ObjectAttributes;
+
+    fn bitor(self, rhs: ObjectAttributes) -> ObjectAttributes {
+        ObjectAttributes(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ObjectAttributes {
+    fn bitor_assign(&mut self, rhs: ObjectAttributes) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A TPMI_ECC_CURVE
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
+pub enum EccCurve {
+    NistP192,
+    NistP224,
+    NistP256,
+    NistP384,
+    NistP521,
+    BnP256,
+    BnP638,
+    Sm2P256,
+}
+
+impl EccCurve {
+    pub fn from_tpm_ecc_curve(curve_id: u16) -> Option<Self> {
+        match curve_id {
+            0x0001 => Some(EccCurve::NistP192),
+            0x0002 => Some(EccCurve::NistP224),
+            0x0003 => Some(EccCurve::NistP256),
+            0x0004 => Some(EccCurve::NistP384),
+            0x0005 => Some(EccCurve::NistP521),
+            0x0010 => Some(EccCurve::BnP256),
+            0x0011 => Some(EccCurve::BnP638),
+            0x0020 => Some(EccCurve::Sm2P256),
+            _ => None,
+        }
+    }
+
+    pub fn to_tpm_ecc_curve(&self) -> u16 {
+        match self {
+            EccCurve::NistP192 => 0x0001,
+            EccCurve::NistP224 => 0x0002,
+            EccCurve::NistP256 => 0x0003,
+            EccCurve::NistP384 => 0x0004,
+            EccCurve::NistP521 => 0x0005,
+            EccCurve::BnP256 => 0x0010,
+            EccCurve::BnP638 => 0x0011,
+            EccCurve::Sm2P256 => 0x0020,
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    pub fn openssl_nid(&self) -> Result<Nid, Error> {
+        match self {
+            EccCurve::NistP192 => Ok(Nid::X9_62_PRIME192V1),
+            EccCurve::NistP224 => Ok(Nid::SECP224R1),
+            EccCurve::NistP256 => Ok(Nid::X9_62_PRIME256V1),
+            EccCurve::NistP384 => Ok(Nid::SECP384R1),
+            EccCurve::NistP521 => Ok(Nid::SECP521R1),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    pub fn from_openssl_nid(nid: Nid) -> Option<Self> {
+        match nid {
+            Nid::X9_62_PRIME192V1 => Some(EccCurve::NistP192),
+            Nid::SECP224R1 => Some(EccCurve::NistP224),
+            Nid::X9_62_PRIME256V1 => Some(EccCurve::NistP256),
+            Nid::SECP384R1 => Some(EccCurve::NistP384),
+            Nid::SECP521R1 => Some(EccCurve::NistP521),
+            _ => None,
+        }
+    }
+
+    /// The size of a coordinate or private scalar on this curve, in bytes
+    pub fn key_size(&self) -> usize {
+        match self {
+            EccCurve::NistP192 => 24,
+            EccCurve::NistP224 => 28,
+            EccCurve::NistP256 | EccCurve::BnP256 | EccCurve::Sm2P256 => 32,
+            EccCurve::NistP384 => 48,
+            EccCurve::NistP521 => 66,
+            EccCurve::BnP638 => 80,
+        }
+    }
+}
+
+/// A TPMT_RSA_SCHEME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RsaScheme {
+    Null,
+    RsaSsa(DigestAlgorithm),
+    RsaPss(DigestAlgorithm),
+    RsaEs,
+    Oaep(DigestAlgorithm),
+}
+
+impl RsaScheme {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<RsaScheme, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(RsaScheme::Null),
+            TPM_ALG_RSASSA => Ok(RsaScheme::RsaSsa(read_hash_alg(reader)?)),
+            TPM_ALG_RSAPSS => Ok(RsaScheme::RsaPss(read_hash_alg(reader)?)),
+            TPM_ALG_RSAES => Ok(RsaScheme::RsaEs),
+            TPM_ALG_OAEP => Ok(RsaScheme::Oaep(read_hash_alg(reader)?)),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (scheme, hash_alg) = match self {
+            RsaScheme::Null => (TPM_ALG_NULL, None),
+            RsaScheme::RsaSsa(algo) => (TPM_ALG_RSASSA, Some(algo)),
+            RsaScheme::RsaPss(algo) => (TPM_ALG_RSAPSS, Some(algo)),
+            RsaScheme::RsaEs => (TPM_ALG_RSAES, None),
+            RsaScheme::Oaep(algo) => (TPM_ALG_OAEP, Some(algo)),
+        };
+        writer.write_u16::<BigEndian>(scheme)?;
+        if let Some(algo) = hash_alg {
+            writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A TPMT_ECC_SCHEME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EccScheme {
+    Null,
+    EcDsa(DigestAlgorithm),
+    EcDh(DigestAlgorithm),
+    EcDaa(DigestAlgorithm, u16),
+    EcSchnorr(DigestAlgorithm),
+    Sm2(DigestAlgorithm),
+}
+
+impl EccScheme {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<EccScheme, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(EccScheme::Null),
+            TPM_ALG_ECDSA => Ok(EccScheme::EcDsa(read_hash_alg(reader)?)),
+            TPM_ALG_ECDH => Ok(EccScheme::EcDh(read_hash_alg(reader)?)),
+            TPM_ALG_ECDAA => {
+                let algo = read_hash_alg(&mut reader)?;
+                Ok(EccScheme::EcDaa(algo, reader.read_u16::<BigEndian>()?))
+            }
+            TPM_ALG_ECSCHNORR => Ok(EccScheme::EcSchnorr(read_hash_alg(reader)?)),
+            TPM_ALG_SM2 => Ok(EccScheme::Sm2(read_hash_alg(reader)?)),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (scheme, hash_alg) = match self {
+            EccScheme::Null => (TPM_ALG_NULL, None),
+            EccScheme::EcDsa(algo) => (TPM_ALG_ECDSA, Some(algo)),
+            EccScheme::EcDh(algo) => (TPM_ALG_ECDH, Some(algo)),
+            EccScheme::EcDaa(algo, _) => (TPM_ALG_ECDAA, Some(algo)),
+            EccScheme::EcSchnorr(algo) => (TPM_ALG_ECSCHNORR, Some(algo)),
+            EccScheme::Sm2(algo) => (TPM_ALG_SM2, Some(algo)),
+        };
+        writer.write_u16::<BigEndian>(scheme)?;
+        if let Some(algo) = hash_alg {
+            writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+        }
+        if let EccScheme::EcDaa(_, count) = self {
+            writer.write_u16::<BigEndian>(*count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A TPMT_KDF_SCHEME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KdfScheme {
+    Null,
+    Mgf1(DigestAlgorithm),
+    Kdf1Sp800_56a(DigestAlgorithm),
+    Kdf2(DigestAlgorithm),
+    Kdf1Sp800_108(DigestAlgorithm),
+}
+
+impl KdfScheme {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<KdfScheme, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(KdfScheme::Null),
+            TPM_ALG_MGF1 => Ok(KdfScheme::Mgf1(read_hash_alg(reader)?)),
+            TPM_ALG_KDF1_SP800_56A => Ok(KdfScheme::Kdf1Sp800_56a(read_hash_alg(reader)?)),
+            TPM_ALG_KDF2 => Ok(KdfScheme::Kdf2(read_hash_alg(reader)?)),
+            TPM_ALG_KDF1_SP800_108 => Ok(KdfScheme::Kdf1Sp800_108(read_hash_alg(reader)?)),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (scheme, hash_alg) = match self {
+            KdfScheme::Null => (TPM_ALG_NULL, None),
+            KdfScheme::Mgf1(algo) => (TPM_ALG_MGF1, Some(algo)),
+            KdfScheme::Kdf1Sp800_56a(algo) => (TPM_ALG_KDF1_SP800_56A, Some(algo)),
+            KdfScheme::Kdf2(algo) => (TPM_ALG_KDF2, Some(algo)),
+            KdfScheme::Kdf1Sp800_108(algo) => (TPM_ALG_KDF1_SP800_108, Some(algo)),
+        };
+        writer.write_u16::<BigEndian>(scheme)?;
+        if let Some(algo) = hash_alg {
+            writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A TPMT_KEYEDHASH_SCHEME
+///
+/// The XOR scheme is not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyedHashScheme {
+    Null,
+    Hmac(DigestAlgorithm),
+}
+
+impl KeyedHashScheme {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<KeyedHashScheme, Error> {
+        match reader.read_u16::<BigEndian>()? {
+            TPM_ALG_NULL => Ok(KeyedHashScheme::Null),
+            TPM_ALG_HMAC => Ok(KeyedHashScheme::Hmac(read_hash_alg(reader)?)),
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        match self {
+            KeyedHashScheme::Null => writer.write_u16::<BigEndian>(TPM_ALG_NULL)?,
+            KeyedHashScheme::Hmac(algo) => {
+                writer.write_u16::<BigEndian>(TPM_ALG_HMAC)?;
+                writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The type-specific parts of a TPMT_PUBLIC: its TPMU_PUBLIC_PARMS and TPMU_PUBLIC_ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PublicParameters {
+    Rsa {
+        symmetric: SymmetricDefinition,
+        scheme: RsaScheme,
+        key_bits: u16,
+        /// The public exponent, where 0 means the default exponent 65537
+        exponent: u32,
+        modulus: Vec<u8>,
+    },
+    Ecc {
+        symmetric: SymmetricDefinition,
+        scheme: EccScheme,
+        curve: EccCurve,
+        kdf: KdfScheme,
+        x: Vec<u8>,
+        y: Vec<u8>,
+    },
+    KeyedHash {
+        scheme: KeyedHashScheme,
+        unique: Vec<u8>,
+    },
+    SymCipher {
+        symmetric: SymmetricDefinition,
+        unique: Vec<u8>,
+    },
+}
+
+impl PublicParameters {
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            PublicParameters::Rsa { .. } => ObjectType::Rsa,
+            PublicParameters::Ecc { .. } => ObjectType::Ecc,
+            PublicParameters::KeyedHash { .. } => ObjectType::KeyedHash,
+            PublicParameters::SymCipher { .. } => ObjectType::SymCipher,
+        }
+    }
+
+    /// The symmetric algorithm used to protect children, for storage keys
+    pub fn symmetric(&self) -> SymmetricDefinition {
+        match self {
+            PublicParameters::Rsa { symmetric, .. }
+            | PublicParameters::Ecc { symmetric, .. }
+            | PublicParameters::SymCipher { symmetric, .. } => *symmetric,
+            PublicParameters::KeyedHash { .. } => SymmetricDefinition::Null,
+        }
+    }
+}
+
+/// A TPMT_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmtPublic {
+    pub name_alg: DigestAlgorithm,
+    pub object_attributes: ObjectAttributes,
+    pub auth_policy: Vec<u8>,
+    pub parameters: PublicParameters,
+}
+
+impl TpmtPublic {
+    pub fn object_type(&self) -> ObjectType {
+        self.parameters.object_type()
+    }
+
+    /// Builds the public area for an RSA or EC key, without symmetric algorithm or scheme
+    #[cfg(feature = "openssl")]
+    pub fn from_public_key<T: HasPublic>(
+        key: &PKeyRef<T>,
+        name_alg: DigestAlgorithm,
+        object_attributes: ObjectAttributes,
+    ) -> Result<TpmtPublic, Error> {
+        let parameters = if key.id() == pkey_id::RSA {
+            let rsa = key.rsa()?;
+            let exponent = rsa.e().to_vec();
+            if exponent.len() > 4 {
+                return Err(Error::UnsupportedAlgo);
+            }
+            let exponent = exponent
+                .iter()
+                .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+
+            PublicParameters::Rsa {
+                symmetric: SymmetricDefinition::Null,
+                scheme: RsaScheme::Null,
+                key_bits: rsa.n().num_bits() as u16,
+                exponent: if exponent == 65537 { 0 } else { exponent },
+                modulus: rsa.n().to_vec(),
+            }
+        } else if key.id() == pkey_id::EC {
+            let (curve, x, y) = ec_public_coordinates(key)?;
+
+            PublicParameters::Ecc {
+                symmetric: SymmetricDefinition::Null,
+                scheme: EccScheme::Null,
+                curve,
+                kdf: KdfScheme::Null,
+                x,
+                y,
+            }
+        } else {
+            return Err(Error::UnsupportedAlgo);
+        };
+
+        Ok(TpmtPublic {
+            name_alg,
+            object_attributes,
+            auth_policy: Vec::new(),
+            parameters,
+        })
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<TpmtPublic, Error> {
+        let object_type = ObjectType::from_tpm_alg_id(reader.read_u16::<BigEndian>()?)
+            .ok_or(Error::UnsupportedAlgo)?;
+        let name_alg = read_hash_alg(&mut reader)?;
+        let object_attributes = ObjectAttributes(reader.read_u32::<BigEndian>()?);
+        let auth_policy = reader.read_tpm2b()?;
+
+        let parameters = match object_type {
+            ObjectType::Rsa => {
+                let symmetric = SymmetricDefinition::from_reader(&mut reader)?;
+                let scheme = RsaScheme::from_reader(&mut reader)?;
+                let key_bits = reader.read_u16::<BigEndian>()?;
+                let exponent = reader.read_u32::<BigEndian>()?;
+                let modulus = reader.read_tpm2b()?;
+                PublicParameters::Rsa {
+                    symmetric,
+                    scheme,
+                    key_bits,
+                    exponent,
+                    modulus,
+                }
+            }
+            ObjectType::Ecc => {
+                let symmetric = SymmetricDefinition::from_reader(&mut reader)?;
+                let scheme = EccScheme::from_reader(&mut reader)?;
+                let curve = EccCurve::from_tpm_ecc_curve(reader.read_u16::<BigEndian>()?)
+                    .ok_or(Error::UnsupportedAlgo)?;
+                let kdf = KdfScheme::from_reader(&mut reader)?;
+                let x = reader.read_tpm2b()?;
+                let y = reader.read_tpm2b()?;
+                PublicParameters::Ecc {
+                    symmetric,
+                    scheme,
+                    curve,
+                    kdf,
+                    x,
+                    y,
+                }
+            }
+            ObjectType::KeyedHash => {
+                let scheme = KeyedHashScheme::from_reader(&mut reader)?;
+                let unique = reader.read_tpm2b()?;
+                PublicParameters::KeyedHash { scheme, unique }
+            }
+            ObjectType::SymCipher => {
+                let symmetric = SymmetricDefinition::from_reader(&mut reader)?;
+                let unique = reader.read_tpm2b()?;
+                PublicParameters::SymCipher { symmetric, unique }
+            }
+        };
+
+        Ok(TpmtPublic {
+            name_alg,
+            object_attributes,
+            auth_policy,
+            parameters,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u16::<BigEndian>(self.object_type().to_tpm_alg_id())?;
+        writer.write_u16::<BigEndian>(self.name_alg.to_tpm_alg_id())?;
+        writer.write_u32::<BigEndian>(self.object_attributes.0)?;
+        Tpm2b::new(&self.auth_policy[..])?.to_writer(&mut writer)?;
+
+        match &self.parameters {
+            PublicParameters::Rsa {
+                symmetric,
+                scheme,
+                key_bits,
+                exponent,
+                modulus,
+            } => {
+                symmetric.to_writer(&mut writer)?;
+                scheme.to_writer(&mut writer)?;
+                writer.write_u16::<BigEndian>(*key_bits)?;
+                writer.write_u32::<BigEndian>(*exponent)?;
+                Tpm2b::new(&modulus[..])?.to_writer(&mut writer)?;
+            }
+            PublicParameters::Ecc {
+                symmetric,
+                scheme,
+                curve,
+                kdf,
+                x,
+                y,
+            } => {
+                symmetric.to_writer(&mut writer)?;
+                scheme.to_writer(&mut writer)?;
+                writer.write_u16::<BigEndian>(curve.to_tpm_ecc_curve())?;
+                kdf.to_writer(&mut writer)?;
+                Tpm2b::new(&x[..])?.to_writer(&mut writer)?;
+                Tpm2b::new(&y[..])?.to_writer(&mut writer)?;
+            }
+            PublicParameters::KeyedHash { scheme, unique } => {
+                scheme.to_writer(&mut writer)?;
+                Tpm2b::new(&unique[..])?.to_writer(&mut writer)?;
+            }
+            PublicParameters::SymCipher { symmetric, unique } => {
+                symmetric.to_writer(&mut writer)?;
+                Tpm2b::new(&unique[..])?.to_writer(&mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Computes the Name of the object: nameAlg || H_nameAlg(TPMT_PUBLIC)
+    pub fn name(&self) -> Result<Vec<u8>, Error> {
+        let mut name = self.name_alg.to_tpm_alg_id().to_be_bytes().to_vec();
+        name.extend_from_slice(&provider().hash(self.name_alg, &[&self.to_vec()?])?);
+        Ok(name)
+    }
+}
+
+/// A TPM2B_PUBLIC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tpm2bPublic(TpmtPublic);
 
 impl Tpm2bPublic {
+    pub fn new(public: TpmtPublic) -> Self {
+        Tpm2bPublic(public)
+    }
+
+    pub fn public(&self) -> &TpmtPublic {
+        &self.0
+    }
+
+    pub fn into_public(self) -> TpmtPublic {
+        self.0
+    }
+
     pub fn from_reader<R: Read>(mut reader: R) -> Result<Tpm2bPublic, Error> {
-        let tpmt_public = Tpm2b::from_reader(reader)?.0;
+        let tpmt_public = reader.read_tpm2b()?;
+        let mut tpmt_public = &tpmt_public[..];
+
+        let public = TpmtPublic::from_reader(&mut tpmt_public)?;
+        if !tpmt_public.is_empty() {
+            return Err(Error::InvalidSize);
+        }
+        Ok(Tpm2bPublic(public))
+    }
 
-        todo!();
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<(), Error> {
+        Tpm2b::new(self.0.to_vec()?)?.to_writer(writer)
     }
 }
 
+#[cfg(feature = "openssl")]
+impl TryFrom<&TpmtPublic> for PKey<Public> {
+    type Error = Error;
+
+    fn try_from(tpmpub: &TpmtPublic) -> Result<PKey<Public>, Error> {
+        match &tpmpub.parameters {
+            PublicParameters::Rsa {
+                exponent, modulus, ..
+            } => {
+                let exponent = if *exponent == 0 { 65537 } else { *exponent };
+                let rsa = Rsa::from_public_components(
+                    BigNum::from_slice(modulus)?,
+                    BigNum::from_u32(exponent)?,
+                )?;
+                Ok(PKey::from_rsa(rsa)?)
+            }
+            PublicParameters::Ecc { curve, x, y, .. } => {
+                let group = EcGroup::from_curve_name(curve.openssl_nid()?)?;
+                let key = EcKey::from_public_key_affine_coordinates(
+                    &group,
+                    &*BigNum::from_slice(x)?,
+                    &*BigNum::from_slice(y)?,
+                )?;
+                key.check_key()?;
+                Ok(PKey::from_ec_key(key)?)
+            }
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
 impl TryFrom<Tpm2bPublic> for PKey<Public> {
     type Error = Error;
 
     fn try_from(tpmpub: Tpm2bPublic) -> Result<PKey<Public>, Error> {
-        todo!();
+        PKey::try_from(&tpmpub.0)
+    }
+}
+
+/// Returns the affine coordinates of an EC public key, padded to the curve size
+#[cfg(feature = "openssl")]
+pub(crate) fn ec_public_coordinates<T: HasPublic>(
+    key: &PKeyRef<T>,
+) -> Result<(EccCurve, Vec<u8>, Vec<u8>), Error> {
+    if key.id() != pkey_id::EC {
+        return Err(Error::UnsupportedAlgo);
+    }
+    let key = key.ec_key()?;
+    let curve = key
+        .group()
+        .curve_name()
+        .and_then(EccCurve::from_openssl_nid)
+        .ok_or(Error::UnsupportedAlgo)?;
+
+    let mut ctx = BigNumContext::new()?;
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    key.public_key()
+        .affine_coordinates(key.group(), &mut x, &mut y, &mut ctx)?;
+
+    let size = curve.key_size() as i32;
+    Ok((curve, x.to_vec_padded(size)?, y.to_vec_padded(size)?))
+}
+
+/// A TPMT_SENSITIVE
+///
+/// The sensitive field contains the TPMU_SENSITIVE_COMPOSITE: the first RSA prime,
+/// the ECC private scalar, the keyed hash data or the symmetric key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TpmtSensitive {
+    pub sensitive_type: ObjectType,
+    pub auth_value: Vec<u8>,
+    pub seed_value: Vec<u8>,
+    pub sensitive: Vec<u8>,
+}
+
+impl core::fmt::Debug for TpmtSensitive {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TpmtSensitive")
+            .field("sensitive_type", &self.sensitive_type)
+            .finish()
+    }
+}
+
+impl TpmtSensitive {
+    /// Builds the sensitive area for an RSA or EC private key
+    ///
+    /// For RSA keys, only the first prime is stored, as the TPM recomputes the rest.
+    #[cfg(feature = "openssl")]
+    pub fn from_private_key<T: HasPrivate>(
+        key: &PKeyRef<T>,
+        auth_value: &[u8],
+        seed_value: &[u8],
+    ) -> Result<TpmtSensitive, Error> {
+        let (sensitive_type, sensitive) = if key.id() == pkey_id::RSA {
+            let rsa = key.rsa()?;
+            let prime = rsa.p().ok_or(Error::UnsupportedAlgo)?.to_vec();
+            (ObjectType::Rsa, prime)
+        } else if key.id() == pkey_id::EC {
+            let ec = key.ec_key()?;
+            let curve = ec
+                .group()
+                .curve_name()
+                .and_then(EccCurve::from_openssl_nid)
+                .ok_or(Error::UnsupportedAlgo)?;
+            let scalar = ec.private_key().to_vec_padded(curve.key_size() as i32)?;
+            (ObjectType::Ecc, scalar)
+        } else {
+            return Err(Error::UnsupportedAlgo);
+        };
+
+        Ok(TpmtSensitive {
+            sensitive_type,
+            auth_value: auth_value.to_vec(),
+            seed_value: seed_value.to_vec(),
+            sensitive,
+        })
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<TpmtSensitive, Error> {
+        let sensitive_type = ObjectType::from_tpm_alg_id(reader.read_u16::<BigEndian>()?)
+            .ok_or(Error::UnsupportedAlgo)?;
+        let auth_value = reader.read_tpm2b()?;
+        let seed_value = reader.read_tpm2b()?;
+        let sensitive = reader.read_tpm2b()?;
+
+        Ok(TpmtSensitive {
+            sensitive_type,
+            auth_value,
+            seed_value,
+            sensitive,
+        })
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u16::<BigEndian>(self.sensitive_type.to_tpm_alg_id())?;
+        Tpm2b::new(&self.auth_value[..])?.to_writer(&mut writer)?;
+        Tpm2b::new(&self.seed_value[..])?.to_writer(&mut writer)?;
+        Tpm2b::new(&self.sensitive[..])?.to_writer(&mut writer)?;
+
+        Ok(())
+    }
+
+    /// Rebuilds the private key from the sensitive area and its public area
+    #[cfg(feature = "openssl")]
+    pub fn to_private_key(&self, public: &TpmtPublic) -> Result<PKey<Private>, Error> {
+        if self.sensitive_type != public.object_type() {
+            return Err(Error::InvalidObject);
+        }
+
+        match &public.parameters {
+            PublicParameters::Rsa {
+                exponent, modulus, ..
+            } => {
+                let mut ctx = BigNumContext::new()?;
+                let one = BigNum::from_u32(1)?;
+                let exponent = if *exponent == 0 { 65537 } else { *exponent };
+
+                let n = BigNum::from_slice(modulus)?;
+                let e = BigNum::from_u32(exponent)?;
+                let p = BigNum::from_slice(&self.sensitive)?;
+
+                let mut q = BigNum::new()?;
+                let mut rem = BigNum::new()?;
+                q.checked_div(&n, &p, &mut ctx)?;
+                rem.checked_rem(&n, &p, &mut ctx)?;
+                if rem.num_bits() != 0 || q.num_bits() == 0 {
+                    return Err(Error::InvalidObject);
+                }
+
+                let mut p1 = BigNum::new()?;
+                let mut q1 = BigNum::new()?;
+                p1.checked_sub(&p, &one)?;
+                q1.checked_sub(&q, &one)?;
+                let mut phi = BigNum::new()?;
+                phi.checked_mul(&p1, &q1, &mut ctx)?;
+
+                let mut d = BigNum::new()?;
+                d.mod_inverse(&e, &phi, &mut ctx)?;
+                let mut dmp1 = BigNum::new()?;
+                let mut dmq1 = BigNum::new()?;
+                let mut iqmp = BigNum::new()?;
+                dmp1.nnmod(&d, &p1, &mut ctx)?;
+                dmq1.nnmod(&d, &q1, &mut ctx)?;
+                iqmp.mod_inverse(&q, &p, &mut ctx)?;
+
+                let rsa = Rsa::from_private_components(n, e, d, p, q, dmp1, dmq1, iqmp)?;
+                if !rsa.check_key()? {
+                    return Err(Error::InvalidObject);
+                }
+                Ok(PKey::from_rsa(rsa)?)
+            }
+            PublicParameters::Ecc { curve, x, y, .. } => {
+                let group = EcGroup::from_curve_name(curve.openssl_nid()?)?;
+                let public_key = EcKey::from_public_key_affine_coordinates(
+                    &group,
+                    &*BigNum::from_slice(x)?,
+                    &*BigNum::from_slice(y)?,
+                )?;
+                let key = EcKey::from_private_components(
+                    &group,
+                    &*BigNum::from_slice(&self.sensitive)?,
+                    public_key.public_key(),
+                )?;
+                key.check_key().map_err(|_| Error::InvalidObject)?;
+                Ok(PKey::from_ec_key(key)?)
+            }
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    }
+
+    /// Marshals the structure as a TPM2B_SENSITIVE
+    pub(crate) fn to_tpm2b_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.to_writer(&mut buf)?;
+
+        let mut out = Vec::with_capacity(buf.len() + 2);
+        Tpm2b::new(buf)?.to_writer(&mut out)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tpm2b_ref() {
+        let encoded = hex::decode("000301020300000001ff00").unwrap();
+        let mut buf = &encoded[..];
+
+        let first = Tpm2bRef::from_bytes(&mut buf).unwrap();
+        assert_eq!(first.contents(), &[1, 2, 3]);
+        assert_eq!(first.contents().as_ptr(), encoded[2..].as_ptr());
+        assert_eq!(
+            Tpm2bRef::from_bytes(&mut buf).unwrap().contents(),
+            &[] as &[u8]
+        );
+
+        let owned = Tpm2b::from_bytes(&mut buf).unwrap();
+        assert_eq!(owned.contents(), &[0xff]);
+        assert_eq!(buf, &[0]);
+
+        match Tpm2bRef::from_bytes(&mut buf) {
+            Err(Error::UnexpectedEnd) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        let mut truncated = &encoded[..4];
+        match Tpm2b::from_bytes(&mut truncated) {
+            Err(Error::UnexpectedEnd) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(truncated.len(), 4);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let public = TpmtPublic {
+            name_alg: DigestAlgorithm::Sha256,
+            object_attributes: ObjectAttributes::from_bits(0x0003_0072),
+            auth_policy: vec![0x11; 32],
+            parameters: PublicParameters::Rsa {
+                symmetric: SymmetricDefinition::Aes { key_bits: 128 },
+                scheme: RsaScheme::Null,
+                key_bits: 2048,
+                exponent: 0,
+                modulus: vec![0xaa; 256],
+            },
+        };
+        let mut encoded = Vec::new();
+        public.to_writer(&mut encoded).unwrap();
+        assert_eq!(TpmtPublic::from_reader(&encoded[..]).unwrap(), public);
+
+        for len in 0..encoded.len() {
+            match TpmtPublic::from_reader(&encoded[..len]) {
+                Err(Error::UnexpectedEnd) => {}
+                res => panic!("Unexpected result for {} bytes: {:?}", len, res),
+            }
+            #[cfg(feature = "std")]
+            match TpmtPublic::from_reader(crate::StdReader(&encoded[..len])) {
+                Err(Error::UnexpectedEnd) => {}
+                res => panic!("Unexpected result for {} bytes: {:?}", len, res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_object_attributes() {
+        // The attributes tpm2_createak uses for an AK
+        let ak = ObjectAttributes::from_bits(0x0005_0072);
+        assert!(ak.contains(ObjectAttributes::FIXED_TPM | ObjectAttributes::USER_WITH_AUTH));
+        assert!(ak.is_restricted_signing());
+        assert!(!ak.is_restricted_decryption());
+        ak.validate_attestation_key().unwrap();
+
+        let mut movable = ak;
+        movable.set(ObjectAttributes::FIXED_TPM, false);
+        movable.validate().unwrap();
+        match movable.validate_attestation_key() {
+            Err(Error::InvalidObjectAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let storage = ObjectAttributes::from_bits(0x0003_0072);
+        assert!(storage.is_restricted_decryption());
+        match storage.validate_attestation_key() {
+            Err(Error::InvalidObjectAttributes) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        for invalid in &[0x0007_0072, 0x0005_0062, 0x0005_0073, 0x0000_0852] {
+            match ObjectAttributes::from_bits(*invalid).validate() {
+                Err(Error::InvalidObjectAttributes) => {}
+                res => panic!("Unexpected result for {:x}: {:?}", invalid, res),
+            }
+        }
     }
 }
@@ -0,0 +1,214 @@
+use alloc::boxed::Box;
+use core::convert::TryFrom;
+
+use openssl::{
+    bn::BigNum,
+    derive::Deriver,
+    ec::EcKey,
+    ecdsa::EcdsaSig,
+    encrypt::{Decrypter, Encrypter},
+    hash::Hasher,
+    pkey::{PKey, Public},
+    rand::rand_bytes,
+    rsa::Padding,
+    sign::{RsaPssSaltlen, Signer, Verifier},
+    symm::{decrypt, encrypt, Cipher},
+};
+
+use crate::{
+    attestation::Signature,
+    objects::{ec_public_coordinates, ObjectType, TpmtPublic, TpmtSensitive},
+    provider::{CryptoProvider, DigestContext, EcdhOutput},
+    DigestAlgorithm, Error,
+};
+
+/// A CryptoProvider using OpenSSL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpensslProvider;
+
+fn aes_cfb_cipher(key: &[u8]) -> Result<Cipher, Error> {
+    match key.len() {
+        16 => Ok(Cipher::aes_128_cfb128()),
+        24 => Ok(Cipher::aes_192_cfb128()),
+        32 => Ok(Cipher::aes_256_cfb128()),
+        _ => Err(Error::InvalidSize),
+    }
+}
+
+impl DigestContext for Hasher {
+    fn update(&mut self, data: &[u8]) -> Result<(), Error> {
+        Ok(Hasher::update(self, data)?)
+    }
+
+    fn finish_reset(&mut self, out: &mut [u8]) -> Result<(), Error> {
+        // Hasher::finish resets the hasher
+        let digest = self.finish()?;
+        if out.len() != digest.len() {
+            return Err(Error::InvalidSize);
+        }
+        out.copy_from_slice(&digest);
+        Ok(())
+    }
+}
+
+impl CryptoProvider for OpensslProvider {
+    fn hash(&self, algo: DigestAlgorithm, parts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+        let mut hasher = Hasher::new(algo.openssl_md()?)?;
+        for part in parts {
+            hasher.update(part)?;
+        }
+        Ok(hasher.finish()?.to_vec())
+    }
+
+    fn digest_context(&self, algo: DigestAlgorithm) -> Result<Box<dyn DigestContext>, Error> {
+        Ok(Box::new(Hasher::new(algo.openssl_md()?)?))
+    }
+
+    fn hmac(&self, algo: DigestAlgorithm, key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(algo.openssl_md()?, &key)?;
+        for part in parts {
+            signer.update(part)?;
+        }
+        Ok(signer.sign_to_vec()?)
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) -> Result<(), Error> {
+        Ok(rand_bytes(buf)?)
+    }
+
+    fn rsa_oaep_encrypt(
+        &self,
+        key: &TpmtPublic,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if key.object_type() != ObjectType::Rsa {
+            return Err(Error::UnsupportedAlgo);
+        }
+        let key = PKey::<Public>::try_from(key)?;
+        let md = algo.openssl_md()?;
+
+        let mut encrypter = Encrypter::new(&key)?;
+        encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+        encrypter.set_rsa_oaep_md(md)?;
+        encrypter.set_rsa_mgf1_md(md)?;
+        encrypter.set_rsa_oaep_label(label)?;
+
+        let mut ciphertext = vec![0; encrypter.encrypt_len(plaintext)?];
+        let len = encrypter.encrypt(plaintext, &mut ciphertext)?;
+        ciphertext.truncate(len);
+        Ok(ciphertext)
+    }
+
+    fn rsa_oaep_decrypt(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if key.object_type() != ObjectType::Rsa {
+            return Err(Error::UnsupportedAlgo);
+        }
+        let key = private.to_private_key(key)?;
+        let md = algo.openssl_md()?;
+
+        let mut decrypter = Decrypter::new(&key)?;
+        decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+        decrypter.set_rsa_oaep_md(md)?;
+        decrypter.set_rsa_mgf1_md(md)?;
+        decrypter.set_rsa_oaep_label(label)?;
+
+        let mut plaintext = vec![0; decrypter.decrypt_len(ciphertext)?];
+        let len = decrypter.decrypt(ciphertext, &mut plaintext)?;
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+
+    fn ecdh_ephemeral(&self, key: &TpmtPublic) -> Result<EcdhOutput, Error> {
+        if key.object_type() != ObjectType::Ecc {
+            return Err(Error::UnsupportedAlgo);
+        }
+        let key = PKey::<Public>::try_from(key)?;
+
+        let ephemeral = PKey::from_ec_key(EcKey::generate(key.ec_key()?.group())?)?;
+        let (_, ephemeral_x, ephemeral_y) = ec_public_coordinates(&ephemeral)?;
+
+        let mut deriver = Deriver::new(&ephemeral)?;
+        deriver.set_peer(&key)?;
+        let z = deriver.derive_to_vec()?;
+
+        Ok((z, ephemeral_x, ephemeral_y))
+    }
+
+    fn ecdh(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        peer_x: &[u8],
+        peer_y: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if key.object_type() != ObjectType::Ecc {
+            return Err(Error::UnsupportedAlgo);
+        }
+        let key = private.to_private_key(key)?;
+
+        let peer = EcKey::from_public_key_affine_coordinates(
+            key.ec_key()?.group(),
+            &*BigNum::from_slice(peer_x)?,
+            &*BigNum::from_slice(peer_y)?,
+        )?;
+        peer.check_key()?;
+        let peer = PKey::from_ec_key(peer)?;
+
+        let mut deriver = Deriver::new(&key)?;
+        deriver.set_peer(&peer)?;
+        Ok(deriver.derive_to_vec()?)
+    }
+
+    fn aes_cfb_encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(encrypt(aes_cfb_cipher(key)?, key, Some(iv), data)?)
+    }
+
+    fn aes_cfb_decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(decrypt(aes_cfb_cipher(key)?, key, Some(iv), data)?)
+    }
+
+    fn verify_signature(
+        &self,
+        key: &TpmtPublic,
+        signature: &Signature,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        let key = PKey::<Public>::try_from(key)?;
+        let mut verifier = Verifier::new(signature.hash_alg().openssl_md()?, &key)?;
+        let valid = match signature {
+            Signature::RsaSsa { signature, .. } => {
+                verifier.set_rsa_padding(Padding::PKCS1)?;
+                verifier.verify_oneshot(signature, message)?
+            }
+            Signature::RsaPss { signature, .. } => {
+                verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                // TPMs differ in the salt length they use, so let OpenSSL detect it
+                verifier.set_rsa_pss_saltlen(RsaPssSaltlen::custom(-2))?;
+                verifier.verify_oneshot(signature, message)?
+            }
+            Signature::EcDsa { r, s, .. } => {
+                let signature = EcdsaSig::from_private_components(
+                    BigNum::from_slice(r)?,
+                    BigNum::from_slice(s)?,
+                )?;
+                verifier.verify_oneshot(&signature.to_der()?, message)?
+            }
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{pcrs::PcrExtender, DigestAlgorithm, Error};
+
+/// The result of comparing a single PCR with its reported value
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PcrComparison {
+    Match,
+    /// The PCR was never extended and the reported value is still the initial value
+    Unextended,
+    Mismatch {
+        expected: Vec<u8>,
+        reported: Vec<u8>,
+        /// The index of the first event after which the running value diverges from
+        /// the reference, if both sides have an event history
+        diverged_at: Option<usize>,
+    },
+    /// No value was reported for this PCR
+    NotReported,
+}
+
+/// A per-bank, per-PCR comparison of expected and reported PCR values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrDiff {
+    banks: BTreeMap<DigestAlgorithm, Vec<PcrComparison>>,
+}
+
+impl PcrDiff {
+    pub fn banks(&self) -> &BTreeMap<DigestAlgorithm, Vec<PcrComparison>> {
+        &self.banks
+    }
+
+    pub fn pcr(&self, pcr_index: u32, algo: DigestAlgorithm) -> Option<&PcrComparison> {
+        self.banks.get(&algo)?.get(pcr_index as usize)
+    }
+
+    /// Whether every reported PCR has the expected value
+    pub fn is_match(&self) -> bool {
+        self.mismatches().next().is_none()
+    }
+
+    /// The bank, PCR number and comparison of every mismatching PCR
+    pub fn mismatches(&self) -> impl Iterator<Item = (DigestAlgorithm, u32, &PcrComparison)> {
+        self.banks.iter().flat_map(|(algo, bank)| {
+            bank.iter()
+                .enumerate()
+                .filter(|(_, comparison)| matches!(comparison, PcrComparison::Mismatch { .. }))
+                .map(move |(pcr, comparison)| (*algo, pcr as u32, comparison))
+        })
+    }
+}
+
+impl PcrExtender {
+    /// Compares the PCR values with reported values, like those of a quote or
+    /// imported from tpm2_pcrread
+    ///
+    /// reported holds the value of every reported PCR by bank and PCR number; PCRs
+    /// that are missing from it are not reported.
+    pub fn compare_values(
+        &self,
+        reported: &BTreeMap<DigestAlgorithm, BTreeMap<u32, Vec<u8>>>,
+    ) -> PcrDiff {
+        let banks = self
+            .values()
+            .into_iter()
+            .map(|(algo, expected)| {
+                let reported = reported.get(&algo);
+                let bank = expected
+                    .into_iter()
+                    .enumerate()
+                    .map(|(pcr, expected)| {
+                        match reported.and_then(|bank| bank.get(&(pcr as u32))) {
+                            None => PcrComparison::NotReported,
+                            Some(reported) if *reported == expected => {
+                                if self.pcr_was_extended(pcr as u32, algo).unwrap_or(true) {
+                                    PcrComparison::Match
+                                } else {
+                                    PcrComparison::Unextended
+                                }
+                            }
+                            Some(reported) => PcrComparison::Mismatch {
+                                expected,
+                                reported: reported.clone(),
+                                diverged_at: None,
+                            },
+                        }
+                    })
+                    .collect();
+                (algo, bank)
+            })
+            .collect();
+        PcrDiff { banks }
+    }
+
+    /// Compares the PCR values with those of another extender
+    ///
+    /// PCRs that the other extender did not read when its values were imported are
+    /// not reported. If both extenders keep a history, mismatches include the first event at which
+    /// this extender diverges from the other one.
+    pub fn compare(&self, reference: &PcrExtender) -> PcrDiff {
+        let mut diff = self.compare_values(&reference.known_values());
+        for (algo, bank) in diff.banks.iter_mut() {
+            for (pcr, comparison) in bank.iter_mut().enumerate() {
+                if let PcrComparison::Mismatch { diverged_at, .. } = comparison {
+                    *diverged_at = self
+                        .first_divergence(reference, pcr as u32, *algo)
+                        .ok()
+                        .flatten();
+                }
+            }
+        }
+        diff
+    }
+
+    /// Finds the index of the first event after which the running value of a PCR
+    /// differs from the running value in a known-good reference
+    ///
+    /// If one history is a prefix of the other, the index is the length of the shorter
+    /// one. Returns None if the histories are identical or either one is not kept.
+    pub fn first_divergence(
+        &self,
+        reference: &PcrExtender,
+        pcr_index: u32,
+        algo: DigestAlgorithm,
+    ) -> Result<Option<usize>, Error> {
+        let ours: Vec<_> = self.intermediate_values(pcr_index, algo)?.collect();
+        let theirs: Vec<_> = reference.intermediate_values(pcr_index, algo)?.collect();
+        if (ours.is_empty() && self.pcr_was_extended(pcr_index, algo)?)
+            || (theirs.is_empty() && reference.pcr_was_extended(pcr_index, algo)?)
+        {
+            return Ok(None);
+        }
+
+        let common = ours
+            .iter()
+            .zip(theirs.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common == ours.len() && common == theirs.len() {
+            Ok(None)
+        } else {
+            Ok(Some(common))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PcrExtenderBuilder, PcrSelection};
+
+    fn extender(events: &[&[u8]]) -> PcrExtender {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .set_keep_history(true)
+            .build();
+        for event in events {
+            extender.extend(4, event).unwrap();
+        }
+        extender
+    }
+
+    #[test]
+    fn test_compare_values() {
+        let replayed = extender(&[b"shim", b"grub"]);
+        let mut reported = replayed.known_values();
+        let diff = replayed.compare_values(&reported);
+        assert!(diff.is_match());
+        assert_eq!(
+            diff.pcr(4, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::Match)
+        );
+        assert_eq!(
+            diff.pcr(0, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::Unextended)
+        );
+
+        let bank = reported.get_mut(&DigestAlgorithm::Sha256).unwrap();
+        bank.insert(4, vec![0xAA; 32]);
+        bank.split_off(&10);
+        let diff = replayed.compare_values(&reported);
+        assert!(!diff.is_match());
+        assert_eq!(
+            diff.mismatches().collect::<Vec<_>>(),
+            vec![(
+                DigestAlgorithm::Sha256,
+                4,
+                &PcrComparison::Mismatch {
+                    expected: replayed
+                        .pcr_algo_value(4, DigestAlgorithm::Sha256)
+                        .unwrap()
+                        .to_vec(),
+                    reported: vec![0xAA; 32],
+                    diverged_at: None,
+                }
+            )]
+        );
+        assert_eq!(
+            diff.pcr(10, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::NotReported)
+        );
+    }
+
+    #[test]
+    fn test_compare_imported() {
+        let replayed = extender(&[b"shim", b"grub"]);
+        let output = format!(
+            "sha256:\n  0 : 0x{}\n  4 : 0x{}\n",
+            hex::encode([0; 32]),
+            hex::encode(replayed.pcr_algo_value(4, DigestAlgorithm::Sha256).unwrap())
+        );
+        let imported = PcrExtender::from_tpm2_pcrread_yaml(&output).unwrap();
+
+        // PCRs that tpm2_pcrread did not read are not compared with zero
+        let diff = replayed.compare(&imported);
+        assert!(diff.is_match());
+        assert_eq!(
+            diff.pcr(0, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::Unextended)
+        );
+        assert_eq!(
+            diff.pcr(4, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::Match)
+        );
+        assert_eq!(
+            diff.pcr(7, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::NotReported)
+        );
+
+        let mut selection = PcrSelection::new();
+        selection.add_bank(DigestAlgorithm::Sha256, &[4]);
+        let imported = PcrExtender::from_tpm2_pcrread_values(
+            &selection,
+            replayed.pcr_algo_value(4, DigestAlgorithm::Sha256).unwrap(),
+        )
+        .unwrap();
+        let diff = replayed.compare(&imported);
+        assert_eq!(
+            diff.pcr(0, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::NotReported)
+        );
+        assert_eq!(
+            diff.pcr(4, DigestAlgorithm::Sha256),
+            Some(&PcrComparison::Match)
+        );
+    }
+
+    #[test]
+    fn test_compare_divergence() {
+        let reference = extender(&[b"shim", b"grub", b"kernel"]);
+
+        let replayed = extender(&[b"shim", b"evil grub", b"kernel"]);
+        match replayed.compare(&reference).pcr(4, DigestAlgorithm::Sha256) {
+            Some(PcrComparison::Mismatch {
+                diverged_at: Some(1),
+                ..
+            }) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let replayed = extender(&[b"shim", b"grub"]);
+        assert_eq!(
+            replayed
+                .first_divergence(&reference, 4, DigestAlgorithm::Sha256)
+                .unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            reference
+                .first_divergence(&reference, 4, DigestAlgorithm::Sha256)
+                .unwrap(),
+            None
+        );
+        assert!(reference.compare(&reference).is_match());
+
+        let mut imported = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        imported.extend(4, b"shim").unwrap();
+        assert_eq!(
+            replayed
+                .first_divergence(&imported, 4, DigestAlgorithm::Sha256)
+                .unwrap(),
+            None
+        );
+    }
+}
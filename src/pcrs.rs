@@ -1,16 +1,151 @@
-use std::collections::BTreeMap;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 
-use openssl::hash::Hasher;
+use byteorder::BigEndian;
 
-use crate::{DigestAlgorithm, Error};
+use crate::{
+    io::{Read, ReadBytesExt, Write, WriteBytesExt},
+    provider::provider,
+    DigestAlgorithm, Error,
+};
 
 type PcrNum = u32;
 
+/// The number of PCRs of a PC client TPM
+const DEFAULT_NUM_PCRS: PcrNum = 24;
+
+/// The minimum sizeofSelect, covering the 24 PCRs of a PC client TPM
+const PCR_SELECT_MIN: usize = 3;
+
+/// The number of PCRs the largest sizeofSelect of a TPML_PCR_SELECTION can cover
+const MAX_NUM_PCRS: PcrNum = u8::MAX as PcrNum * 8;
+
+/// A TPML_PCR_SELECTION
+///
+/// The banks are kept in the order they were added, as that is the order the PCR
+/// values are hashed in. The sizeofSelect of parsed selections is kept, so that they
+/// are marshalled to the same bytes, as creationHash and cpHash are computed over them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PcrSelection {
+    banks: Vec<(DigestAlgorithm, Vec<PcrNum>)>,
+    select_sizes: Vec<usize>,
+}
+
+impl PcrSelection {
+    pub fn new() -> Self {
+        PcrSelection {
+            banks: Vec::new(),
+            select_sizes: Vec::new(),
+        }
+    }
+
+    /// Selects the PCRs in a bank, which are kept in ascending order
+    pub fn add_bank(&mut self, algo: DigestAlgorithm, pcrs: &[PcrNum]) -> &mut Self {
+        let mut pcrs = pcrs.to_vec();
+        pcrs.sort_unstable();
+        pcrs.dedup();
+        let size = pcrs
+            .last()
+            .map(|pcr| *pcr as usize / 8 + 1)
+            .unwrap_or(0)
+            .max(PCR_SELECT_MIN);
+        self.banks.push((algo, pcrs));
+        self.select_sizes.push(size);
+        self
+    }
+
+    pub fn banks(&self) -> &[(DigestAlgorithm, Vec<PcrNum>)] {
+        &self.banks
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<PcrSelection, Error> {
+        let count = reader.read_u32::<BigEndian>()?;
+        let mut selection = PcrSelection::new();
+        for _ in 0..count {
+            let algo = DigestAlgorithm::from_tpm_alg_id(reader.read_u16::<BigEndian>()?)
+                .ok_or(Error::UnsupportedAlgo)?;
+            let mut select = vec![0; reader.read_u8()? as usize];
+            reader.read_exact(&mut select)?;
+
+            let pcrs = (0..select.len() as PcrNum * 8)
+                .filter(|pcr| select[*pcr as usize / 8] & (1 << (pcr % 8)) != 0)
+                .collect();
+            selection.banks.push((algo, pcrs));
+            selection.select_sizes.push(select.len());
+        }
+        Ok(selection)
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_u32::<BigEndian>(self.banks.len() as u32)?;
+        for ((algo, pcrs), size) in self.banks.iter().zip(&self.select_sizes) {
+            if *size > u8::MAX as usize {
+                return Err(Error::InvalidPcr);
+            }
+            let mut select = vec![0; *size];
+            for pcr in pcrs {
+                select[*pcr as usize / 8] |= 1 << (pcr % 8);
+            }
+
+            writer.write_u16::<BigEndian>(algo.to_tpm_alg_id())?;
+            writer.write_u8(*size as u8)?;
+            writer.write_all(&select)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single extend of a PCR bank, as recorded when the history is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrEvent {
+    pub digest: Vec<u8>,
+    /// The PCR value after this extend
+    pub value: Vec<u8>,
+    /// Caller-supplied information about the event, like its event log entry
+    pub metadata: Option<String>,
+}
+
+/// An event to extend into all banks with PcrExtender::replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReplayEvent<'a> {
+    /// Extends the hash of the data, like PcrExtender::extend
+    Data { pcr: PcrNum, data: &'a [u8] },
+    /// Extends the digest into the bank of algo, and the hash of the data into the
+    /// other banks, as when the log only records the digest of one bank
+    KnownDigest {
+        pcr: PcrNum,
+        algo: DigestAlgorithm,
+        digest: &'a [u8],
+        data: &'a [u8],
+    },
+    /// Extends a digest of all ones, which is how IMA records violations
+    Invalidation { pcr: PcrNum },
+}
+
+impl ReplayEvent<'_> {
+    pub fn pcr(&self) -> PcrNum {
+        match self {
+            ReplayEvent::Data { pcr, .. }
+            | ReplayEvent::KnownDigest { pcr, .. }
+            | ReplayEvent::Invalidation { pcr } => *pcr,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PcrValue {
     algo: DigestAlgorithm,
     value: Vec<u8>,
     ever_extended: bool,
+    /// Whether the value is known, rather than left at zero for a PCR that was not
+    /// read when importing values
+    known: bool,
+    history: Option<Vec<PcrEvent>>,
 }
 
 #[cfg(any(feature = "serialize", test))]
@@ -24,23 +159,82 @@ impl serde::Serialize for PcrValue {
 }
 
 impl PcrValue {
-    fn extend(&mut self, digest: &[u8]) -> Result<(), Error> {
-        let mut hasher = Hasher::new(self.algo.openssl_md())?;
-        hasher.update(&self.value)?;
-        hasher.update(digest)?;
-        self.value = hasher.finish()?.to_vec();
+    fn extend(&mut self, digest: &[u8], metadata: Option<&str>) -> Result<(), Error> {
+        self.value = provider().hash(self.algo, &[&self.value, digest])?;
         self.ever_extended = true;
+
+        if let Some(history) = &mut self.history {
+            history.push(PcrEvent {
+                digest: digest.to_vec(),
+                value: self.value.clone(),
+                metadata: metadata.map(str::to_string),
+            });
+        }
         Ok(())
     }
+
+    fn from_value(algo: DigestAlgorithm, value: Vec<u8>) -> Result<PcrValue, Error> {
+        if value.len() != algo.digest_size() {
+            return Err(Error::InvalidSize);
+        }
+        Ok(PcrValue {
+            algo,
+            ever_extended: value.iter().any(|b| *b != 0),
+            value,
+            known: true,
+            history: None,
+        })
+    }
+}
+
+fn replay_bank(
+    algo: DigestAlgorithm,
+    bank: &mut [PcrValue],
+    events: &[ReplayEvent],
+) -> Result<(), Error> {
+    let mut context = provider().digest_context(algo)?;
+    let mut digest = vec![0; algo.digest_size()];
+
+    for event in events {
+        match event {
+            ReplayEvent::KnownDigest {
+                algo: digest_algo,
+                digest: known,
+                ..
+            } if *digest_algo == algo => digest.copy_from_slice(known),
+            ReplayEvent::Data { data, .. } | ReplayEvent::KnownDigest { data, .. } => {
+                context.update(data)?;
+                context.finish_reset(&mut digest)?;
+            }
+            ReplayEvent::Invalidation { .. } => digest.fill(0xff),
+        }
+
+        let pcr = &mut bank[event.pcr() as usize];
+        context.update(&pcr.value)?;
+        context.update(&digest)?;
+        context.finish_reset(&mut pcr.value)?;
+        pcr.ever_extended = true;
+
+        if let Some(history) = &mut pcr.history {
+            history.push(PcrEvent {
+                digest: digest.clone(),
+                value: pcr.value.clone(),
+                metadata: None,
+            });
+        }
+    }
+    Ok(())
 }
 
 impl DigestAlgorithm {
-    fn new_empty(&self) -> PcrValue {
-        let len = self.openssl_md().size();
+    fn new_empty(&self, keep_history: bool) -> PcrValue {
+        let len = self.digest_size();
         PcrValue {
             algo: *self,
             value: vec![0; len],
             ever_extended: false,
+            known: true,
+            history: if keep_history { Some(Vec::new()) } else { None },
         }
     }
 }
@@ -64,40 +258,155 @@ impl serde::Serialize for PcrExtender {
     }
 }
 
+#[cfg(any(feature = "serialize", test))]
+impl<'de> serde::Deserialize<'de> for PcrExtender {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error as _;
+
+        let values: BTreeMap<DigestAlgorithm, Vec<String>> =
+            serde::Deserialize::deserialize(deserializer)?;
+        let mut banks = BTreeMap::new();
+        for (algo, values) in values {
+            let bank = values
+                .iter()
+                .map(|value| {
+                    let value = hex::decode(value).map_err(D::Error::custom)?;
+                    PcrValue::from_value(algo, value).map_err(D::Error::custom)
+                })
+                .collect::<Result<_, _>>()?;
+            banks.insert(algo, bank);
+        }
+        Ok(PcrExtender { banks })
+    }
+}
+
 impl PcrExtender {
     pub fn extend_digest(
         &mut self,
         pcr_index: PcrNum,
         algo: DigestAlgorithm,
         digest: &[u8],
+    ) -> Result<(), Error> {
+        self.extend_digest_with_metadata(pcr_index, algo, digest, None)
+    }
+
+    /// Extends a digest into a bank, recording the metadata in the history of the PCR
+    pub fn extend_digest_with_metadata(
+        &mut self,
+        pcr_index: PcrNum,
+        algo: DigestAlgorithm,
+        digest: &[u8],
+        metadata: Option<&str>,
     ) -> Result<(), Error> {
         let pcr_index = pcr_index as usize;
 
-        if digest.len() != algo.openssl_md().size() {
+        if digest.len() != algo.digest_size() {
             return Err(Error::InvalidSize);
         }
 
         let bank = self.banks.get_mut(&algo).ok_or(Error::UnusedAlgo)?;
-        if pcr_index < bank.len() {
-            bank[pcr_index].extend(digest)?;
+        bank.get_mut(pcr_index)
+            .ok_or(Error::InvalidPcr)?
+            .extend(digest, metadata)
+    }
+
+    /// Extends the digests of a single event into all banks
+    ///
+    /// There must be exactly one digest for every bank, and none for algorithms without
+    /// a bank. Nothing is extended if any of the digests is invalid.
+    pub fn extend_event(
+        &mut self,
+        pcr_index: PcrNum,
+        digests: &[(DigestAlgorithm, &[u8])],
+    ) -> Result<(), Error> {
+        let pcr_index = pcr_index as usize;
+
+        for (algo, digest) in digests {
+            let bank = self.banks.get(algo).ok_or(Error::BankMismatch)?;
+            if pcr_index >= bank.len() {
+                return Err(Error::InvalidPcr);
+            }
+            if digest.len() != algo.digest_size() {
+                return Err(Error::InvalidSize);
+            }
+        }
+        let mut algos: Vec<_> = digests.iter().map(|(algo, _)| *algo).collect();
+        algos.sort_unstable();
+        algos.dedup();
+        if algos.len() != digests.len() || algos.len() != self.banks.len() {
+            return Err(Error::BankMismatch);
         }
 
+        for (algo, digest) in digests {
+            self.banks.get_mut(algo).unwrap()[pcr_index].extend(digest, None)?;
+        }
         Ok(())
     }
 
     pub fn extend(&mut self, pcr_index: PcrNum, value: &[u8]) -> Result<(), Error> {
+        self.extend_with_metadata(pcr_index, value, None)
+    }
+
+    /// Extends the hash of the data into all banks, recording the metadata in the
+    /// history of the PCR
+    pub fn extend_with_metadata(
+        &mut self,
+        pcr_index: PcrNum,
+        value: &[u8],
+        metadata: Option<&str>,
+    ) -> Result<(), Error> {
         let pcr_index = pcr_index as usize;
 
+        if self.banks.values().any(|bank| pcr_index >= bank.len()) {
+            return Err(Error::InvalidPcr);
+        }
+
         for (algo, bank) in self.banks.iter_mut() {
-            let mut hasher = Hasher::new(algo.openssl_md())?;
-            hasher.update(value)?;
-            let new_val = hasher.finish()?;
+            let new_val = provider().hash(*algo, &[value])?;
 
-            bank[pcr_index].extend(&new_val)?;
+            bank[pcr_index].extend(&new_val, metadata)?;
         }
         Ok(())
     }
 
+    /// Extends a batch of events into all banks, with the same result as extending
+    /// them one by one
+    ///
+    /// Each bank reuses a single hashing context, and with the rayon feature the
+    /// banks are replayed in parallel. Nothing is extended if any of the events is
+    /// invalid.
+    pub fn replay(&mut self, events: &[ReplayEvent]) -> Result<(), Error> {
+        for event in events {
+            let pcr_index = event.pcr() as usize;
+            if self.banks.values().any(|bank| pcr_index >= bank.len()) {
+                return Err(Error::InvalidPcr);
+            }
+            if let ReplayEvent::KnownDigest { algo, digest, .. } = event {
+                if digest.len() != algo.digest_size() {
+                    return Err(Error::InvalidSize);
+                }
+            }
+        }
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+            self.banks
+                .par_iter_mut()
+                .try_for_each(|(algo, bank)| replay_bank(*algo, bank, events))
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            self.banks
+                .iter_mut()
+                .try_for_each(|(algo, bank)| replay_bank(*algo, bank, events))
+        }
+    }
+
     pub fn pcr_algo_value(&self, pcr_index: PcrNum, algo: DigestAlgorithm) -> Result<&[u8], Error> {
         let pcr_index = pcr_index as usize;
 
@@ -108,25 +417,209 @@ impl PcrExtender {
         Ok(&bank[pcr_index].value)
     }
 
+    fn pcr_value(&self, pcr_index: PcrNum, algo: DigestAlgorithm) -> Result<&PcrValue, Error> {
+        let bank = self.banks.get(&algo).ok_or(Error::UnusedAlgo)?;
+        bank.get(pcr_index as usize).ok_or(Error::InvalidPcr)
+    }
+
+    /// Whether the PCR was extended, or imported with a non-zero value
+    pub fn pcr_was_extended(
+        &self,
+        pcr_index: PcrNum,
+        algo: DigestAlgorithm,
+    ) -> Result<bool, Error> {
+        Ok(self.pcr_value(pcr_index, algo)?.ever_extended)
+    }
+
+    /// The extends of a PCR bank in order
+    ///
+    /// This is empty unless the history was enabled with
+    /// PcrExtenderBuilder::set_keep_history.
+    pub fn events(
+        &self,
+        pcr_index: PcrNum,
+        algo: DigestAlgorithm,
+    ) -> Result<core::slice::Iter<'_, PcrEvent>, Error> {
+        let history = self.pcr_value(pcr_index, algo)?.history.as_deref();
+        Ok(history.unwrap_or(&[]).iter())
+    }
+
+    /// The running value of a PCR bank after each recorded extend
+    pub fn intermediate_values(
+        &self,
+        pcr_index: PcrNum,
+        algo: DigestAlgorithm,
+    ) -> Result<impl Iterator<Item = &[u8]>, Error> {
+        Ok(self.events(pcr_index, algo)?.map(|event| &event.value[..]))
+    }
+
+    /// Computes the digest over the selected PCR values, as in the pcrDigest of
+    /// quotes and creation data.
+    pub fn pcr_digest(
+        &self,
+        selection: &PcrSelection,
+        algo: DigestAlgorithm,
+    ) -> Result<Vec<u8>, Error> {
+        let mut values = Vec::new();
+        for (bank_algo, pcrs) in selection.banks() {
+            for pcr in pcrs {
+                values.push(self.pcr_algo_value(*pcr, *bank_algo)?);
+            }
+        }
+        provider().hash(algo, &values)
+    }
+
+    /// Imports the PCR values printed by tpm2_pcrread
+    ///
+    /// Both the output of tpm2-tools 4 and later, with 0x-prefixed values, and the
+    /// output of tpm2-tools 3 are accepted. PCRs that were not read are left at zero,
+    /// and are left out of known_values so that comparisons report them as not
+    /// reported.
+    pub fn from_tpm2_pcrread_yaml(output: &str) -> Result<PcrExtender, Error> {
+        let mut values: BTreeMap<DigestAlgorithm, BTreeMap<PcrNum, PcrValue>> = BTreeMap::new();
+        let mut bank = None;
+
+        for line in output.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.split_once(':') {
+                Some((algo, "")) => {
+                    let algo = algo.trim().parse()?;
+                    values.entry(algo).or_default();
+                    bank = Some(algo);
+                }
+                Some((pcr, value)) => {
+                    let algo = bank.ok_or(Error::InvalidPcrFormat)?;
+                    let pcr: PcrNum = pcr.trim().parse().map_err(|_| Error::InvalidPcrFormat)?;
+                    if pcr >= MAX_NUM_PCRS {
+                        return Err(Error::InvalidPcr);
+                    }
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix("0x")
+                        .or_else(|| value.strip_prefix("0X"))
+                        .unwrap_or(value);
+                    values
+                        .entry(algo)
+                        .or_default()
+                        .insert(pcr, PcrValue::from_value(algo, decode_hex(value)?)?);
+                }
+                None => return Err(Error::InvalidPcrFormat),
+            }
+        }
+
+        PcrExtender::from_sparse(values)
+    }
+
+    /// Imports the PCR values written by `tpm2_pcrread -o`, in its default values format
+    ///
+    /// The file only contains the concatenated values, so the selection that was passed
+    /// to tpm2_pcrread is needed to interpret it, and the file must hold exactly the
+    /// values of that selection. PCRs that were not read are left at zero, and are
+    /// left out of known_values.
+    pub fn from_tpm2_pcrread_values(
+        selection: &PcrSelection,
+        mut values_file: &[u8],
+    ) -> Result<PcrExtender, Error> {
+        let mut values: BTreeMap<DigestAlgorithm, BTreeMap<PcrNum, PcrValue>> = BTreeMap::new();
+        for (algo, pcrs) in selection.banks() {
+            let bank = values.entry(*algo).or_default();
+            for pcr in pcrs {
+                let mut value = vec![0; algo.digest_size()];
+                values_file.read_exact(&mut value)?;
+                bank.insert(*pcr, PcrValue::from_value(*algo, value)?);
+            }
+        }
+        if !values_file.is_empty() {
+            return Err(Error::InvalidPcrFormat);
+        }
+        PcrExtender::from_sparse(values)
+    }
+
+    fn from_sparse(
+        values: BTreeMap<DigestAlgorithm, BTreeMap<PcrNum, PcrValue>>,
+    ) -> Result<PcrExtender, Error> {
+        let mut num_pcrs = DEFAULT_NUM_PCRS;
+        for pcr in values.values().filter_map(|bank| bank.keys().last()) {
+            let needed = pcr
+                .checked_add(1)
+                .filter(|needed| *needed <= MAX_NUM_PCRS)
+                .ok_or(Error::InvalidPcr)?;
+            num_pcrs = num_pcrs.max(needed);
+        }
+
+        let banks = values
+            .into_iter()
+            .map(|(algo, mut bank)| {
+                let bank = (0..num_pcrs)
+                    .map(|pcr| {
+                        bank.remove(&pcr).unwrap_or_else(|| PcrValue {
+                            known: false,
+                            ..algo.new_empty(false)
+                        })
+                    })
+                    .collect();
+                (algo, bank)
+            })
+            .collect();
+        Ok(PcrExtender { banks })
+    }
+
     pub fn values(&self) -> BTreeMap<DigestAlgorithm, Vec<Vec<u8>>> {
         self.banks
             .iter()
             .map(|(algo, bank)| (*algo, bank.iter().map(|val| val.value.clone()).collect()))
             .collect()
     }
+
+    /// The values of the PCRs that are known, leaving out those that were not read
+    /// when the values were imported
+    pub fn known_values(&self) -> BTreeMap<DigestAlgorithm, BTreeMap<PcrNum, Vec<u8>>> {
+        self.banks
+            .iter()
+            .map(|(algo, bank)| {
+                let bank = bank
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, val)| val.known)
+                    .map(|(pcr, val)| (pcr as PcrNum, val.value.clone()))
+                    .collect();
+                (*algo, bank)
+            })
+            .collect()
+    }
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, Error> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|digit| {
+            core::str::from_utf8(digit)
+                .ok()
+                .filter(|digit| digit.len() == 2)
+                .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+                .ok_or(Error::InvalidPcrFormat)
+        })
+        .collect()
 }
 
 #[derive(Default, Debug)]
 pub struct PcrExtenderBuilder {
     num_pcrs: PcrNum,
     mds: Vec<DigestAlgorithm>,
+    keep_history: bool,
 }
 
 impl PcrExtenderBuilder {
     pub fn new() -> Self {
         PcrExtenderBuilder {
-            num_pcrs: 24,
+            num_pcrs: DEFAULT_NUM_PCRS,
             mds: Vec::new(),
+            keep_history: false,
         }
     }
 
@@ -135,6 +628,12 @@ impl PcrExtenderBuilder {
         self
     }
 
+    /// Records every extend of the PCRs, see PcrExtender::events
+    pub fn set_keep_history(&mut self, val: bool) -> &mut Self {
+        self.keep_history = val;
+        self
+    }
+
     pub fn add_digest_method(&mut self, md: DigestAlgorithm) -> &mut Self {
         self.mds.push(md);
         self
@@ -146,7 +645,7 @@ impl PcrExtenderBuilder {
             let mut bank = Vec::new();
 
             for _ in 0..self.num_pcrs {
-                bank.push(algo.new_empty());
+                bank.push(algo.new_empty(self.keep_history));
             }
 
             banks.insert(*algo, bank);
@@ -209,6 +708,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_data() {
         let mut extender = PcrExtenderBuilder::new()
             .set_num_pcrs(24)
@@ -344,6 +844,49 @@ mod tests {
         assert_eq!(values.get(&DigestAlgorithm::Sha256).unwrap()[10], [0; 32],);
     }
 
+    #[test]
+    fn test_pcr_selection() {
+        let mut selection = PcrSelection::new();
+        selection
+            .add_bank(DigestAlgorithm::Sha256, &[7, 0, 1])
+            .add_bank(DigestAlgorithm::Sha1, &[]);
+        let mut encoded = Vec::new();
+        selection.to_writer(&mut encoded).unwrap();
+        assert_eq!(
+            encoded,
+            hex::decode("00000002000b03830000000403000000").unwrap()
+        );
+        assert_eq!(PcrSelection::from_reader(&encoded[..]).unwrap(), selection);
+
+        // A sizeofSelect larger than needed is kept when marshalling again
+        let encoded = hex::decode("00000001000b0483000000").unwrap();
+        let parsed = PcrSelection::from_reader(&encoded[..]).unwrap();
+        assert_eq!(parsed.banks(), &[(DigestAlgorithm::Sha256, vec![0, 1, 7])]);
+        let mut reencoded = Vec::new();
+        parsed.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        extender.extend(1, "testing 42".as_bytes()).unwrap();
+        // sha256 of PCR 0, 1 and 7 concatenated
+        assert_eq!(
+            extender
+                .pcr_digest(&selection, DigestAlgorithm::Sha256)
+                .unwrap(),
+            hex::decode("2898be46b43a7ad0de48a6d7964217d925ac9e213562bf6da178be969b1b6942")
+                .unwrap(),
+        );
+
+        let mut selection = PcrSelection::new();
+        selection.add_bank(DigestAlgorithm::Sha256, &[24]);
+        match extender.pcr_digest(&selection, DigestAlgorithm::Sha256) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
     #[test]
     fn test_multibank_serialize() {
         let mut extender = PcrExtenderBuilder::new()
@@ -429,4 +972,466 @@ mod tests {
   ]"#,
         ));
     }
+
+    const SHA1_PCR8: &str = "3d96efe6e4a9ecb1270df4d80dedd5062b831b5a";
+    const SHA256_PCR8: &str = "44f12027ab81dfb6e096018f5a9f19645f988d45529cded3427159dc0032d921";
+
+    #[test]
+    fn test_deserialize() {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        extender.extend(8, b"testing 42").unwrap();
+
+        let serialized = serde_json::to_string(&extender).unwrap();
+        let deserialized: PcrExtender = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.values(), extender.values());
+
+        assert_eq!(
+            serde_json::from_str::<DigestAlgorithm>(r#""sha384""#).unwrap(),
+            DigestAlgorithm::Sha384
+        );
+        serde_json::from_str::<PcrExtender>(r#"{"sha1": ["deadbeef"]}"#).unwrap_err();
+        serde_json::from_str::<PcrExtender>(r#"{"md5": []}"#).unwrap_err();
+    }
+
+    #[test]
+    fn test_tpm2_pcrread_yaml() {
+        let output = format!(
+            "  sha1:\n    0 : 0x{}\n    8 : 0x{}\n  sha256:\n    8 : 0x{}\n",
+            "00".repeat(20),
+            SHA1_PCR8.to_uppercase(),
+            SHA256_PCR8.to_uppercase(),
+        );
+        let extender = PcrExtender::from_tpm2_pcrread_yaml(&output).unwrap();
+        assert_eq!(
+            extender.pcr_algo_value(8, DigestAlgorithm::Sha1).unwrap(),
+            &hex::decode(SHA1_PCR8).unwrap()[..]
+        );
+        assert_eq!(
+            extender.pcr_algo_value(8, DigestAlgorithm::Sha256).unwrap(),
+            &hex::decode(SHA256_PCR8).unwrap()[..]
+        );
+        assert_eq!(
+            extender.pcr_algo_value(0, DigestAlgorithm::Sha256).unwrap(),
+            &[0; 32]
+        );
+        assert_eq!(extender.values()[&DigestAlgorithm::Sha1].len(), 24);
+
+        // tpm2-tools 3 does not prefix the values
+        let output = format!("sha256 :\n  8 : {}\n", SHA256_PCR8);
+        let extender = PcrExtender::from_tpm2_pcrread_yaml(&output).unwrap();
+        assert_eq!(
+            extender.pcr_algo_value(8, DigestAlgorithm::Sha256).unwrap(),
+            &hex::decode(SHA256_PCR8).unwrap()[..]
+        );
+
+        match PcrExtender::from_tpm2_pcrread_yaml("  0 : 0x00\n") {
+            Err(Error::InvalidPcrFormat) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match PcrExtender::from_tpm2_pcrread_yaml("sha256:\n  0 : 0xzz\n") {
+            Err(Error::InvalidPcrFormat) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match PcrExtender::from_tpm2_pcrread_yaml("sha256:\n  0 : 0x0000\n") {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Indices beyond what a PCR selection can express are rejected before
+        // allocating the banks
+        let zero = "00".repeat(32);
+        let extender =
+            PcrExtender::from_tpm2_pcrread_yaml(&format!("sha256:\n  2039 : 0x{}\n", zero))
+                .unwrap();
+        assert_eq!(extender.values()[&DigestAlgorithm::Sha256].len(), 2040);
+        for pcr in ["2040", "4000000000", "4294967295"] {
+            let output = format!("sha256:\n  {} : 0x{}\n", pcr, zero);
+            match PcrExtender::from_tpm2_pcrread_yaml(&output) {
+                Err(Error::InvalidPcr) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tpm2_pcrread_values() {
+        let mut selection = PcrSelection::new();
+        selection
+            .add_bank(DigestAlgorithm::Sha1, &[8])
+            .add_bank(DigestAlgorithm::Sha256, &[0, 8]);
+        let mut values = hex::decode(SHA1_PCR8).unwrap();
+        values.extend_from_slice(&[0; 32]);
+        values.extend_from_slice(&hex::decode(SHA256_PCR8).unwrap());
+
+        let extender = PcrExtender::from_tpm2_pcrread_values(&selection, &values[..]).unwrap();
+        assert_eq!(
+            extender.pcr_algo_value(8, DigestAlgorithm::Sha1).unwrap(),
+            &hex::decode(SHA1_PCR8).unwrap()[..]
+        );
+        assert_eq!(
+            extender.pcr_algo_value(8, DigestAlgorithm::Sha256).unwrap(),
+            &hex::decode(SHA256_PCR8).unwrap()[..]
+        );
+
+        match PcrExtender::from_tpm2_pcrread_values(&selection, &values[..40]) {
+            Err(Error::UnexpectedEnd) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        values.push(0);
+        match PcrExtender::from_tpm2_pcrread_values(&selection, &values) {
+            Err(Error::InvalidPcrFormat) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut selection = PcrSelection::new();
+        selection.add_bank(DigestAlgorithm::Sha1, &[PcrNum::MAX]);
+        match PcrExtender::from_tpm2_pcrread_values(&selection, &values[..20]) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .set_keep_history(true)
+            .build();
+
+        extender
+            .extend_digest_with_metadata(
+                8,
+                DigestAlgorithm::Sha1,
+                &hex::decode("f1d2d2f924e986ac86fdf7b36c94bcdf32beec15").unwrap(),
+                Some("grub_cmd: linux"),
+            )
+            .unwrap();
+        extender
+            .extend_digest(
+                8,
+                DigestAlgorithm::Sha1,
+                &hex::decode("f1d2d2f924e986ac86fdf7b36c94bcdf32beec15").unwrap(),
+            )
+            .unwrap();
+        extender
+            .extend_with_metadata(0, b"testing 42", Some("test"))
+            .unwrap();
+
+        let events: Vec<_> = extender.events(8, DigestAlgorithm::Sha1).unwrap().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].digest,
+            hex::decode("f1d2d2f924e986ac86fdf7b36c94bcdf32beec15").unwrap()
+        );
+        assert_eq!(events[0].metadata.as_deref(), Some("grub_cmd: linux"));
+        assert_eq!(events[1].metadata, None);
+        assert_eq!(
+            extender
+                .intermediate_values(8, DigestAlgorithm::Sha1)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![
+                &hex::decode("3D96EFE6E4A9ECB1270DF4D80DEDD5062B831B5A").unwrap()[..],
+                &hex::decode("F804A5AC9D182856C86FF6FD33A7A07BFFB7CD27").unwrap()[..],
+            ]
+        );
+        assert_eq!(
+            extender.events(8, DigestAlgorithm::Sha256).unwrap().count(),
+            0
+        );
+        assert!(!extender
+            .pcr_was_extended(8, DigestAlgorithm::Sha256)
+            .unwrap());
+
+        let events: Vec<_> = extender
+            .events(0, DigestAlgorithm::Sha256)
+            .unwrap()
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].metadata.as_deref(), Some("test"));
+        assert_eq!(
+            events[0].value,
+            hex::decode("F11F5E30B2297E43A6AC98E9E0B0A94069B5074E0C1B021C77FC571872473BCD")
+                .unwrap()
+        );
+        assert!(extender
+            .pcr_was_extended(0, DigestAlgorithm::Sha256)
+            .unwrap());
+
+        match extender.events(24, DigestAlgorithm::Sha1) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Without the history, only the final value is kept
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        extender.extend(0, b"testing 42").unwrap();
+        assert_eq!(
+            extender.events(0, DigestAlgorithm::Sha256).unwrap().count(),
+            0
+        );
+        assert!(extender
+            .pcr_was_extended(0, DigestAlgorithm::Sha256)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .build();
+
+        match extender.extend(24, b"testing 42") {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_digest(24, DigestAlgorithm::Sha1, &[0; 20]) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_event(24, &[(DigestAlgorithm::Sha1, &[0; 20])]) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_extend_event() {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha1)
+            .add_digest_method(DigestAlgorithm::Sha256)
+            .build();
+        let sha1 = hex::decode("f1d2d2f924e986ac86fdf7b36c94bcdf32beec15").unwrap();
+        let sha256 =
+            hex::decode("b5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c")
+                .unwrap();
+
+        match extender.extend_event(0, &[(DigestAlgorithm::Sha1, &sha1)]) {
+            Err(Error::BankMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_event(
+            0,
+            &[
+                (DigestAlgorithm::Sha1, &sha1),
+                (DigestAlgorithm::Sha1, &sha1),
+            ],
+        ) {
+            Err(Error::BankMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_event(
+            0,
+            &[
+                (DigestAlgorithm::Sha1, &sha1),
+                (DigestAlgorithm::Sha256, &sha256),
+                (DigestAlgorithm::Sha384, &[0; 48]),
+            ],
+        ) {
+            Err(Error::BankMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_event(
+            0,
+            &[
+                (DigestAlgorithm::Sha1, &sha1),
+                (DigestAlgorithm::Sha384, &[0; 48]),
+            ],
+        ) {
+            Err(Error::BankMismatch) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.extend_event(
+            0,
+            &[
+                (DigestAlgorithm::Sha1, &sha1),
+                (DigestAlgorithm::Sha256, &sha1),
+            ],
+        ) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert!(!extender.pcr_was_extended(0, DigestAlgorithm::Sha1).unwrap());
+
+        extender
+            .extend_event(
+                0,
+                &[
+                    (DigestAlgorithm::Sha256, &sha256),
+                    (DigestAlgorithm::Sha1, &sha1),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            extender.pcr_algo_value(0, DigestAlgorithm::Sha1).unwrap(),
+            &hex::decode("3D96EFE6E4A9ECB1270DF4D80DEDD5062B831B5A").unwrap(),
+        );
+        assert_eq!(
+            extender.pcr_algo_value(0, DigestAlgorithm::Sha256).unwrap(),
+            &hex::decode("44F12027AB81DFB6E096018F5A9F19645F988D45529CDED3427159DC0032D921")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_sha3_sm3_banks() {
+        let mut extender = PcrExtenderBuilder::new()
+            .add_digest_method(DigestAlgorithm::Sha3_256)
+            .add_digest_method(DigestAlgorithm::Sha3_512)
+            .add_digest_method(DigestAlgorithm::Sm3_256)
+            .build();
+        extender.extend(0, b"testing 42").unwrap();
+
+        assert_eq!(
+            extender
+                .pcr_algo_value(0, DigestAlgorithm::Sha3_256)
+                .unwrap(),
+            &hex::decode("f73dba04a7305fa0595fc743bd7969c15e9a91ef71bbeb1791a58f33ff861b36")
+                .unwrap()[..],
+        );
+        assert_eq!(
+            extender
+                .pcr_algo_value(0, DigestAlgorithm::Sha3_512)
+                .unwrap(),
+            &hex::decode(
+                "e971fbb2bf88ece278fe508b8dfa924826403a1e85b7a120b19ccaba52f01676\
+                 b72079d0d6258a1135a4d91a13be8de6800443fc5b5c1a4c3bb9ca22a3e80282"
+            )
+            .unwrap()[..],
+        );
+        assert_eq!(
+            extender
+                .pcr_algo_value(0, DigestAlgorithm::Sm3_256)
+                .unwrap(),
+            &hex::decode("f29b9a277945afdeecbb9b252b61701e7b5f960f97abfdf6b740f86c28868704")
+                .unwrap()[..],
+        );
+
+        let encoded = hex::decode("00000002002703810000001203010000").unwrap();
+        let selection = PcrSelection::from_reader(&encoded[..]).unwrap();
+        assert_eq!(
+            selection.banks(),
+            &[
+                (DigestAlgorithm::Sha3_256, vec![0, 7]),
+                (DigestAlgorithm::Sm3_256, vec![0]),
+            ]
+        );
+        let mut reencoded = Vec::new();
+        selection.to_writer(&mut reencoded).unwrap();
+        assert_eq!(reencoded, encoded);
+
+        let serialized = serde_json::to_string(&extender).unwrap();
+        assert!(serialized.contains(r#""sha3_256":"#));
+        assert!(serialized.contains(r#""sm3_256":"#));
+        let deserialized: PcrExtender = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.values(), extender.values());
+        assert_eq!(
+            "SHA3_384".parse::<DigestAlgorithm>().unwrap(),
+            DigestAlgorithm::Sha3_384
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let new_extender = || {
+            PcrExtenderBuilder::new()
+                .set_keep_history(true)
+                .add_digest_method(DigestAlgorithm::Sha1)
+                .add_digest_method(DigestAlgorithm::Sha256)
+                .add_digest_method(DigestAlgorithm::Sha384)
+                .build()
+        };
+        let sha1_digest = hex::decode("f1d2d2f924e986ac86fdf7b36c94bcdf32beec15").unwrap();
+        let events = [
+            ReplayEvent::Data {
+                pcr: 10,
+                data: b"boot_aggregate",
+            },
+            ReplayEvent::KnownDigest {
+                pcr: 10,
+                algo: DigestAlgorithm::Sha1,
+                digest: &sha1_digest,
+                data: b"foo",
+            },
+            ReplayEvent::Invalidation { pcr: 10 },
+            ReplayEvent::Data {
+                pcr: 11,
+                data: b"testing 42",
+            },
+        ];
+
+        let mut sequential = new_extender();
+        sequential.extend(10, b"boot_aggregate").unwrap();
+        sequential
+            .extend_event(
+                10,
+                &[
+                    (DigestAlgorithm::Sha1, &sha1_digest),
+                    (
+                        DigestAlgorithm::Sha256,
+                        &provider().hash(DigestAlgorithm::Sha256, &[b"foo"]).unwrap(),
+                    ),
+                    (
+                        DigestAlgorithm::Sha384,
+                        &provider().hash(DigestAlgorithm::Sha384, &[b"foo"]).unwrap(),
+                    ),
+                ],
+            )
+            .unwrap();
+        sequential
+            .extend_event(
+                10,
+                &[
+                    (DigestAlgorithm::Sha1, &[0xff; 20]),
+                    (DigestAlgorithm::Sha256, &[0xff; 32]),
+                    (DigestAlgorithm::Sha384, &[0xff; 48]),
+                ],
+            )
+            .unwrap();
+        sequential.extend(11, b"testing 42").unwrap();
+
+        let mut replayed = new_extender();
+        replayed.replay(&events).unwrap();
+        assert_eq!(replayed.values(), sequential.values());
+        for algo in [
+            DigestAlgorithm::Sha1,
+            DigestAlgorithm::Sha256,
+            DigestAlgorithm::Sha384,
+        ] {
+            assert!(replayed
+                .events(10, algo)
+                .unwrap()
+                .eq(sequential.events(10, algo).unwrap()));
+        }
+        assert_eq!(
+            replayed.pcr_algo_value(11, DigestAlgorithm::Sha1).unwrap(),
+            &hex::decode("B2BC0096E981EBEF006DA20BBDD3F0BEC757BDD4").unwrap(),
+        );
+
+        // Nothing is extended if any event is invalid
+        let mut extender = new_extender();
+        match extender.replay(&[events[0], ReplayEvent::Invalidation { pcr: 24 }]) {
+            Err(Error::InvalidPcr) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        match extender.replay(&[
+            events[0],
+            ReplayEvent::KnownDigest {
+                pcr: 10,
+                algo: DigestAlgorithm::Sha256,
+                digest: &sha1_digest,
+                data: b"foo",
+            },
+        ]) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(extender.values(), new_extender().values());
+    }
 }
@@ -0,0 +1,110 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    attestation::Signature,
+    objects::{TpmtPublic, TpmtSensitive},
+    DigestAlgorithm, Error,
+};
+
+/// The x coordinate of the shared point and the x and y coordinates of the
+/// ephemeral public key of an ECDH exchange
+pub type EcdhOutput = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// A hashing context that can be reused for many messages
+pub trait DigestContext {
+    fn update(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Writes the digest to out, which must be the size of the digest, and resets
+    /// the context for the next message
+    fn finish_reset(&mut self, out: &mut [u8]) -> Result<(), Error>;
+}
+
+/// The cryptographic primitives the crate needs, so that it can run on different
+/// crypto libraries
+///
+/// Keys are passed as their TPM public area, and private keys as their public and
+/// sensitive areas, so implementations do not need to share a key type.
+///
+/// Operations that need randomness return Error::RandomFailed from providers
+/// without a random number generator.
+pub trait CryptoProvider {
+    /// Hashes the concatenation of parts
+    fn hash(&self, algo: DigestAlgorithm, parts: &[&[u8]]) -> Result<Vec<u8>, Error>;
+
+    /// Creates a hashing context, for hashing many messages without setting up a
+    /// context for each
+    fn digest_context(&self, algo: DigestAlgorithm) -> Result<Box<dyn DigestContext>, Error>;
+
+    /// Computes the HMAC over the concatenation of parts
+    fn hmac(&self, algo: DigestAlgorithm, key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>, Error>;
+
+    fn random_bytes(&self, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Encrypts with RSA-OAEP, using algo for both the OAEP hash and MGF1
+    fn rsa_oaep_encrypt(
+        &self,
+        key: &TpmtPublic,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+
+    /// Decrypts with RSA-OAEP, using algo for both the OAEP hash and MGF1
+    ///
+    /// Returns Error::InvalidObject if private is not the sensitive area of key.
+    fn rsa_oaep_decrypt(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+
+    /// Performs ECDH between a new ephemeral key and key, with all coordinates
+    /// padded to the curve size
+    fn ecdh_ephemeral(&self, key: &TpmtPublic) -> Result<EcdhOutput, Error>;
+
+    /// Performs ECDH between the private key and the peer point, returning the x
+    /// coordinate of the shared point padded to the curve size
+    ///
+    /// Returns Error::InvalidObject if private is not the sensitive area of key.
+    fn ecdh(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        peer_x: &[u8],
+        peer_y: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+
+    /// AES in CFB mode with a full-block feedback, for any AES key size
+    fn aes_cfb_encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error>;
+
+    fn aes_cfb_decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Verifies a signature over message, returning Error::InvalidSignature if it
+    /// does not match
+    fn verify_signature(
+        &self,
+        key: &TpmtPublic,
+        signature: &Signature,
+        message: &[u8],
+    ) -> Result<(), Error>;
+}
+
+/// The provider used by the crate, preferring OpenSSL if both backends are enabled
+pub(crate) fn provider() -> &'static dyn CryptoProvider {
+    #[cfg(feature = "openssl")]
+    {
+        &crate::openssl_provider::OpensslProvider
+    }
+    #[cfg(all(not(feature = "openssl"), feature = "rustcrypto"))]
+    {
+        &crate::rustcrypto_provider::RustCryptoProvider
+    }
+}
+
+/// Compares two byte strings in constant time
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -0,0 +1,222 @@
+use alloc::vec::Vec;
+
+use crate::attestation::{Attestation, ClockInfo};
+
+/// Something suspicious in an attestation compared to the previous one of the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FreshnessIssue {
+    /// The clock is lower than in the previous attestation
+    ClockWentBackwards { previous: u64, current: u64 },
+    /// The clock did not advance since the previous attestation, so this is likely a
+    /// replay of it
+    Replayed { clock: u64 },
+    /// The TPM was reset, usually because the host rebooted
+    Reset { previous: u32, current: u32 },
+    /// The TPM was restarted without a reset, usually after a suspend or hibernate
+    Restart { previous: u32, current: u32 },
+    /// The reset count went down, so the attestation is older than the previous one
+    /// or comes from a different TPM
+    ResetCountDecreased { previous: u32, current: u32 },
+    /// The restart count went down without a reset
+    RestartCountDecreased { previous: u32, current: u32 },
+    /// A higher clock may have been reported before, after an unorderly shutdown
+    Unsafe,
+    /// The TPM firmware was updated or downgraded
+    FirmwareChanged { previous: u64, current: u64 },
+}
+
+/// Tracks the clock and counters of the verified attestations of a single TPM
+#[derive(Debug, Clone, Default)]
+pub struct QuoteHistory {
+    last: Option<(ClockInfo, u64)>,
+}
+
+impl QuoteHistory {
+    pub fn new() -> Self {
+        QuoteHistory { last: None }
+    }
+
+    /// The clock info and firmware version of the last accepted attestation
+    pub fn last(&self) -> Option<(&ClockInfo, u64)> {
+        self.last
+            .as_ref()
+            .map(|(clock_info, firmware_version)| (clock_info, *firmware_version))
+    }
+
+    /// Records an attestation, returning the issues compared to the previous one
+    ///
+    /// The attestation must already have been verified. If it looks older than the
+    /// previous one, it is not used as the reference for the next attestation.
+    pub fn record(&mut self, attestation: &Attestation) -> Vec<FreshnessIssue> {
+        let current = attestation.clock_info;
+        let mut issues = Vec::new();
+
+        if !current.safe {
+            issues.push(FreshnessIssue::Unsafe);
+        }
+
+        let (previous, previous_firmware) = match &self.last {
+            Some(last) => *last,
+            None => {
+                self.last = Some((current, attestation.firmware_version));
+                return issues;
+            }
+        };
+
+        if current.clock < previous.clock {
+            issues.push(FreshnessIssue::ClockWentBackwards {
+                previous: previous.clock,
+                current: current.clock,
+            });
+        } else if current.clock == previous.clock {
+            issues.push(FreshnessIssue::Replayed {
+                clock: current.clock,
+            });
+        }
+
+        let mut older = false;
+        if current.reset_count < previous.reset_count {
+            older = true;
+            issues.push(FreshnessIssue::ResetCountDecreased {
+                previous: previous.reset_count,
+                current: current.reset_count,
+            });
+        } else if current.reset_count > previous.reset_count {
+            issues.push(FreshnessIssue::Reset {
+                previous: previous.reset_count,
+                current: current.reset_count,
+            });
+        } else {
+            older = current.clock <= previous.clock;
+            if current.restart_count < previous.restart_count {
+                older = true;
+                issues.push(FreshnessIssue::RestartCountDecreased {
+                    previous: previous.restart_count,
+                    current: current.restart_count,
+                });
+            } else if current.restart_count > previous.restart_count {
+                issues.push(FreshnessIssue::Restart {
+                    previous: previous.restart_count,
+                    current: current.restart_count,
+                });
+            }
+        }
+
+        if attestation.firmware_version != previous_firmware {
+            issues.push(FreshnessIssue::FirmwareChanged {
+                previous: previous_firmware,
+                current: attestation.firmware_version,
+            });
+        }
+
+        if !older {
+            self.last = Some((current, attestation.firmware_version));
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{attestation::Attested, pcrs::PcrSelection};
+
+    fn quote(clock: u64, reset_count: u32, restart_count: u32, safe: bool) -> Attestation {
+        Attestation {
+            qualified_signer: Vec::new(),
+            extra_data: Vec::new(),
+            clock_info: ClockInfo {
+                clock,
+                reset_count,
+                restart_count,
+                safe,
+            },
+            firmware_version: 1,
+            attested: Attested::Quote {
+                pcr_select: PcrSelection::new(),
+                pcr_digest: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_quote_history() {
+        let mut history = QuoteHistory::new();
+        assert!(history.record(&quote(1000, 3, 0, true)).is_empty());
+        assert!(history.record(&quote(2000, 3, 0, true)).is_empty());
+
+        assert_eq!(
+            history.record(&quote(3000, 3, 1, true)),
+            vec![FreshnessIssue::Restart {
+                previous: 0,
+                current: 1
+            }]
+        );
+
+        // A replay of an earlier quote is flagged and does not become the reference
+        assert_eq!(
+            history.record(&quote(2000, 3, 0, true)),
+            vec![
+                FreshnessIssue::ClockWentBackwards {
+                    previous: 3000,
+                    current: 2000
+                },
+                FreshnessIssue::RestartCountDecreased {
+                    previous: 1,
+                    current: 0
+                },
+            ]
+        );
+        assert_eq!(history.last().unwrap().0.clock, 3000);
+
+        // A reboot after an unorderly shutdown
+        assert_eq!(
+            history.record(&quote(2500, 4, 0, false)),
+            vec![
+                FreshnessIssue::Unsafe,
+                FreshnessIssue::ClockWentBackwards {
+                    previous: 3000,
+                    current: 2500
+                },
+                FreshnessIssue::Reset {
+                    previous: 3,
+                    current: 4
+                },
+            ]
+        );
+        assert_eq!(history.last().unwrap().0.clock, 2500);
+
+        assert_eq!(
+            history.record(&quote(1000, 2, 0, true)),
+            vec![
+                FreshnessIssue::ClockWentBackwards {
+                    previous: 2500,
+                    current: 1000
+                },
+                FreshnessIssue::ResetCountDecreased {
+                    previous: 4,
+                    current: 2
+                },
+            ]
+        );
+
+        // Recording the same attestation twice
+        let replayed = quote(3500, 4, 0, true);
+        assert!(history.record(&replayed).is_empty());
+        assert_eq!(
+            history.record(&replayed),
+            vec![FreshnessIssue::Replayed { clock: 3500 }]
+        );
+
+        let mut updated = quote(4000, 4, 0, true);
+        updated.firmware_version = 2;
+        assert_eq!(
+            history.record(&updated),
+            vec![FreshnessIssue::FirmwareChanged {
+                previous: 1,
+                current: 2
+            }]
+        );
+    }
+}
@@ -0,0 +1,643 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use aes::cipher::{AsyncStreamCipher, KeyIvInit};
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::sec1::FromEncodedPoint;
+#[cfg(feature = "rand")]
+use rand_core::{OsRng, RngCore};
+use rsa::{traits::PublicKeyParts, BigUint, Oaep, Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey};
+use sha2::{digest::FixedOutputReset, Digest};
+
+use crate::{
+    attestation::Signature,
+    objects::{EccCurve, ObjectType, PublicParameters, TpmtPublic, TpmtSensitive},
+    provider::{CryptoProvider, DigestContext, EcdhOutput},
+    DigestAlgorithm, Error,
+};
+
+/// A CryptoProvider using the pure Rust RustCrypto crates
+///
+/// Without the rand feature it has no random number generator, so making
+/// credentials and wrapping objects fail with Error::RandomFailed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustCryptoProvider;
+
+/// Runs $body with $digest as the RustCrypto type of the hash algorithm
+macro_rules! with_digest {
+    ($algo:expr, $digest:ident => $body:expr) => {
+        match $algo {
+            DigestAlgorithm::Sha1 => {
+                type $digest = sha1::Sha1;
+                $body
+            }
+            DigestAlgorithm::Sha256 => {
+                type $digest = sha2::Sha256;
+                $body
+            }
+            DigestAlgorithm::Sha384 => {
+                type $digest = sha2::Sha384;
+                $body
+            }
+            DigestAlgorithm::Sha512 => {
+                type $digest = sha2::Sha512;
+                $body
+            }
+            DigestAlgorithm::Sm3_256 => {
+                type $digest = sm3::Sm3;
+                $body
+            }
+            DigestAlgorithm::Sha3_256 => {
+                type $digest = sha3::Sha3_256;
+                $body
+            }
+            DigestAlgorithm::Sha3_384 => {
+                type $digest = sha3::Sha3_384;
+                $body
+            }
+            DigestAlgorithm::Sha3_512 => {
+                type $digest = sha3::Sha3_512;
+                $body
+            }
+        }
+    };
+}
+
+/// Runs $body with $curve as the RustCrypto module of the curve
+macro_rules! with_curve {
+    ($curve:expr, $module:ident => $body:expr) => {
+        match $curve {
+            EccCurve::NistP256 => {
+                use p256 as $module;
+                $body
+            }
+            EccCurve::NistP384 => {
+                use p384 as $module;
+                $body
+            }
+            EccCurve::NistP521 => {
+                use p521 as $module;
+                $body
+            }
+            _ => Err(Error::UnsupportedAlgo),
+        }
+    };
+}
+
+fn rsa_public_key(key: &TpmtPublic) -> Result<RsaPublicKey, Error> {
+    match &key.parameters {
+        PublicParameters::Rsa {
+            exponent, modulus, ..
+        } => {
+            let exponent = if *exponent == 0 { 65537 } else { *exponent };
+            RsaPublicKey::new(BigUint::from_bytes_be(modulus), BigUint::from(exponent))
+                .map_err(|_| Error::InvalidObject)
+        }
+        _ => Err(Error::UnsupportedAlgo),
+    }
+}
+
+/// Rebuilds the RSA private key from the public modulus and the first prime
+fn rsa_private_key(key: &TpmtPublic, private: &TpmtSensitive) -> Result<RsaPrivateKey, Error> {
+    let public = rsa_public_key(key)?;
+    if private.sensitive_type != ObjectType::Rsa {
+        return Err(Error::InvalidObject);
+    }
+
+    let p = BigUint::from_bytes_be(&private.sensitive);
+    if p <= BigUint::from(1u8) {
+        return Err(Error::InvalidObject);
+    }
+    let q = public.n() / &p;
+    if &(&p * &q) != public.n() {
+        return Err(Error::InvalidObject);
+    }
+    RsaPrivateKey::from_p_q(p, q, public.e().clone()).map_err(|_| Error::InvalidObject)
+}
+
+/// Left-pads a big-endian coordinate or scalar to the curve size
+fn pad_coordinate(value: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    if value.len() > size {
+        return Err(Error::InvalidObject);
+    }
+    let mut padded = vec![0; size - value.len()];
+    padded.extend_from_slice(value);
+    Ok(padded)
+}
+
+fn ecc_point(key: &TpmtPublic) -> Result<(EccCurve, Vec<u8>, Vec<u8>), Error> {
+    match &key.parameters {
+        PublicParameters::Ecc { curve, x, y, .. } => {
+            let size = curve.key_size();
+            Ok((*curve, pad_coordinate(x, size)?, pad_coordinate(y, size)?))
+        }
+        _ => Err(Error::UnsupportedAlgo),
+    }
+}
+
+fn pad_scalar(scalar: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    let scalar = &scalar[scalar.iter().take_while(|b| **b == 0).count()..];
+    if scalar.len() > size {
+        return Err(Error::InvalidSignature);
+    }
+    let mut padded = vec![0; size - scalar.len()];
+    padded.extend_from_slice(scalar);
+    Ok(padded)
+}
+
+fn aes_cfb(key: &[u8], iv: &[u8], data: &[u8], encrypt: bool) -> Result<Vec<u8>, Error> {
+    let mut buf = data.to_vec();
+    macro_rules! run {
+        ($cipher:ty) => {
+            if encrypt {
+                cfb_mode::Encryptor::<$cipher>::new_from_slices(key, iv)
+                    .map_err(|_| Error::InvalidSize)?
+                    .encrypt(&mut buf)
+            } else {
+                cfb_mode::Decryptor::<$cipher>::new_from_slices(key, iv)
+                    .map_err(|_| Error::InvalidSize)?
+                    .decrypt(&mut buf)
+            }
+        };
+    }
+    match key.len() {
+        16 => run!(aes::Aes128),
+        24 => run!(aes::Aes192),
+        32 => run!(aes::Aes256),
+        _ => return Err(Error::InvalidSize),
+    }
+    Ok(buf)
+}
+
+/// OAEP padding with algo for both the hash and MGF1
+fn oaep_padding(algo: DigestAlgorithm, label: &[u8]) -> Result<Oaep, Error> {
+    // The TPM labels are ASCII strings with a terminating NUL
+    let label = core::str::from_utf8(label).map_err(|_| Error::InvalidSize)?;
+    Ok(with_digest!(algo, D => Oaep::new_with_label::<D, _>(label)))
+}
+
+/// A DigestContext for a RustCrypto hash
+struct RustCryptoDigest<D>(D);
+
+impl<D: Digest + FixedOutputReset> DigestContext for RustCryptoDigest<D> {
+    fn update(&mut self, data: &[u8]) -> Result<(), Error> {
+        Digest::update(&mut self.0, data);
+        Ok(())
+    }
+
+    fn finish_reset(&mut self, out: &mut [u8]) -> Result<(), Error> {
+        if out.len() != <D as Digest>::output_size() {
+            return Err(Error::InvalidSize);
+        }
+        Digest::finalize_into_reset(&mut self.0, out.into());
+        Ok(())
+    }
+}
+
+impl CryptoProvider for RustCryptoProvider {
+    fn hash(&self, algo: DigestAlgorithm, parts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+        with_digest!(algo, D => {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            Ok(hasher.finalize().to_vec())
+        })
+    }
+
+    fn digest_context(&self, algo: DigestAlgorithm) -> Result<Box<dyn DigestContext>, Error> {
+        with_digest!(algo, D => Ok(Box::new(RustCryptoDigest(D::new()))))
+    }
+
+    fn hmac(&self, algo: DigestAlgorithm, key: &[u8], parts: &[&[u8]]) -> Result<Vec<u8>, Error> {
+        with_digest!(algo, D => {
+            let mut mac = <Hmac<D> as Mac>::new_from_slice(key).map_err(|_| Error::InvalidSize)?;
+            for part in parts {
+                mac.update(part);
+            }
+            Ok(mac.finalize().into_bytes().to_vec())
+        })
+    }
+
+    #[cfg(feature = "rand")]
+    fn random_bytes(&self, buf: &mut [u8]) -> Result<(), Error> {
+        OsRng.try_fill_bytes(buf).map_err(|_| Error::RandomFailed)
+    }
+
+    #[cfg(not(feature = "rand"))]
+    fn random_bytes(&self, _buf: &mut [u8]) -> Result<(), Error> {
+        Err(Error::RandomFailed)
+    }
+
+    #[cfg(feature = "rand")]
+    fn rsa_oaep_encrypt(
+        &self,
+        key: &TpmtPublic,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let key = rsa_public_key(key)?;
+        let padding = oaep_padding(algo, label)?;
+        key.encrypt(&mut OsRng, padding, plaintext)
+            .map_err(|_| Error::InvalidSize)
+    }
+
+    #[cfg(not(feature = "rand"))]
+    fn rsa_oaep_encrypt(
+        &self,
+        _key: &TpmtPublic,
+        _algo: DigestAlgorithm,
+        _label: &[u8],
+        _plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        Err(Error::RandomFailed)
+    }
+
+    fn rsa_oaep_decrypt(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        algo: DigestAlgorithm,
+        label: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let key = rsa_private_key(key, private)?;
+        let padding = oaep_padding(algo, label)?;
+        key.decrypt(padding, ciphertext)
+            .map_err(|_| Error::DecryptionFailed)
+    }
+
+    #[cfg(feature = "rand")]
+    fn ecdh_ephemeral(&self, key: &TpmtPublic) -> Result<EcdhOutput, Error> {
+        let (curve, x, y) = ecc_point(key)?;
+        with_curve!(curve, c => {
+            let point = c::EncodedPoint::from_affine_coordinates(
+                c::FieldBytes::from_slice(&x),
+                c::FieldBytes::from_slice(&y),
+                false,
+            );
+            let peer = Option::<c::PublicKey>::from(c::PublicKey::from_encoded_point(&point))
+                .ok_or(Error::InvalidObject)?;
+
+            let ephemeral = c::ecdh::EphemeralSecret::random(&mut OsRng);
+            let z = ephemeral.diffie_hellman(&peer).raw_secret_bytes().to_vec();
+            let ephemeral_point = c::EncodedPoint::from(ephemeral.public_key());
+            let ephemeral_x = ephemeral_point.x().ok_or(Error::InvalidObject)?.to_vec();
+            let ephemeral_y = ephemeral_point.y().ok_or(Error::InvalidObject)?.to_vec();
+            Ok((z, ephemeral_x, ephemeral_y))
+        })
+    }
+
+    #[cfg(not(feature = "rand"))]
+    fn ecdh_ephemeral(&self, _key: &TpmtPublic) -> Result<EcdhOutput, Error> {
+        Err(Error::RandomFailed)
+    }
+
+    fn ecdh(
+        &self,
+        key: &TpmtPublic,
+        private: &TpmtSensitive,
+        peer_x: &[u8],
+        peer_y: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let (curve, x, y) = ecc_point(key)?;
+        if private.sensitive_type != ObjectType::Ecc {
+            return Err(Error::InvalidObject);
+        }
+        let size = curve.key_size();
+        let scalar = pad_coordinate(&private.sensitive, size)?;
+        let (peer_x, peer_y) = (pad_coordinate(peer_x, size)?, pad_coordinate(peer_y, size)?);
+        with_curve!(curve, c => {
+            let secret = c::SecretKey::from_slice(&scalar).map_err(|_| Error::InvalidObject)?;
+            let own = c::EncodedPoint::from_affine_coordinates(
+                c::FieldBytes::from_slice(&x),
+                c::FieldBytes::from_slice(&y),
+                false,
+            );
+            if c::EncodedPoint::from(secret.public_key()) != own {
+                return Err(Error::InvalidObject);
+            }
+
+            let point = c::EncodedPoint::from_affine_coordinates(
+                c::FieldBytes::from_slice(&peer_x),
+                c::FieldBytes::from_slice(&peer_y),
+                false,
+            );
+            let peer = Option::<c::PublicKey>::from(c::PublicKey::from_encoded_point(&point))
+                .ok_or(Error::InvalidObject)?;
+            let shared = c::ecdh::diffie_hellman(secret.to_nonzero_scalar(), peer.as_affine());
+            Ok(shared.raw_secret_bytes().to_vec())
+        })
+    }
+
+    fn aes_cfb_encrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        aes_cfb(key, iv, data, true)
+    }
+
+    fn aes_cfb_decrypt(&self, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        aes_cfb(key, iv, data, false)
+    }
+
+    fn verify_signature(
+        &self,
+        key: &TpmtPublic,
+        signature: &Signature,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        let hash_alg = signature.hash_alg();
+        let digest = self.hash(hash_alg, &[message])?;
+
+        match signature {
+            Signature::RsaSsa { signature, .. } => {
+                let key = rsa_public_key(key)?;
+                // The DigestInfo prefix needs an OID, which the sm3 crate does not provide
+                let padding = match hash_alg {
+                    DigestAlgorithm::Sha1 => Pkcs1v15Sign::new::<sha1::Sha1>(),
+                    DigestAlgorithm::Sha256 => Pkcs1v15Sign::new::<sha2::Sha256>(),
+                    DigestAlgorithm::Sha384 => Pkcs1v15Sign::new::<sha2::Sha384>(),
+                    DigestAlgorithm::Sha512 => Pkcs1v15Sign::new::<sha2::Sha512>(),
+                    DigestAlgorithm::Sha3_256 => Pkcs1v15Sign::new::<sha3::Sha3_256>(),
+                    DigestAlgorithm::Sha3_384 => Pkcs1v15Sign::new::<sha3::Sha3_384>(),
+                    DigestAlgorithm::Sha3_512 => Pkcs1v15Sign::new::<sha3::Sha3_512>(),
+                    DigestAlgorithm::Sm3_256 => return Err(Error::UnsupportedAlgo),
+                };
+                key.verify(padding, &digest, signature)
+                    .map_err(|_| Error::InvalidSignature)
+            }
+            Signature::RsaPss { signature, .. } => {
+                let key = rsa_public_key(key)?;
+                // TPMs differ in the salt length they use: most use the digest size,
+                // but some use the maximum the key allows
+                let max_salt_len = (key.n().bits() - 1)
+                    .div_ceil(8)
+                    .checked_sub(digest.len() + 2)
+                    .ok_or(Error::InvalidSignature)?;
+                for salt_len in [digest.len(), max_salt_len] {
+                    let padding = with_digest!(hash_alg, D => Pss::new_with_salt::<D>(salt_len));
+                    if key.verify(padding, &digest, signature).is_ok() {
+                        return Ok(());
+                    }
+                }
+                Err(Error::InvalidSignature)
+            }
+            Signature::EcDsa { r, s, .. } => {
+                use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+                let (curve, x, y) = ecc_point(key)?;
+                let size = curve.key_size();
+                let (r, s) = (pad_scalar(r, size)?, pad_scalar(s, size)?);
+                // A digest shorter than the curve order is used as is, which the ecdsa
+                // crate only accepts down to half the field size
+                let digest = if digest.len() < size {
+                    pad_scalar(&digest, size)?
+                } else {
+                    digest
+                };
+                with_curve!(curve, c => {
+                    let point = c::EncodedPoint::from_affine_coordinates(
+                        c::FieldBytes::from_slice(&x),
+                        c::FieldBytes::from_slice(&y),
+                        false,
+                    );
+                    let key = c::ecdsa::VerifyingKey::from_encoded_point(&point)
+                        .map_err(|_| Error::InvalidObject)?;
+                    let signature = c::ecdsa::Signature::from_scalars(
+                        *c::FieldBytes::from_slice(&r),
+                        *c::FieldBytes::from_slice(&s),
+                    )
+                    .map_err(|_| Error::InvalidSignature)?;
+                    key.verify_prehash(&digest, &signature)
+                        .map_err(|_| Error::InvalidSignature)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let provider = RustCryptoProvider;
+        assert_eq!(
+            provider
+                .hash(DigestAlgorithm::Sha256, &[b"a", b"bc"])
+                .unwrap(),
+            hex::decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                .unwrap(),
+        );
+        assert_eq!(
+            provider.hash(DigestAlgorithm::Sm3_256, &[b"abc"]).unwrap(),
+            hex::decode("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0")
+                .unwrap(),
+        );
+        assert_eq!(
+            provider.hash(DigestAlgorithm::Sha3_256, &[b"abc"]).unwrap(),
+            hex::decode("3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            RustCryptoProvider
+                .hmac(
+                    DigestAlgorithm::Sha256,
+                    b"Jefe",
+                    &[b"what do ya want ", b"for nothing?"]
+                )
+                .unwrap(),
+            hex::decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_aes_cfb() {
+        // SP 800-38A, F.3.13 CFB128-AES128.Encrypt
+        let key = hex::decode("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+        let iv = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172aae2d8a57").unwrap();
+        let ciphertext = hex::decode("3b3fd92eb72dad20333449f8e83cfb4ac8a64537").unwrap();
+
+        assert_eq!(
+            RustCryptoProvider
+                .aes_cfb_encrypt(&key, &iv, &plaintext)
+                .unwrap(),
+            ciphertext
+        );
+        assert_eq!(
+            RustCryptoProvider
+                .aes_cfb_decrypt(&key, &iv, &ciphertext)
+                .unwrap(),
+            plaintext
+        );
+        match RustCryptoProvider.aes_cfb_encrypt(&key[..15], &iv, &plaintext) {
+            Err(Error::InvalidSize) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_signatures_match_openssl() {
+        use openssl::{
+            ec::{EcGroup, EcKey},
+            hash::MessageDigest,
+            nid::Nid,
+            pkey::PKey,
+            rsa::{Padding, Rsa},
+            sign::{RsaPssSaltlen, Signer},
+        };
+
+        use crate::{
+            attestation::tests::sign,
+            objects::{ObjectAttributes, TPM_ALG_ECDSA, TPM_ALG_RSAPSS, TPM_ALG_RSASSA},
+        };
+
+        let message = b"attested data";
+        let attributes = ObjectAttributes::from_bits(0x0005_0072);
+
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let rsa_public =
+            TpmtPublic::from_public_key(&rsa, DigestAlgorithm::Sha256, attributes).unwrap();
+        for scheme in [TPM_ALG_RSASSA, TPM_ALG_RSAPSS] {
+            let signature = sign(&rsa, scheme, message);
+            RustCryptoProvider
+                .verify_signature(&rsa_public, &signature, message)
+                .unwrap();
+            match RustCryptoProvider.verify_signature(&rsa_public, &signature, b"other data") {
+                Err(Error::InvalidSignature) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        // Some TPMs use the maximum salt length for RSA-PSS
+        let mut signer = Signer::new(MessageDigest::sha256(), &rsa).unwrap();
+        signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+        signer
+            .set_rsa_pss_saltlen(RsaPssSaltlen::MAXIMUM_LENGTH)
+            .unwrap();
+        let signature = Signature::RsaPss {
+            hash_alg: DigestAlgorithm::Sha256,
+            signature: signer.sign_oneshot_to_vec(message).unwrap(),
+        };
+        RustCryptoProvider
+            .verify_signature(&rsa_public, &signature, message)
+            .unwrap();
+
+        for nid in [Nid::X9_62_PRIME256V1, Nid::SECP384R1, Nid::SECP521R1] {
+            let group = EcGroup::from_curve_name(nid).unwrap();
+            let ec = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+            let ec_public =
+                TpmtPublic::from_public_key(&ec, DigestAlgorithm::Sha256, attributes).unwrap();
+            let signature = sign(&ec, TPM_ALG_ECDSA, message);
+            RustCryptoProvider
+                .verify_signature(&ec_public, &signature, message)
+                .unwrap();
+            match RustCryptoProvider.verify_signature(&ec_public, &signature, b"other data") {
+                Err(Error::InvalidSignature) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_key_exchange_matches_openssl() {
+        use openssl::{
+            derive::Deriver,
+            ec::{EcGroup, EcKey, EcPoint},
+            encrypt::Decrypter,
+            hash::MessageDigest,
+            nid::Nid,
+            pkey::PKey,
+            rsa::{Padding, Rsa},
+        };
+
+        use crate::{objects::ObjectAttributes, OpensslProvider};
+
+        let attributes = ObjectAttributes::from_bits(0x0003_00B2);
+
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let rsa_public =
+            TpmtPublic::from_public_key(&rsa, DigestAlgorithm::Sha256, attributes).unwrap();
+        let ciphertext = RustCryptoProvider
+            .rsa_oaep_encrypt(&rsa_public, DigestAlgorithm::Sha256, b"IDENTITY\0", b"seed")
+            .unwrap();
+
+        let mut decrypter = Decrypter::new(&rsa).unwrap();
+        decrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+        decrypter.set_rsa_oaep_md(MessageDigest::sha256()).unwrap();
+        decrypter.set_rsa_mgf1_md(MessageDigest::sha256()).unwrap();
+        decrypter.set_rsa_oaep_label(b"IDENTITY\0").unwrap();
+        let mut plaintext = vec![0; decrypter.decrypt_len(&ciphertext).unwrap()];
+        let len = decrypter.decrypt(&ciphertext, &mut plaintext).unwrap();
+        assert_eq!(&plaintext[..len], b"seed");
+
+        let rsa_private = TpmtSensitive::from_private_key(&rsa, &[], &[]).unwrap();
+        let ciphertext = OpensslProvider
+            .rsa_oaep_encrypt(&rsa_public, DigestAlgorithm::Sha256, b"IDENTITY\0", b"seed")
+            .unwrap();
+        assert_eq!(
+            RustCryptoProvider
+                .rsa_oaep_decrypt(
+                    &rsa_public,
+                    &rsa_private,
+                    DigestAlgorithm::Sha256,
+                    b"IDENTITY\0",
+                    &ciphertext
+                )
+                .unwrap(),
+            b"seed"
+        );
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other_private = TpmtSensitive::from_private_key(&other, &[], &[]).unwrap();
+        for provider in [&RustCryptoProvider as &dyn CryptoProvider, &OpensslProvider] {
+            match provider.rsa_oaep_decrypt(
+                &rsa_public,
+                &other_private,
+                DigestAlgorithm::Sha256,
+                b"IDENTITY\0",
+                &ciphertext,
+            ) {
+                Err(Error::InvalidObject) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let ec = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let ec_public =
+            TpmtPublic::from_public_key(&ec, DigestAlgorithm::Sha384, attributes).unwrap();
+        let (z, x, y) = RustCryptoProvider.ecdh_ephemeral(&ec_public).unwrap();
+        assert_eq!((z.len(), x.len(), y.len()), (48, 48, 48));
+
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        let mut encoded = vec![0x04];
+        encoded.extend_from_slice(&x);
+        encoded.extend_from_slice(&y);
+        let point = EcPoint::from_bytes(&group, &encoded, &mut ctx).unwrap();
+        let ephemeral = PKey::from_ec_key(EcKey::from_public_key(&group, &point).unwrap()).unwrap();
+        let mut deriver = Deriver::new(&ec).unwrap();
+        deriver.set_peer(&ephemeral).unwrap();
+        assert_eq!(deriver.derive_to_vec().unwrap(), z);
+
+        let ec_private = TpmtSensitive::from_private_key(&ec, &[], &[]).unwrap();
+        for provider in [&RustCryptoProvider as &dyn CryptoProvider, &OpensslProvider] {
+            assert_eq!(provider.ecdh(&ec_public, &ec_private, &x, &y).unwrap(), z);
+        }
+        let other = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let other_private = TpmtSensitive::from_private_key(&other, &[], &[]).unwrap();
+        for provider in [&RustCryptoProvider as &dyn CryptoProvider, &OpensslProvider] {
+            match provider.ecdh(&ec_public, &other_private, &x, &y) {
+                Err(Error::InvalidObject) => {}
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+    }
+}
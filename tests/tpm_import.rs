@@ -0,0 +1,198 @@
+// Imports objects wrapped by this crate into a TPM and uses them there, so that the
+// wrapping is checked by a TPM instead of only by unwrap_duplicate.
+//
+// Run against a software TPM with, for example:
+//   swtpm socket --tpm2 --server type=tcp,port=2321 --ctrl type=tcp,port=2322 \
+//       --flags not-need-init,startup-clear --tpmstate dir=/tmp/swtpm &
+//   TCTI=swtpm:port=2321 cargo test --features tpm_tests --test tpm_import
+
+use std::convert::{TryFrom, TryInto};
+
+use openssl::{
+    bn::BigNum,
+    ec::{EcGroup, EcKey},
+    ecdsa::EcdsaSig,
+    hash::{hash, MessageDigest},
+    nid::Nid,
+    pkey::PKey,
+    rsa::Rsa,
+};
+use tss_esapi::{
+    constants::tss::{TPM2_RH_NULL, TPM2_ST_HASHCHECK},
+    handles::KeyHandle,
+    interface_types::{
+        algorithm::HashingAlgorithm, key_bits::RsaKeyBits, resource_handles::Hierarchy,
+        session_handles::AuthSession,
+    },
+    structures::{
+        Auth, Data, Digest, EncryptedSecret, HashScheme, Private, Public, RsaExponent, Signature,
+        SignatureScheme, SymmetricDefinitionObject,
+    },
+    traits::{Marshall, UnMarshall},
+    tss2_esys::TPMT_TK_HASHCHECK,
+    utils::create_restricted_decryption_rsa_public,
+    Context, TctiNameConf,
+};
+
+use tpmless_tpm2::{
+    DuplicationBlob, KeyImportBuilder, ObjectAttributes, SealedObjectBuilder, SymmetricDefinition,
+    TpmtPublic,
+};
+
+fn context() -> Context {
+    let tcti = TctiNameConf::from_environment_variable().expect("TCTI is not set");
+    let mut context = Context::new(tcti).unwrap();
+    context.set_sessions((Some(AuthSession::Password), None, None));
+    context
+}
+
+/// Creates an RSA 2048 storage primary key with AES-128-CFB under the owner hierarchy
+fn storage_primary(context: &mut Context) -> (KeyHandle, TpmtPublic) {
+    let template = create_restricted_decryption_rsa_public(
+        SymmetricDefinitionObject::AES_128_CFB,
+        RsaKeyBits::Rsa2048,
+        RsaExponent::default(),
+    )
+    .unwrap();
+    let primary = context
+        .create_primary(Hierarchy::Owner, template, None, None, None, None)
+        .unwrap();
+
+    let public = primary.out_public.marshall().unwrap();
+    (
+        primary.key_handle,
+        TpmtPublic::from_reader(&public[..]).unwrap(),
+    )
+}
+
+/// Runs TPM2_Import and TPM2_Load for a blob
+fn import(
+    context: &mut Context,
+    parent: KeyHandle,
+    blob: &DuplicationBlob,
+    encryption_key: Option<&[u8]>,
+) -> KeyHandle {
+    let mut public = Vec::new();
+    blob.public().to_writer(&mut public).unwrap();
+    let public = Public::unmarshall(&public).unwrap();
+
+    let symmetric_alg = match blob.symmetric_alg() {
+        SymmetricDefinition::Null => SymmetricDefinitionObject::Null,
+        SymmetricDefinition::Aes { key_bits: 128 } => SymmetricDefinitionObject::AES_128_CFB,
+        other => panic!("Unexpected inner wrapper: {:?}", other),
+    };
+
+    let private = context
+        .import(
+            parent.into(),
+            encryption_key.map(|key| Data::try_from(key.to_vec()).unwrap()),
+            public.clone(),
+            Private::try_from(blob.duplicate().to_vec()).unwrap(),
+            EncryptedSecret::try_from(blob.in_sym_seed().to_vec()).unwrap(),
+            symmetric_alg,
+        )
+        .unwrap();
+    context.load(parent, private, public).unwrap()
+}
+
+fn unseal(context: &mut Context, key: KeyHandle, auth_value: &[u8]) -> Vec<u8> {
+    context
+        .tr_set_auth(key.into(), Auth::try_from(auth_value.to_vec()).unwrap())
+        .unwrap();
+    context.unseal(key.into()).unwrap().value().to_vec()
+}
+
+#[test]
+fn test_import_sealed_object() {
+    let mut context = context();
+    let (primary, parent) = storage_primary(&mut context);
+
+    let blob = SealedObjectBuilder::new()
+        .set_auth_value(b"password")
+        .set_data(b"super secret")
+        .build(&parent)
+        .unwrap();
+    let key = import(&mut context, primary, &blob, None);
+    assert_eq!(unseal(&mut context, key, b"password"), b"super secret");
+
+    let blob = SealedObjectBuilder::new()
+        .set_auth_value(b"password")
+        .set_data(b"inner secret")
+        .set_encryption_key([0x42; 16])
+        .build(&parent)
+        .unwrap();
+    let key = import(&mut context, primary, &blob, Some(&[0x42; 16]));
+    assert_eq!(unseal(&mut context, key, b"password"), b"inner secret");
+}
+
+#[test]
+fn test_import_signing_key() {
+    let mut context = context();
+    let (primary, parent) = storage_primary(&mut context);
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ec_key = EcKey::generate(&group).unwrap();
+    let key = PKey::from_ec_key(ec_key.clone()).unwrap();
+    let blob = KeyImportBuilder::new()
+        .set_auth_value(b"password")
+        .build(&key, &parent)
+        .unwrap();
+    let handle = import(&mut context, primary, &blob, None);
+    context
+        .tr_set_auth(handle.into(), Auth::try_from(b"password".to_vec()).unwrap())
+        .unwrap();
+
+    let digest = hash(MessageDigest::sha256(), b"signed by the TPM").unwrap();
+    let validation = TPMT_TK_HASHCHECK {
+        tag: TPM2_ST_HASHCHECK,
+        hierarchy: TPM2_RH_NULL,
+        digest: Default::default(),
+    };
+    let signature = context
+        .sign(
+            handle,
+            Digest::try_from(digest.to_vec()).unwrap(),
+            SignatureScheme::EcDsa {
+                hash_scheme: HashScheme::new(HashingAlgorithm::Sha256),
+            },
+            validation.try_into().unwrap(),
+        )
+        .unwrap();
+
+    // The TPM signed with the key that was imported, not one of its own
+    let signature = match signature {
+        Signature::EcDsa(signature) => EcdsaSig::from_private_components(
+            BigNum::from_slice(signature.signature_r().value()).unwrap(),
+            BigNum::from_slice(signature.signature_s().value()).unwrap(),
+        )
+        .unwrap(),
+        other => panic!("Unexpected signature: {:?}", other),
+    };
+    assert!(signature.verify(&digest, &ec_key).unwrap());
+}
+
+#[test]
+fn test_import_storage_key() {
+    let mut context = context();
+    let (primary, parent) = storage_primary(&mut context);
+
+    // The imported storage key must carry a symmetric definition to be usable as
+    // a parent, which importing an object under it checks
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let blob = KeyImportBuilder::new()
+        .set_object_attributes(
+            ObjectAttributes::USER_WITH_AUTH
+                | ObjectAttributes::RESTRICTED
+                | ObjectAttributes::DECRYPT,
+        )
+        .build(&key, &parent)
+        .unwrap();
+    let storage = import(&mut context, primary, &blob, None);
+
+    let sealed = SealedObjectBuilder::new()
+        .set_data(b"nested secret")
+        .build(blob.public())
+        .unwrap();
+    let sealed = import(&mut context, storage, &sealed, None);
+    assert_eq!(unseal(&mut context, sealed, b""), b"nested secret");
+}